@@ -0,0 +1,11 @@
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users NOT NULL,
+    --- Only a hash of the current refresh token is stored,
+    --- it is replaced every time the session is refreshed
+    refresh_token TEXT NOT NULL UNIQUE,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_refreshed DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires DATETIME NOT NULL,
+    revoked BOOLEAN DEFAULT FALSE NOT NULL
+);
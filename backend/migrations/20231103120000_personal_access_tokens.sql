@@ -0,0 +1,20 @@
+CREATE TABLE personal_access_tokens (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users NOT NULL,
+    name TEXT NOT NULL,
+    --- Only a hash of the token is stored, the token itself
+    --- is shown once when it is created
+    token TEXT NOT NULL UNIQUE,
+    scopes INTEGER NOT NULL,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires DATETIME,
+    last_used DATETIME
+);
+
+--- When a token has any entries here it may only be used
+--- on these projects
+CREATE TABLE personal_access_token_projects (
+    token_id TEXT REFERENCES personal_access_tokens NOT NULL,
+    project_id TEXT REFERENCES projects NOT NULL,
+    UNIQUE (token_id, project_id)
+);
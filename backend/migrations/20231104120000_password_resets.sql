@@ -0,0 +1,10 @@
+CREATE TABLE password_resets (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users NOT NULL,
+    --- Only a hash of the reset token is stored, the token
+    --- itself is only ever sent to the user's email address
+    token TEXT NOT NULL UNIQUE,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires DATETIME NOT NULL,
+    used BOOLEAN DEFAULT FALSE NOT NULL
+);
@@ -0,0 +1,14 @@
+ALTER TABLE users ADD COLUMN verified BOOLEAN DEFAULT FALSE NOT NULL;
+
+CREATE TABLE email_verifications (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users NOT NULL,
+    --- The address the token was sent to, the user is only
+    --- verified if this is still their address when redeemed
+    email TEXT NOT NULL,
+    --- Only a hash of the token is stored
+    token TEXT NOT NULL UNIQUE,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires DATETIME NOT NULL,
+    used BOOLEAN DEFAULT FALSE NOT NULL
+);
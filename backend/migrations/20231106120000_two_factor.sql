@@ -0,0 +1,25 @@
+--- The base32 encoded TOTP secret, set on enrolment but only
+--- enforced once the user has confirmed it with a valid code
+ALTER TABLE users ADD COLUMN totp_secret TEXT;
+ALTER TABLE users ADD COLUMN totp_enabled BOOLEAN DEFAULT FALSE NOT NULL;
+--- The last time step a code was accepted for, codes from this
+--- step or earlier are rejected so they cannot be replayed
+ALTER TABLE users ADD COLUMN totp_last_step INTEGER;
+
+CREATE TABLE recovery_codes (
+    user_id TEXT REFERENCES users NOT NULL,
+    --- Only a hash of each code is stored
+    code TEXT NOT NULL,
+    used BOOLEAN DEFAULT FALSE NOT NULL,
+    UNIQUE(user_id, code)
+);
+
+CREATE TABLE mfa_challenges (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users NOT NULL,
+    --- Only a hash of the challenge token is stored
+    token TEXT NOT NULL UNIQUE,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires DATETIME NOT NULL,
+    used BOOLEAN DEFAULT FALSE NOT NULL
+);
@@ -0,0 +1,11 @@
+ALTER TABLE sessions ADD COLUMN ip_address TEXT;
+ALTER TABLE sessions ADD COLUMN user_agent TEXT;
+
+CREATE TABLE login_history (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users NOT NULL,
+    session_id TEXT REFERENCES sessions NOT NULL,
+    login_timestamp DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    ip_address TEXT,
+    user_agent TEXT
+);
@@ -0,0 +1,15 @@
+CREATE TABLE login_attempts (
+    id TEXT PRIMARY KEY NOT NULL,
+    --- The username or email that was entered, lower cased
+    identifier TEXT NOT NULL,
+    --- The user the identifier belonged to, if any
+    user_id TEXT REFERENCES users,
+    ip_address TEXT,
+    user_agent TEXT,
+    attempted DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    successful BOOLEAN NOT NULL
+);
+
+CREATE INDEX login_attempts_identifier ON login_attempts (identifier, attempted);
+CREATE INDEX login_attempts_user_id ON login_attempts (user_id, attempted);
+CREATE INDEX login_attempts_ip_address ON login_attempts (ip_address, attempted);
@@ -0,0 +1,9 @@
+--- Stands in for users who have deleted their account, taking
+--- over the tasks they created and the audit entries they made.
+--- The password is not a valid hash so it can never be logged in to.
+INSERT INTO users (
+    id, username, password, email, verified
+)
+VALUES (
+    '03082007', 'Deleted user', '', 'deleted-user@invalid', false
+);
@@ -0,0 +1,23 @@
+CREATE TABLE user_identities (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users NOT NULL,
+    --- The provider's issuer url and its identifier
+    --- for the user
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE (issuer, subject)
+);
+
+CREATE INDEX user_identities_user_id ON user_identities (user_id);
+
+CREATE TABLE oidc_logins (
+    id TEXT PRIMARY KEY NOT NULL,
+    --- A hash of the state sent to the provider
+    state TEXT NOT NULL UNIQUE,
+    nonce TEXT NOT NULL,
+    code_verifier TEXT NOT NULL,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires DATETIME NOT NULL,
+    used BOOLEAN DEFAULT FALSE NOT NULL
+);
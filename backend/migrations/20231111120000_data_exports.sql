@@ -0,0 +1,13 @@
+CREATE TABLE data_exports (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users NOT NULL,
+    --- Either pending, complete or failed
+    status TEXT NOT NULL,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    completed DATETIME,
+    expires DATETIME,
+    --- The JSON archive, only set once the export is complete
+    data TEXT
+);
+
+CREATE INDEX data_exports_user_id ON data_exports (user_id);
@@ -0,0 +1,6 @@
+--- Instance administrators can manage every user and
+--- project, the first is promoted with the cli
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN DEFAULT FALSE NOT NULL;
+--- Suspended users can neither log in nor use their
+--- personal access tokens
+ALTER TABLE users ADD COLUMN suspended BOOLEAN DEFAULT FALSE NOT NULL;
@@ -0,0 +1,4 @@
+--- Users who are not discoverable are left out of search
+--- results and can only be invited by their id
+ALTER TABLE users ADD COLUMN discoverable BOOLEAN DEFAULT TRUE NOT NULL;
+UPDATE users SET discoverable = FALSE WHERE id = '03082007';
@@ -0,0 +1,45 @@
+--- Tasks are created by a project membership rather than a user.
+--- SQLite cannot change a foreign key in place so the table is
+--- rebuilt, the rows referencing tasks are set aside while it is
+--- so the old table can be dropped without breaking them.
+CREATE TEMP TABLE task_edges_backup AS SELECT * FROM task_edges;
+CREATE TEMP TABLE sub_tasks_backup AS SELECT * FROM sub_tasks;
+CREATE TEMP TABLE labels_backup AS SELECT * FROM labels;
+
+DELETE FROM task_edges;
+DELETE FROM sub_tasks;
+DELETE FROM labels;
+
+CREATE TABLE tasks_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    project_id TEXT REFERENCES projects NOT NULL,
+    task_group_id TEXT REFERENCES task_groups NOT NULL,
+    name TEXT NOT NULL,
+    information TEXT,
+    creator TEXT REFERENCES project_members NOT NULL,
+    due DATETIME,
+    primary_colour TEXT NOT NULL,
+    accent_colour TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+INSERT INTO tasks_new (
+    id, project_id, task_group_id, name, information, creator,
+    due, primary_colour, accent_colour, position, created
+)
+SELECT id, project_id, task_group_id, name, information, creator,
+       due, primary_colour, accent_colour, position, created
+FROM tasks;
+
+DROP TABLE tasks;
+
+ALTER TABLE tasks_new RENAME TO tasks;
+
+INSERT INTO task_edges SELECT * FROM task_edges_backup;
+INSERT INTO sub_tasks SELECT * FROM sub_tasks_backup;
+INSERT INTO labels SELECT * FROM labels_backup;
+
+DROP TABLE task_edges_backup;
+DROP TABLE sub_tasks_backup;
+DROP TABLE labels_backup;
@@ -0,0 +1,41 @@
+--- Named sets of permissions members can be given, members
+--- without a role have had their permissions set by hand
+CREATE TABLE project_roles (
+    id TEXT PRIMARY KEY NOT NULL,
+    project_id TEXT REFERENCES projects NOT NULL,
+    name TEXT NOT NULL,
+    permissions INTEGER NOT NULL,
+    --- Ensure no project can have two roles with the
+    --- same name
+    UNIQUE (project_id, name)
+);
+
+ALTER TABLE project_members ADD COLUMN role_id TEXT REFERENCES project_roles;
+
+--- Give existing projects the same roles as new ones
+INSERT INTO project_roles (id, project_id, name, permissions)
+SELECT lower(hex(randomblob(5))), id, 'Viewer', 1
+FROM projects;
+
+INSERT INTO project_roles (id, project_id, name, permissions)
+SELECT lower(hex(randomblob(5))), id, 'Contributor', 63
+FROM projects;
+
+INSERT INTO project_roles (id, project_id, name, permissions)
+SELECT lower(hex(randomblob(5))), id, 'Maintainer', 1023
+FROM projects;
+
+--- Members whose permissions match one of the roles exactly
+--- are given it, the owner always keeps every permission
+UPDATE project_members
+SET role_id = (
+    SELECT project_roles.id
+    FROM project_roles
+    WHERE project_roles.project_id = project_members.project_id
+    AND project_roles.permissions = project_members.permissions
+)
+WHERE user_id NOT IN (
+    SELECT owner
+    FROM projects
+    WHERE projects.id = project_members.project_id
+);
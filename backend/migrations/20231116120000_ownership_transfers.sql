@@ -0,0 +1,10 @@
+--- Nominations of a member to take over a project from its
+--- owner, which are pending until the member accepts them
+CREATE TABLE ownership_transfers (
+    id TEXT PRIMARY KEY NOT NULL,
+    --- A project can only be transferred to one member
+    --- at a time
+    project_id TEXT REFERENCES projects NOT NULL UNIQUE,
+    nominee TEXT REFERENCES project_members NOT NULL,
+    created DATETIME NOT NULL
+);
@@ -0,0 +1,4 @@
+--- Archived projects are read-only and hidden from the project
+--- list, trashed projects can be restored until they are purged
+ALTER TABLE projects ADD COLUMN archived BOOLEAN DEFAULT FALSE NOT NULL;
+ALTER TABLE projects ADD COLUMN trashed DATETIME;
@@ -0,0 +1,13 @@
+--- Templates keep the structure of a project so new projects
+--- can be created from it
+CREATE TABLE project_templates (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT REFERENCES users NOT NULL,
+    name TEXT NOT NULL,
+    --- The JSON snapshot of the project's task groups, tasks,
+    --- sub-tasks and labels
+    content TEXT NOT NULL,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX project_templates_user_id ON project_templates (user_id);
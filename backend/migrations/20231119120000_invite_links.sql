@@ -0,0 +1,18 @@
+--- Links anyone logged in can use to join a project, until
+--- they expire or run out of uses
+CREATE TABLE project_invite_links (
+    id TEXT PRIMARY KEY NOT NULL,
+    project_id TEXT REFERENCES projects NOT NULL,
+    creator TEXT REFERENCES project_members NOT NULL,
+    --- Only a hash of the link's token is stored, the token
+    --- itself is shown once when the link is created
+    token TEXT NOT NULL UNIQUE,
+    role_id TEXT REFERENCES project_roles,
+    permissions INTEGER NOT NULL,
+    max_uses INTEGER,
+    uses INTEGER DEFAULT 0 NOT NULL,
+    expires DATETIME NOT NULL,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX project_invite_links_project_id ON project_invite_links (project_id);
@@ -0,0 +1,36 @@
+--- Organisations group projects and the people working on
+--- them, so members only have to be added once
+CREATE TABLE organisations (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    created DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+--- The roles new projects in the organisation start with
+CREATE TABLE organisation_roles (
+    id TEXT PRIMARY KEY NOT NULL,
+    organisation_id TEXT REFERENCES organisations NOT NULL,
+    name TEXT NOT NULL,
+    permissions INTEGER NOT NULL,
+    UNIQUE (organisation_id, name)
+);
+
+CREATE TABLE organisation_members (
+    id TEXT PRIMARY KEY NOT NULL,
+    organisation_id TEXT REFERENCES organisations NOT NULL,
+    user_id TEXT REFERENCES users NOT NULL,
+    --- Admins manage the organisation's details, members
+    --- and default roles
+    admin BOOLEAN DEFAULT FALSE NOT NULL,
+    --- The default role the member joins the organisation's
+    --- projects with
+    role_id TEXT REFERENCES organisation_roles,
+    joined DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE (organisation_id, user_id)
+);
+
+CREATE INDEX organisation_members_user_id ON organisation_members (user_id);
+
+ALTER TABLE projects ADD COLUMN organisation_id TEXT REFERENCES organisations;
+
+CREATE INDEX projects_organisation_id ON projects (organisation_id);
@@ -0,0 +1,12 @@
+--- Details describing what a project is about, shown on
+--- the project's page
+ALTER TABLE projects ADD COLUMN description TEXT DEFAULT '' NOT NULL;
+
+ALTER TABLE projects ADD COLUMN readme TEXT DEFAULT '' NOT NULL;
+
+--- The JSON list of the project's external links
+ALTER TABLE projects ADD COLUMN links TEXT DEFAULT '[]' NOT NULL;
+
+ALTER TABLE projects ADD COLUMN start_date DATE;
+
+ALTER TABLE projects ADD COLUMN target_date DATE;
@@ -0,0 +1,5 @@
+--- The hash of the refresh token the current one replaced, a
+--- replayed old token means the session has been compromised
+ALTER TABLE sessions ADD COLUMN previous_refresh_token TEXT;
+
+CREATE INDEX sessions_previous_refresh_token ON sessions (previous_refresh_token);
@@ -1,33 +1,70 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 
 use axum::Router;
 use axum::http::{Method, header};
 use tower_cookies::CookieManagerLayer;
+use tower_http::auth::AsyncRequireAuthorizationLayer;
 use tower_http::cors::{CorsLayer, Any};
 use tower_http::trace::TraceLayer;
 use tracing::info;
 
-use crate::cli::StartCommandArguments;
+use crate::cli::{StartCommandArguments, InstanceSettings};
 use crate::database::SqlPool;
 use crate::database::sql::connect;
+use crate::mail::{self, Mailer};
+use crate::oidc::{self, OidcClient};
+use crate::middleware::user_authentication::UserAuthenticationLayer;
+use crate::models::projects::Project;
+use crate::utilities::keyring::Keyring;
 
 pub mod docs;
 pub mod v1;
 
+/// How often projects which have been in the trash longer than the
+/// retention period are purged
+/// 
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 #[derive(Clone)]
 pub struct ApiContext {
-    pub pool: SqlPool
+    pub pool: SqlPool,
+    pub keyring: Arc<Keyring>,
+    pub mailer: Arc<dyn Mailer>,
+    pub oidc: Option<Arc<OidcClient>>,
+    pub settings: Arc<InstanceSettings>,
 }
 
 pub async fn init(
     StartCommandArguments {
         listen_address,
         database_url,
+        keyring_location,
+        jwt_secret,
+        mail: mail_arguments,
+        oidc: oidc_arguments,
+        settings,
         ..
     }: StartCommandArguments
 ) -> Result<(), Box<dyn std::error::Error>> {
     let pool: SqlPool = connect(database_url).await?; 
 
+    let keyring = match jwt_secret {
+        Some(secret) => Keyring::from_secret(secret),
+        None => Keyring::load_or_generate(&keyring_location)?,
+    };
+
+    let ctx = ApiContext {
+        pool,
+        keyring: Arc::new(keyring),
+        mailer: mail::configure(mail_arguments)?,
+        oidc: oidc::configure(oidc_arguments)?,
+        settings: Arc::new(settings),
+    };
+
+    tokio::spawn(purge_trash(ctx.pool.clone(), ctx.settings.trash_retention_days));
+
     let app = Router::new()
         .nest("/api/v1", v1::configure())
         .merge(docs::configure())
@@ -42,6 +79,7 @@ pub async fn init(
             .allow_methods([
                 Method::GET,
                 Method::PUT,
+                Method::PATCH,
                 Method::POST,
                 Method::DELETE,
                 Method::OPTIONS
@@ -49,9 +87,10 @@ pub async fn init(
             .allow_origin(Any)
             .max_age(Duration::from_secs(86400))
         )
+        .layer(AsyncRequireAuthorizationLayer::new(UserAuthenticationLayer::new(ctx.clone())))
         .layer(TraceLayer::new_for_http())
         .layer(CookieManagerLayer::new())
-        .with_state(ApiContext { pool });
+        .with_state(ctx);
 
     info!("Starting Nine Yards server on http://{}", listen_address);
     #[cfg(feature = "swagger")]
@@ -62,8 +101,25 @@ pub async fn init(
     info!("View the Rapidoc documentation at http://{}/rapidoc-ui", listen_address);
 
     axum::Server::bind(&listen_address)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Periodically removes projects which have been in the trash for longer
+/// than the retention period, for as long as the server runs
+/// 
+async fn purge_trash(pool: SqlPool, retention_days: i64) {
+    let mut interval = tokio::time::interval(TRASH_PURGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match Project::purge_trash(retention_days, &pool).await {
+            Ok(0) => {},
+            Ok(count) => info!("Purged {} projects from the trash", count),
+            Err(e) => tracing::error!("Failed to purge the trash: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use axum::extract::{Path, Query, State};
+use axum::routing::{get, post, delete};
+use axum::{Json, Router};
+
+use crate::api::ApiContext;
+use crate::api::v1::PageQuery;
+use crate::error::ApiError;
+use crate::middleware::extractors::Admin;
+use crate::models::id::UserId;
+use crate::models::login_attempts::LoginAttempt;
+use crate::models::projects::Project;
+use crate::models::sessions::Session;
+use crate::models::statistics::InstanceStatistics;
+use crate::models::users::{User, DELETED_USER};
+use crate::response::Result;
+
+/// Create a router to be nested on the main api router with
+/// endpoints for administrating the instance.
+///
+pub fn configure() -> Router<ApiContext> {
+    Router::new()
+        .route("/admin/users", get(get_users))
+        .route("/admin/users/:id", delete(delete_user))
+        .route("/admin/users/:id/suspend", post(suspend_user))
+        .route("/admin/users/:id/unsuspend", post(unsuspend_user))
+        .route("/admin/projects", get(get_projects))
+        .route("/admin/statistics", get(get_statistics))
+        .route("/admin/login-attempts", get(get_login_attempts))
+}
+
+#[derive(Deserialize)]
+pub struct LoginAttemptQuery {
+    /// Only include attempts for this username or email
+    ///
+    pub identifier: Option<String>,
+    /// Only include attempts from this ip address
+    ///
+    pub ip_address: Option<String>,
+}
+
+/// Lists every user on the instance, ordered by username.
+///
+/// This endpoint requires a session belonging to an administrator.
+///
+#[utoipa::path(
+    get,
+    path = "/admin/users",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(
+        ("limit" = Option<u32>, Query, description = "The maximum number of users to fetch, by default 50"),
+        ("page" = Option<u32>, Query, description = "The page to fetch"),
+    ),
+    responses(
+        (status = 200, description = "Successfully retrieved users", body = [User], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you are not an administrator"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn get_users(
+    State(ctx): State<ApiContext>,
+    Query(query): Query<PageQuery>,
+    _admin: Admin,
+) -> Result<Json<Vec<User>>> {
+    let (limit, offset) = query.limit_and_offset();
+
+    User::get_all(limit, offset, &ctx.pool)
+        .await
+        .map(Json)
+        .map_err(|error| error.into())
+}
+
+/// Suspends a user, ending all of their sessions. Suspended users
+/// cannot log in and their personal access tokens stop working
+/// until they are unsuspended.
+///
+/// This endpoint requires a session belonging to an administrator.
+///
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/suspend",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the user", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully suspended the user"),
+        (status = 400, description = "Bad request, you cannot suspend yourself"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you are not an administrator"),
+        (status = 404, description = "Not found, there is no user with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn suspend_user(
+    State(ctx): State<ApiContext>,
+    Path(user_id): Path<UserId>,
+    Admin(admin): Admin,
+) -> Result<()> {
+    if admin.id.0 == user_id.0 {
+        return Err(ApiError::BadRequest);
+    }
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    if !User::set_suspended(user_id.clone(), true, &mut transaction).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    Session::revoke_all(user_id.clone(), &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    tracing::info!("User {} was suspended by {}", user_id.0, admin.id.0);
+
+    Ok(())
+}
+
+/// Lifts a user's suspension, allowing them to log in again.
+///
+/// This endpoint requires a session belonging to an administrator.
+///
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/unsuspend",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the user", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully unsuspended the user"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you are not an administrator"),
+        (status = 404, description = "Not found, there is no user with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn unsuspend_user(
+    State(ctx): State<ApiContext>,
+    Path(user_id): Path<UserId>,
+    Admin(admin): Admin,
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    if !User::set_suspended(user_id.clone(), false, &mut transaction).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    transaction.commit().await?;
+
+    tracing::info!("User {} was unsuspended by {}", user_id.0, admin.id.0);
+
+    Ok(())
+}
+
+/// Deletes a user, in the same way as if they had deleted their own
+/// account. Users who still own projects cannot be deleted until
+/// the projects are deleted or transferred, projects they own in the
+/// trash are permanently removed.
+///
+/// This endpoint requires a session belonging to an administrator.
+///
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the user", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully deleted the user"),
+        (status = 400, description = "Bad request, delete your own account from /users instead"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you are not an administrator"),
+        (status = 404, description = "Not found, there is no user with this id"),
+        (status = 409, description = "Conflict, the user still owns projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn delete_user(
+    State(ctx): State<ApiContext>,
+    Path(user_id): Path<UserId>,
+    Admin(admin): Admin,
+) -> Result<()> {
+    if admin.id.0 == user_id.0 {
+        return Err(ApiError::BadRequest);
+    }
+
+    if user_id.0 == DELETED_USER {
+        return Err(ApiError::NotFound);
+    }
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let user = User::get(user_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    user.remove(true, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    tracing::info!("User {} was deleted by {}", user.id.0, admin.id.0);
+
+    Ok(())
+}
+
+/// Lists every project on the instance, ordered by name, regardless
+/// of whether the administrator is a member.
+///
+/// This endpoint requires a session belonging to an administrator.
+///
+#[utoipa::path(
+    get,
+    path = "/admin/projects",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(
+        ("limit" = Option<u32>, Query, description = "The maximum number of projects to fetch, by default 50"),
+        ("page" = Option<u32>, Query, description = "The page to fetch"),
+    ),
+    responses(
+        (status = 200, description = "Successfully retrieved projects", body = [Project], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you are not an administrator"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn get_projects(
+    State(ctx): State<ApiContext>,
+    Query(query): Query<PageQuery>,
+    _admin: Admin,
+) -> Result<Json<Vec<Project>>> {
+    let (limit, offset) = query.limit_and_offset();
+
+    Project::get_all(limit, offset, &ctx.pool)
+        .await
+        .map(Json)
+        .map_err(|error| error.into())
+}
+
+/// Counts the users, projects, tasks and active sessions on the
+/// instance.
+///
+/// This endpoint requires a session belonging to an administrator.
+///
+#[utoipa::path(
+    get,
+    path = "/admin/statistics",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully retrieved statistics", body = InstanceStatistics, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you are not an administrator"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn get_statistics(
+    State(ctx): State<ApiContext>,
+    _admin: Admin,
+) -> Result<Json<InstanceStatistics>> {
+    InstanceStatistics::get(&ctx.pool)
+        .await
+        .map(Json)
+        .map_err(|error| error.into())
+}
+
+/// Lists the most recent attempts to log in, successful or not, so
+/// administrators can review accounts or addresses being targeted.
+///
+/// This endpoint requires a session belonging to an administrator.
+///
+#[utoipa::path(
+    get,
+    path = "/admin/login-attempts",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(
+        ("identifier" = Option<String>, Query, description = "Only include attempts for this username or email"),
+        ("ip_address" = Option<String>, Query, description = "Only include attempts from this ip address"),
+    ),
+    responses(
+        (status = 200, description = "Successfully retrieved login attempts", body = [LoginAttempt], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you are not an administrator"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn get_login_attempts(
+    State(ctx): State<ApiContext>,
+    Query(query): Query<LoginAttemptQuery>,
+    _admin: Admin,
+) -> Result<Json<Vec<LoginAttempt>>> {
+    LoginAttempt::get_recent(
+        query.identifier.as_deref(),
+        query.ip_address.as_deref(),
+        &ctx.pool
+    )
+    .await
+    .map(Json)
+    .map_err(|error| error.into())
+}
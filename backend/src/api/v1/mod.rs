@@ -8,6 +8,9 @@ pub mod projects;
 pub mod task_groups;
 pub mod tasks;
 pub mod sub_tasks;
+pub mod templates;
+pub mod organisations;
+pub mod admin;
 
 pub fn configure() -> Router<ApiContext> {
     Router::new()
@@ -16,6 +19,9 @@ pub fn configure() -> Router<ApiContext> {
         .merge(task_groups::configure())
         .merge(tasks::configure())
         .merge(sub_tasks::configure())
+        .merge(templates::configure())
+        .merge(organisations::configure())
+        .merge(admin::configure())
 }
 
 #[derive(Deserialize)]
@@ -60,3 +66,25 @@ impl From<FetchQuery> for FetchOptions {
         }
     }
 }
+
+#[derive(Deserialize)]
+pub struct PageQuery {
+    /// The maximum number of results to fetch, by default 50
+    ///
+    pub limit: Option<u32>,
+    /// The page to fetch, the offset is calculated from the
+    /// limit (limit * page)
+    ///
+    pub page: Option<u32>,
+}
+
+impl PageQuery {
+    /// The limit and offset to fetch with, the limit is capped so a
+    /// single request cannot read the entire instance
+    ///
+    pub fn limit_and_offset(&self) -> (u32, u32) {
+        let limit = self.limit.unwrap_or(50).min(200);
+
+        (limit, limit.saturating_mul(self.page.unwrap_or(0)))
+    }
+}
@@ -0,0 +1,572 @@
+use axum::extract::{State, Path, Query};
+use axum::routing::{get, patch};
+use axum::{Router, Json};
+
+use crate::error::ApiError;
+use crate::middleware::user_authentication::Credentials;
+use crate::models::id::{OrganisationId, OrganisationMemberId, OrganisationRoleId};
+use crate::models::organisations::{
+    Organisation, OrganisationBuilder, EditOrganisation, OrganisationMember, AddOrganisationMember,
+    EditOrganisationMember, OrganisationRole, OrganisationRoleBuilder, EditOrganisationRole,
+};
+use crate::models::projects::{Project, ListProjects};
+use crate::response::Result;
+use crate::api::ApiContext;
+
+/// Create a router to be nested on the main api router with
+/// endpoints for managing organisations, their members and the
+/// default roles of their projects
+///
+pub fn configure() -> Router<ApiContext> {
+    Router::new()
+        .route(
+            "/organisations",
+            get(get_organisations)
+            .post(create_organisation)
+        )
+        .route(
+            "/organisations/:id",
+            get(get_organisation)
+            .patch(edit_organisation)
+            .delete(remove_organisation)
+        )
+        .route("/organisations/:id/projects",
+            get(get_projects)
+        )
+        .route("/organisations/:id/members",
+            get(get_members)
+            .post(add_member)
+        )
+        .route("/organisations/:id/members/:member_id",
+            patch(edit_member)
+            .delete(remove_member)
+        )
+        .route("/organisations/:id/roles",
+            get(get_roles)
+            .post(create_role)
+        )
+        .route("/organisations/:id/roles/:role_id",
+            patch(edit_role)
+            .delete(remove_role)
+        )
+}
+
+/// Fetches the organisations the logged in user is a member of,
+/// ordered by name.
+///
+/// This endpoint requires a bearer token.
+///
+#[utoipa::path(
+    get,
+    path = "/organisations",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully retrieved the organisations", body = [Organisation], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn get_organisations(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+) -> Result<Json<Vec<Organisation>>> {
+    credentials.require_unrestricted()?;
+
+    Organisation::get_many_from_user(credentials.user_id().clone(), &ctx.pool)
+        .await
+        .map_err(|error| error.into())
+        .map(Json)
+}
+
+/// Creates an organisation with the logged in user as its first
+/// admin.
+///
+/// This endpoint requires a bearer token.
+///
+#[utoipa::path(
+    post,
+    path = "/organisations",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = OrganisationBuilder, description = "The organisation's name", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully created the organisation", body = Organisation, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 422, description = "Unprocessable entity, the name is invalid"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn create_organisation(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+    Json(form): Json<OrganisationBuilder>,
+) -> Result<Json<Organisation>> {
+    credentials.require_unrestricted()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let organisation = Organisation::create(form, credentials.user_id().clone(), &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(organisation))
+}
+
+/// Fetches an organisation by its id.
+///
+/// This endpoint requires a bearer token belonging to a member of
+/// the organisation.
+///
+#[utoipa::path(
+    get,
+    path = "/organisations/{id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully retrieved the organisation", body = Organisation, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't a member of this organisation or the access token is restricted to certain projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn get_organisation(
+    State(ctx): State<ApiContext>,
+    membership: OrganisationMember,
+) -> Result<Json<Organisation>> {
+    Organisation::get(membership.organisation_id, &ctx.pool)
+        .await?
+        .ok_or(ApiError::Forbidden)
+        .map(Json)
+}
+
+/// Renames an organisation.
+///
+/// This endpoint requires a bearer token belonging to an admin of
+/// the organisation.
+///
+#[utoipa::path(
+    patch,
+    path = "/organisations/{id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = EditOrganisation, description = "The organisation's new name", content_type = "application/json"),
+    params(("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully changed the organisation", body = Organisation, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't an admin of this organisation or the access token is restricted to certain projects"),
+        (status = 422, description = "Unprocessable entity, the name is invalid"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn edit_organisation(
+    State(ctx): State<ApiContext>,
+    membership: OrganisationMember,
+    Json(form): Json<EditOrganisation>,
+) -> Result<Json<Organisation>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let mut organisation = Organisation::get(membership.organisation_id.clone(), &mut *transaction)
+        .await?
+        .ok_or(ApiError::Forbidden)?;
+
+    organisation.edit(form, &membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(organisation))
+}
+
+/// Removes an organisation along with its memberships and roles.
+/// The organisation's projects have to be deleted and purged from
+/// the trash first.
+///
+/// This endpoint requires a bearer token belonging to an admin of
+/// the organisation.
+///
+#[utoipa::path(
+    delete,
+    path = "/organisations/{id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully removed the organisation"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't an admin of this organisation or the access token is restricted to certain projects"),
+        (status = 409, description = "Conflict, the organisation still has projects, including any in the trash"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn remove_organisation(
+    State(ctx): State<ApiContext>,
+    membership: OrganisationMember,
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let organisation = Organisation::get(membership.organisation_id.clone(), &mut *transaction)
+        .await?
+        .ok_or(ApiError::Forbidden)?;
+
+    organisation.remove(&membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Fetches every project of the organisation ordered by name, whether
+/// or not the logged in user is a member of it. Projects in the trash
+/// are never listed and archived projects are only listed when
+/// `include_archived` is set.
+///
+/// This endpoint requires a bearer token belonging to a member of
+/// the organisation.
+///
+#[utoipa::path(
+    get,
+    path = "/organisations/{id}/projects",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8),
+        ("include_archived" = Option<bool>, Query, description = "Whether to include archived projects, by default false"),
+    ),
+    responses(
+        (status = 200, description = "Successfully retrieved the organisation's projects", body = [Project], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't a member of this organisation or the access token is restricted to certain projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn get_projects(
+    State(ctx): State<ApiContext>,
+    membership: OrganisationMember,
+    Query(query): Query<ListProjects>,
+) -> Result<Json<Vec<Project>>> {
+    let include_archived = query.include_archived.unwrap_or(false);
+
+    Project::get_many_from_organisation(membership.organisation_id, include_archived, &ctx.pool)
+        .await
+        .map_err(|error| error.into())
+        .map(Json)
+}
+
+/// Fetches the organisation's members in the order they joined.
+///
+/// This endpoint requires a bearer token belonging to a member of
+/// the organisation.
+///
+#[utoipa::path(
+    get,
+    path = "/organisations/{id}/members",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully retrieved the organisation's members", body = [OrganisationMember], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't a member of this organisation or the access token is restricted to certain projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn get_members(
+    State(ctx): State<ApiContext>,
+    membership: OrganisationMember,
+) -> Result<Json<Vec<OrganisationMember>>> {
+    OrganisationMember::get_many_from_organisation(membership.organisation_id, &ctx.pool)
+        .await
+        .map_err(|error| error.into())
+        .map(Json)
+}
+
+/// Adds a user to the organisation, they are invited to every one of
+/// its projects with their organisation role and are notified.
+///
+/// This endpoint requires a bearer token belonging to an admin of
+/// the organisation.
+///
+#[utoipa::path(
+    post,
+    path = "/organisations/{id}/members",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = AddOrganisationMember, description = "The user to add and their role", content_type = "application/json"),
+    params(("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully added the member", body = OrganisationMember, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't an admin of this organisation or the access token is restricted to certain projects"),
+        (status = 404, description = "Not found, the user or role does not exist"),
+        (status = 409, description = "Conflict, the user is already a member"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn add_member(
+    State(ctx): State<ApiContext>,
+    membership: OrganisationMember,
+    Json(form): Json<AddOrganisationMember>,
+) -> Result<Json<OrganisationMember>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let member = OrganisationMember::add(form, &membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(member))
+}
+
+/// Changes a member's organisation role or whether they are an admin.
+/// A new role applies to the projects the member is invited to from
+/// then on.
+///
+/// This endpoint requires a bearer token belonging to an admin of
+/// the organisation.
+///
+#[utoipa::path(
+    patch,
+    path = "/organisations/{id}/members/{member_id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = EditOrganisationMember, description = "The member's new role or admin status", content_type = "application/json"),
+    params(
+        ("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8),
+        ("member_id" = String, Path, description = "The id of the membership", max_length = 8, min_length = 8),
+    ),
+    responses(
+        (status = 200, description = "Successfully changed the member", body = OrganisationMember, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't an admin of this organisation or the access token is restricted to certain projects"),
+        (status = 404, description = "Not found, the organisation has no member or role with this id"),
+        (status = 409, description = "Conflict, the organisation would be left without an admin"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn edit_member(
+    State(ctx): State<ApiContext>,
+    Path((organisation_id, member_id)): Path<(OrganisationId, OrganisationMemberId)>,
+    membership: OrganisationMember,
+    Json(form): Json<EditOrganisationMember>,
+) -> Result<Json<OrganisationMember>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let mut member = OrganisationMember::get(member_id, organisation_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    member.edit(form, &membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(member))
+}
+
+/// Removes a member from the organisation and each of its projects,
+/// the tasks they created are kept. Members can remove themselves to
+/// leave the organisation.
+///
+/// This endpoint requires a bearer token belonging to an admin of
+/// the organisation, or to the member being removed.
+///
+#[utoipa::path(
+    delete,
+    path = "/organisations/{id}/members/{member_id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8),
+        ("member_id" = String, Path, description = "The id of the membership", max_length = 8, min_length = 8),
+    ),
+    responses(
+        (status = 200, description = "Successfully removed the member"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't an admin of this organisation or the access token is restricted to certain projects"),
+        (status = 404, description = "Not found, the organisation has no member with this id"),
+        (status = 409, description = "Conflict, the member is the last admin or still owns projects of the organisation"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn remove_member(
+    State(ctx): State<ApiContext>,
+    Path((organisation_id, member_id)): Path<(OrganisationId, OrganisationMemberId)>,
+    membership: OrganisationMember,
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let member = OrganisationMember::get(member_id, organisation_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    member.remove(&membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Fetches the organisation's default roles, ordered by name.
+///
+/// This endpoint requires a bearer token belonging to a member of
+/// the organisation.
+///
+#[utoipa::path(
+    get,
+    path = "/organisations/{id}/roles",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully retrieved the organisation's roles", body = [OrganisationRole], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't a member of this organisation or the access token is restricted to certain projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn get_roles(
+    State(ctx): State<ApiContext>,
+    membership: OrganisationMember,
+) -> Result<Json<Vec<OrganisationRole>>> {
+    OrganisationRole::get_many_from_organisation(membership.organisation_id, &ctx.pool)
+        .await
+        .map_err(|error| error.into())
+        .map(Json)
+}
+
+/// Creates a default role in the organisation, projects created in
+/// the organisation from then on start with a copy of it.
+///
+/// This endpoint requires a bearer token belonging to an admin of
+/// the organisation.
+///
+#[utoipa::path(
+    post,
+    path = "/organisations/{id}/roles",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = OrganisationRoleBuilder, description = "The role's name and permissions", content_type = "application/json"),
+    params(("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully created the role", body = OrganisationRole, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't an admin of this organisation or the access token is restricted to certain projects"),
+        (status = 422, description = "Unprocessable entity, the name is taken or invalid"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn create_role(
+    State(ctx): State<ApiContext>,
+    membership: OrganisationMember,
+    Json(form): Json<OrganisationRoleBuilder>,
+) -> Result<Json<OrganisationRole>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let role = OrganisationRole::create(form, &membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(role))
+}
+
+/// Renames a default role or changes its permissions. Copies of the
+/// role in existing projects are left unchanged.
+///
+/// This endpoint requires a bearer token belonging to an admin of
+/// the organisation.
+///
+#[utoipa::path(
+    patch,
+    path = "/organisations/{id}/roles/{role_id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = EditOrganisationRole, description = "The role's new name or permissions", content_type = "application/json"),
+    params(
+        ("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8),
+        ("role_id" = String, Path, description = "The id of the role", max_length = 10, min_length = 10),
+    ),
+    responses(
+        (status = 200, description = "Successfully changed the role", body = OrganisationRole, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't an admin of this organisation or the access token is restricted to certain projects"),
+        (status = 404, description = "Not found, the organisation has no role with this id"),
+        (status = 422, description = "Unprocessable entity, the name is taken or invalid"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn edit_role(
+    State(ctx): State<ApiContext>,
+    Path((organisation_id, role_id)): Path<(OrganisationId, OrganisationRoleId)>,
+    membership: OrganisationMember,
+    Json(form): Json<EditOrganisationRole>,
+) -> Result<Json<OrganisationRole>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let mut role = OrganisationRole::get(role_id, organisation_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    role.edit(form, &membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(role))
+}
+
+/// Removes a default role from the organisation. Members with the
+/// role are invited to projects with the same permissions as a new
+/// member from then on.
+///
+/// This endpoint requires a bearer token belonging to an admin of
+/// the organisation.
+///
+#[utoipa::path(
+    delete,
+    path = "/organisations/{id}/roles/{role_id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The id of the organisation", max_length = 8, min_length = 8),
+        ("role_id" = String, Path, description = "The id of the role", max_length = 10, min_length = 10),
+    ),
+    responses(
+        (status = 200, description = "Successfully removed the role"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't an admin of this organisation or the access token is restricted to certain projects"),
+        (status = 404, description = "Not found, the organisation has no role with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn remove_role(
+    State(ctx): State<ApiContext>,
+    Path((organisation_id, role_id)): Path<(OrganisationId, OrganisationRoleId)>,
+    membership: OrganisationMember,
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let role = OrganisationRole::get(role_id, organisation_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    role.remove(&membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
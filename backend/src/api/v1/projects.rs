@@ -1,12 +1,20 @@
-use axum::extract::{State, Path};
-use axum::routing::get;
+use axum::extract::{State, Path, Query};
+use axum::routing::{delete, get, patch, post};
 use axum::{Router, Json};
 
 use crate::models::audits::Audit;
-use crate::models::projects::{ProjectBuilder, Project, ProjectMember, Permissions, EditProject};
-use crate::models::id::{UserId, ProjectId};
+use crate::middleware::user_authentication::Credentials;
+use crate::middleware::extractors::require_two_factor;
+use crate::models::projects::{ProjectBuilder, Project, ProjectMember, Permissions, EditProject, InviteMembers, EditMember, RemoveMember, ListProjects, CloneProject};
+use crate::models::id::{ProjectId, ProjectMemberId, ProjectRoleId, InviteLinkId};
+use crate::models::roles::{ProjectRole, RoleBuilder, EditRole};
+use crate::models::ownership_transfers::{OwnershipTransfer, NominateOwner};
+use crate::models::templates::{ProjectTemplate, TemplateBuilder};
+use crate::models::invite_links::{InviteLink, InviteLinkBuilder, CreatedInviteLink, JoinProject};
+use crate::models::two_factor::TwoFactor;
 use crate::error::ApiError;
 use crate::models::tasks::{TaskGroup, TaskGroupBuilder};
+use crate::models::users::{User, DELETED_USER};
 use crate::response::Result;
 use crate::api::ApiContext;
 
@@ -22,12 +30,35 @@ pub fn configure() -> Router<ApiContext> {
             get(get_memberships_from_user)
             .post(create_project)
         )
+        .route(
+            "/projects/trash", 
+            get(get_trash)
+        )
+        .route(
+            "/projects/join", 
+            post(join_project)
+        )
         .route(
             "/projects/:id", 
             get(get_project_by_id)
             .put(update_project)
             .delete(remove_project)
         )
+        .route("/projects/:id/archive", 
+            post(archive_project)
+        )
+        .route("/projects/:id/unarchive", 
+            post(unarchive_project)
+        )
+        .route("/projects/:id/restore", 
+            post(restore_project)
+        )
+        .route("/projects/:id/clone", 
+            post(clone_project)
+        )
+        .route("/projects/:id/template", 
+            post(save_template)
+        )
         .route("/projects/:id/audits", 
             get(get_audits)
         )
@@ -35,6 +66,42 @@ pub fn configure() -> Router<ApiContext> {
             get(get_members)
             .post(invite_member)
         )
+        .route("/projects/:id/members/:member_id", 
+            patch(edit_member)
+            .delete(remove_member)
+        )
+        .route("/projects/:id/invite-links", 
+            get(get_invite_links)
+            .post(create_invite_link)
+        )
+        .route("/projects/:id/invite-links/:link_id", 
+            delete(revoke_invite_link)
+        )
+        .route("/projects/:id/invitation/accept", 
+            post(accept_invitation)
+        )
+        .route("/projects/:id/invitation/deny", 
+            post(deny_invitation)
+        )
+        .route("/projects/:id/transfer", 
+            get(get_transfer)
+            .post(transfer_project)
+            .delete(cancel_transfer)
+        )
+        .route("/projects/:id/transfer/accept", 
+            post(accept_transfer)
+        )
+        .route("/projects/:id/transfer/deny", 
+            post(deny_transfer)
+        )
+        .route("/projects/:id/roles", 
+            get(get_roles)
+            .post(create_role)
+        )
+        .route("/projects/:id/roles/:role_id", 
+            patch(edit_role)
+            .delete(remove_role)
+        )
         .route("/projects/:id/task-groups",
             get(get_task_groups)
             .post(create_task_group)
@@ -46,6 +113,9 @@ pub fn configure() -> Router<ApiContext> {
 /// the request will still return a success response with an empty
 /// array in the body.
 /// 
+/// Projects in the trash are never listed and archived projects
+/// are only listed when `include_archived` is set.
+/// 
 /// This endpoint requires a bearer token in order to retreive a
 /// given user's memberships.
 /// 
@@ -54,6 +124,7 @@ pub fn configure() -> Router<ApiContext> {
     path = "/projects",
     context_path = "/api/v1",
     tag = "v1",
+    params(("include_archived" = Option<bool>, Query, description = "Whether to include archived projects, by default false")),
     responses(
         (status = 200, description = "Successfully retrieved project memberships", body = [ProjectMember], content_type = "application/json"),
         (status = 401, description = "Unauthorized, provide a bearer token"),
@@ -63,12 +134,57 @@ pub fn configure() -> Router<ApiContext> {
 )]
 async fn get_memberships_from_user(
     State(ctx): State<ApiContext>,
-    user_id: UserId,
+    credentials: Credentials,
+    Query(query): Query<ListProjects>,
 ) -> Result<Json<Vec<ProjectMember>>> {
-    ProjectMember::get_many_from_user(user_id, &ctx.pool)
-        .await
-        .map_err(|error| error.into())
-        .map(|memberships| Json(memberships))
+    let include_archived = query.include_archived.unwrap_or(false);
+
+    let memberships = ProjectMember::get_many_listed_from_user(
+        credentials.user_id().clone(),
+        include_archived,
+        &ctx.pool
+    ).await?;
+
+    // Access tokens limited to some projects only see those projects
+    Ok(Json(
+        memberships
+            .into_iter()
+            .filter(|membership| credentials.check_project(&membership.project_id).is_ok())
+            .collect()
+    ))
+}
+
+/// Fetches the projects in the trash which the logged in user can
+/// restore, that is projects they have permission to delete. They
+/// are ordered with the most recently trashed first.
+/// 
+/// This endpoint requires a bearer token.
+/// 
+#[utoipa::path(
+    get,
+    path = "/projects/trash",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully retrieved the trashed projects", body = [Project], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn get_trash(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+) -> Result<Json<Vec<Project>>> {
+    let projects = Project::get_trashed_from_user(credentials.user_id().clone(), &ctx.pool).await?;
+
+    // Access tokens limited to some projects only see those projects
+    Ok(Json(
+        projects
+            .into_iter()
+            .filter(|project| credentials.check_project(&project.id).is_ok())
+            .collect()
+    ))
 }
 
 /// Fetches a project by it's id.
@@ -99,16 +215,15 @@ async fn get_project_by_id(
     Project::get(project_id, &ctx.pool)
         .await?
         .ok_or(ApiError::Forbidden) // Prevent checking weather a task exists in another project
-        .map(|project| Json(project))
+        .map(Json)
 }
 
 /// Gets all audits on a project within (by default) the last 7
 /// days. By default these are in reverse chronological order so
 /// the newest audits are shown first.
 /// 
-/// If the project is public then this endpoint requires no
-/// authentication, if it is private then a membership of the
-/// project is required. 
+/// This endpoint requires a membership of the project, visitors
+/// to public projects cannot see its audits.
 /// 
 #[utoipa::path(
     get,
@@ -119,19 +234,24 @@ async fn get_project_by_id(
     responses(
         (status = 200, description = "Successfully retrieved roject audits", body = [Audit], content_type = "application/json"),
         (status = 401, description = "Unauthorized, provide a bearer token"),
-        (status = 403, description = "Forbidden, you don't have permission to access this project"),
+        (status = 403, description = "Forbidden, you aren't a member of this project"),
         (status = 500, description = "Internal server error")
     ),
-    security((), ("Bearer" = []))
+    security(("Bearer" = []))
 )]
 async fn get_audits(
     State(ctx): State<ApiContext>,
     Path(project_id): Path<ProjectId>,
-    _membership: ProjectMember,
+    membership: ProjectMember,
 ) -> Result<Json<Vec<Audit>>> {
+    // Visitors to public projects cannot see its history
+    if !membership.accepted {
+        return Err(ApiError::Forbidden);
+    }
+
     Audit::get_many_from_project(project_id, &ctx.pool)
         .await
-        .map(|audits| Json(audits))
+        .map(Json)
         .map_err(|error| error.into())
 }
 
@@ -144,23 +264,128 @@ async fn get_audits(
     responses(
         (status = 200, description = "Successfully created a project", body = Project, content_type = "application/json"),
         (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 404, description = "Not found, you have no template with the given id"),
         (status = 500, description = "Internal server error")
     ),
     security(("Bearer" = []))
 )]
 async fn create_project(
     State(ctx): State<ApiContext>,
-    user_id: UserId,
+    credentials: Credentials,
     Json(form): Json<ProjectBuilder>,
 ) -> Result<Json<Project>> {
+    credentials.require_unrestricted()?;
+
     let mut transaction = ctx.pool.begin().await?;
     
-    let project = Project::create(form, user_id, &mut transaction).await?;
+    let project = Project::create(form, credentials.user_id().clone(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(Json(project))
+}
+
+/// Creates a new project owned by the logged in user as a copy of
+/// this one. By default the task groups, tasks, sub-tasks and labels
+/// are copied, the members can be invited to the copy as well.
+/// 
+/// This endpoint requires a bearer token belonging to a member who
+/// can read the project, and who can invite members to it if its
+/// members are copied.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/clone",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = CloneProject, description = "Details of the new project and what to copy", content_type = "application/json"),
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully cloned the project", body = Project, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't a member of this project, don't have permission to clone it or the access token is restricted to certain projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn clone_project(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    credentials: Credentials,
+    membership: ProjectMember,
+    Json(form): Json<CloneProject>,
+) -> Result<Json<Project>> {
+    credentials.require_unrestricted()?;
+
+    // Visitors to public projects cannot copy them
+    if !membership.accepted {
+        return Err(ApiError::Forbidden);
+    }
+
+    membership.check_permissions(Permissions::READ_PROJECT)?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let source = Project::get(project_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::Forbidden)?;
+
+    let project = Project::create_clone(&source, form, &membership, &mut transaction).await?;
+
     transaction.commit().await?;
 
     Ok(Json(project))
 }
 
+/// Saves the project's task groups, tasks, sub-tasks and labels as
+/// a template belonging to the logged in user, new projects can
+/// then be created from it. Due dates, assignees and completed
+/// sub-tasks are cleared.
+/// 
+/// This endpoint requires a bearer token belonging to a member who
+/// can read the project.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/template",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = TemplateBuilder, description = "The template's name", content_type = "application/json"),
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully saved the template", body = ProjectTemplate, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you aren't a member of this project, don't have permission to read it or the access token is restricted to certain projects"),
+        (status = 422, description = "Unprocessable entity, the name is not 1 to 30 characters"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn save_template(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+    membership: ProjectMember,
+    Json(form): Json<TemplateBuilder>,
+) -> Result<Json<ProjectTemplate>> {
+    // Templates belong to the account rather than the project
+    credentials.require_unrestricted()?;
+
+    // Visitors to public projects cannot copy them
+    if !membership.accepted {
+        return Err(ApiError::Forbidden);
+    }
+
+    membership.check_permissions(Permissions::READ_PROJECT)?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let template = ProjectTemplate::create(form, &membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(template))
+}
+
 #[utoipa::path(
     get,
     path = "/projects/{id}/audits",
@@ -170,112 +395,1036 @@ async fn create_project(
     responses(
         (status = 200, description = "Successfully retrieved project audits", body = [Audit], content_type = "application/json"),
         (status = 401, description = "Unauthorized, provide a bearer token"),
-        (status = 403, description = "Forbidden, you don't have permission to access this project's memberships"),
+        (status = 403, description = "Forbidden, you aren't a member of this project"),
         (status = 500, description = "Internal server error")
     ),
-    security((), ("Bearer" = []))
+    security(("Bearer" = []))
 )]
 async fn get_members(
     State(ctx): State<ApiContext>,
     Path(project_id): Path<ProjectId>,
-    _membership: ProjectMember,
+    membership: ProjectMember,
 ) -> Result<Json<Vec<ProjectMember>>> {
+    // Visitors to public projects cannot see who its members are
+    if !membership.accepted {
+        return Err(ApiError::Forbidden);
+    }
+
     ProjectMember::get_many_from_project(project_id, &ctx.pool)
         .await
-        .map(|members| Json(members))
+        .map(Json)
         .map_err(|error| error.into())
 }
 
+/// Invites users to the project with the given role, or with the given
+/// permissions capped at the inviter's own. The invitations are pending
+/// until accepted, each user is sent a notification with actions to
+/// accept or deny it.
+/// 
+/// This endpoint requires a bearer token belonging to a member with
+/// permission to invite members.
+/// 
 #[utoipa::path(
     post,
     path = "/projects/{id}/members",
     context_path = "/api/v1",
     tag = "v1",
-    request_body(content = [String], description = "The users id's to invite", content_type = "application/json"),
+    request_body(content = InviteMembers, description = "The users to invite and their role or permissions", content_type = "application/json"),
     params(("id" = String, Path, description = "The id of the project to invite a user to", max_length = 8, min_length = 8)),
     responses(
-        (status = 200, description = "Successfully invited member and sent invitation notification"),
+        (status = 200, description = "Successfully invited members and sent invitation notifications", body = [ProjectMember], content_type = "application/json"),
         (status = 401, description = "Unauthorized, provide a bearer token"),
-        (status = 403, description = "Forbidden, you don't have permission to invite a member to this project"),
+        (status = 403, description = "Forbidden, you don't have permission to invite a member to this project or give this role, or an email address is unverified"),
+        (status = 404, description = "Not found, one of the users or the role does not exist"),
         (status = 500, description = "Internal server error")
     ),
     security(("Bearer" = []))
 )]
 async fn invite_member(
     State(ctx): State<ApiContext>,
-    Path(project_id): Path<ProjectId>,
     membership: ProjectMember,
-    Json(user_ids): Json<Vec<UserId>>,
-) -> Result<()> {
+    Json(form): Json<InviteMembers>,
+) -> Result<Json<Vec<ProjectMember>>> {
     let mut transaction = ctx.pool.begin().await?;
 
     if !membership.permissions.contains(Permissions::INVITE_MEMBERS) {
         return Err(ApiError::Forbidden);
     }
+
+    if ctx.settings.require_verified_email {
+        // Both the inviter and everyone they invite must have
+        // verified their email addresses
+        let user_ids = std::iter::once(&membership.user_id).chain(form.user_ids.iter());
+
+        for user_id in user_ids {
+            let user = User::get(user_id.clone(), &mut *transaction)
+                .await?
+                .ok_or(ApiError::NotFound)?;
+
+            if !user.verified {
+                return Err(ApiError::EmailNotVerified);
+            }
+        }
+    }
     
-    ProjectMember::invite_users(user_ids, project_id, &mut transaction).await?;
+    let invitations = ProjectMember::invite_users(form, &membership, &mut transaction).await?;
 
     transaction.commit().await?;
 
-    Ok(())
+    Ok(Json(invitations))
+}
+
+/// Gives a member of the project a role, or sets their permissions
+/// by hand which overrides their role. Members can only give or take
+/// away permissions they hold themselves, and the owner's permissions
+/// cannot be changed. The change is recorded in the project's audit
+/// log.
+/// 
+/// This endpoint requires a bearer token belonging to a member with
+/// permission to both invite and remove members.
+/// 
+#[utoipa::path(
+    patch,
+    path = "/projects/{id}/members/{member_id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = EditMember, description = "The member's new role or permissions", content_type = "application/json"),
+    params(
+        ("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8),
+        ("member_id" = String, Path, description = "The id of the membership", max_length = 8, min_length = 8),
+    ),
+    responses(
+        (status = 200, description = "Successfully changed the member's permissions", body = ProjectMember, content_type = "application/json"),
+        (status = 400, description = "Bad request, give either a role or permissions"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you can't change these permissions or this member's permissions"),
+        (status = 404, description = "Not found, the project has no member or role with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn edit_member(
+    State(ctx): State<ApiContext>,
+    Path((project_id, member_id)): Path<(ProjectId, ProjectMemberId)>,
+    membership: ProjectMember,
+    Json(form): Json<EditMember>,
+) -> Result<Json<ProjectMember>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let mut member = ProjectMember::get(member_id, &mut *transaction)
+        .await?
+        .filter(|member| member.project_id.0 == project_id.0 && member.user_id.0 != DELETED_USER)
+        .ok_or(ApiError::NotFound)?;
+
+    match (form.role_id, form.permissions) {
+        (Some(role_id), None) => {
+            let role = ProjectRole::get(role_id, project_id, &mut *transaction)
+                .await?
+                .ok_or(ApiError::NotFound)?;
+
+            member.set_role(&role, &membership, &mut transaction).await?;
+        }
+        (None, Some(permissions)) => {
+            member.set_permissions(permissions, &membership, &mut transaction).await?;
+        }
+        _ => return Err(ApiError::BadRequest),
+    }
+
+    transaction.commit().await?;
+
+    Ok(Json(member))
 }
 
+/// Removes a member from the project, or withdraws a pending
+/// invitation. The tasks the member created are kept unless
+/// `delete_tasks` is set, which requires permission to delete
+/// tasks. The owner cannot be removed, and the removed user is
+/// notified.
+/// 
+/// This endpoint requires a bearer token belonging to a member with
+/// permission to remove members.
+/// 
 #[utoipa::path(
     delete,
-    path = "/projects/{id}",
+    path = "/projects/{id}/members/{member_id}",
     context_path = "/api/v1",
     tag = "v1",
-    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    params(
+        ("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8),
+        ("member_id" = String, Path, description = "The id of the membership", max_length = 8, min_length = 8),
+        ("delete_tasks" = Option<bool>, Query, description = "Delete the tasks the member created instead of keeping them"),
+    ),
     responses(
-        (status = 200, description = "Successfully removed a project"),
+        (status = 200, description = "Successfully removed the member"),
+        (status = 400, description = "Bad request, you cannot remove yourself"),
         (status = 401, description = "Unauthorized, provide a bearer token"),
-        (status = 403, description = "Forbidden, you don't have permission to invite a member to this project"),
+        (status = 403, description = "Forbidden, you don't have permission to remove this member or delete their tasks"),
+        (status = 404, description = "Not found, the project has no member with this id"),
         (status = 500, description = "Internal server error")
     ),
     security(("Bearer" = []))
 )]
-async fn remove_project(
+async fn remove_member(
     State(ctx): State<ApiContext>,
-    Path(project_id): Path<ProjectId>,
-    membership: ProjectMember
+    Path((project_id, member_id)): Path<(ProjectId, ProjectMemberId)>,
+    Query(query): Query<RemoveMember>,
+    membership: ProjectMember,
 ) -> Result<()> {
     let mut transaction = ctx.pool.begin().await?;
 
-    if !membership.permissions.contains(Permissions::DELETE_RPOJECT) {
-        return Err(ApiError::Forbidden);
-    }
+    let member = ProjectMember::get(member_id, &mut *transaction)
+        .await?
+        .filter(|member| member.project_id.0 == project_id.0 && member.user_id.0 != DELETED_USER)
+        .ok_or(ApiError::NotFound)?;
+
+    member.remove(&membership, query.delete_tasks.unwrap_or(false), &mut transaction).await?;
 
-    Project::remove(project_id, &mut transaction).await?;
     transaction.commit().await?;
 
     Ok(())
 }
 
+/// Fetches the project's roles, ordered by name.
+/// 
+/// If the project is public then this endpoint requires no
+/// authentication, if it is private then a membership of the
+/// project is required.
+/// 
 #[utoipa::path(
-    put,
-    path = "/projects/{id}",
+    get,
+    path = "/projects/{id}/roles",
     context_path = "/api/v1",
     tag = "v1",
-    request_body(content = EditProject, description = "The values to update", content_type = "application/json"),
     params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
     responses(
-        (status = 200, description = "Successfully edited the project", body = Project, content_type = "application/json"),
+        (status = 200, description = "Successfully retrieved the project's roles", body = [ProjectRole], content_type = "application/json"),
         (status = 401, description = "Unauthorized, provide a bearer token"),
-        (status = 403, description = "Forbidden, you don't have permission to edit this project"),
+        (status = 403, description = "Forbidden, you don't have permission to access this project's roles"),
         (status = 500, description = "Internal server error")
     ),
-    security(("Bearer" = [])),
+    security((), ("Bearer" = []))
 )]
-async fn update_project(
+async fn get_roles(
     State(ctx): State<ApiContext>,
     Path(project_id): Path<ProjectId>,
+    _membership: ProjectMember,
+) -> Result<Json<Vec<ProjectRole>>> {
+    ProjectRole::get_many_from_project(project_id, &ctx.pool)
+        .await
+        .map(Json)
+        .map_err(|error| error.into())
+}
+
+/// Creates a role in the project. Members can only create roles
+/// with permissions they hold themselves. The creation is recorded
+/// in the project's audit log.
+/// 
+/// This endpoint requires a bearer token belonging to a member with
+/// permission to invite members.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/roles",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = RoleBuilder, description = "The role's name and permissions", content_type = "application/json"),
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully created the role", body = ProjectRole, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you can't create roles or give these permissions"),
+        (status = 422, description = "Unprocessable entity, the name is taken or invalid"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn create_role(
+    State(ctx): State<ApiContext>,
     membership: ProjectMember,
-    Json(form): Json<EditProject>,
-) -> Result<Json<Project>> {
+    Json(form): Json<RoleBuilder>,
+) -> Result<Json<ProjectRole>> {
     let mut transaction = ctx.pool.begin().await?;
 
-    if !membership.permissions.contains(Permissions::EDIT_PROJECT) {
-        return Err(ApiError::Forbidden);
+    let role = ProjectRole::create(form, &membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(role))
+}
+
+/// Renames a role or changes its permissions, every member with the
+/// role is given the new permissions. Members can only give or take
+/// away permissions they hold themselves. The change is recorded in
+/// the project's audit log.
+/// 
+/// This endpoint requires a bearer token belonging to a member with
+/// permission to invite members.
+/// 
+#[utoipa::path(
+    patch,
+    path = "/projects/{id}/roles/{role_id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = EditRole, description = "The role's new name or permissions", content_type = "application/json"),
+    params(
+        ("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8),
+        ("role_id" = String, Path, description = "The id of the role", max_length = 10, min_length = 10),
+    ),
+    responses(
+        (status = 200, description = "Successfully changed the role", body = ProjectRole, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you can't edit roles or change these permissions"),
+        (status = 404, description = "Not found, the project has no role with this id"),
+        (status = 422, description = "Unprocessable entity, the name is taken or invalid"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn edit_role(
+    State(ctx): State<ApiContext>,
+    Path((project_id, role_id)): Path<(ProjectId, ProjectRoleId)>,
+    membership: ProjectMember,
+    Json(form): Json<EditRole>,
+) -> Result<Json<ProjectRole>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let mut role = ProjectRole::get(role_id, project_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    role.edit(form, &membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(role))
+}
+
+/// Removes a role from the project. Members with the role keep its
+/// permissions, as if they had been set by hand. The removal is
+/// recorded in the project's audit log.
+/// 
+/// This endpoint requires a bearer token belonging to a member with
+/// permission to invite members.
+/// 
+#[utoipa::path(
+    delete,
+    path = "/projects/{id}/roles/{role_id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8),
+        ("role_id" = String, Path, description = "The id of the role", max_length = 10, min_length = 10),
+    ),
+    responses(
+        (status = 200, description = "Successfully removed the role"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you can't remove roles"),
+        (status = 404, description = "Not found, the project has no role with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn remove_role(
+    State(ctx): State<ApiContext>,
+    Path((project_id, role_id)): Path<(ProjectId, ProjectRoleId)>,
+    membership: ProjectMember,
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let role = ProjectRole::get(role_id, project_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    role.remove(&membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Accepts the current user's pending invitation to the project,
+/// after which they are a member with the permissions they were
+/// invited with.
+/// 
+/// This endpoint requires a bearer token.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/invitation/accept",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully joined the project", body = ProjectMember, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 404, description = "Not found, you have no pending invitation to this project"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn accept_invitation(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    credentials: Credentials,
+) -> Result<Json<ProjectMember>> {
+    credentials.check_project(&project_id)?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    Project::get(project_id.clone(), &mut *transaction)
+        .await?
+        .filter(|project| project.trashed.is_none())
+        .ok_or(ApiError::NotFound)?;
+
+    let mut invitation = ProjectMember::get_from_user(credentials.user_id().clone(), project_id, &mut *transaction)
+        .await?
+        .filter(|member| !member.accepted)
+        .ok_or(ApiError::NotFound)?;
+
+    invitation.accept_invitation(&mut transaction).await?;
+
+    transaction.commit().await?;
+
+    invitation.accepted = true;
+
+    Ok(Json(invitation))
+}
+
+/// Fetches the project's invite links, including ones which have
+/// expired or been used up, newest first. The links' tokens are
+/// not included.
+/// 
+/// This endpoint requires a bearer token belonging to a member with
+/// permission to invite members.
+/// 
+#[utoipa::path(
+    get,
+    path = "/projects/{id}/invite-links",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully retrieved the invite links", body = [InviteLink], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you don't have permission to invite members to this project"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn get_invite_links(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    membership: ProjectMember,
+) -> Result<Json<Vec<InviteLink>>> {
+    membership.check_permissions(Permissions::INVITE_MEMBERS)?;
+
+    InviteLink::get_many_from_project(project_id, &ctx.pool)
+        .await
+        .map_err(|error| error.into())
+        .map(Json)
+}
+
+/// Creates a link any logged in user can use to join the project
+/// with the given role or permissions, until it expires or runs out
+/// of uses. The link's token is only returned by this request and
+/// cannot be fetched again later.
+/// 
+/// This endpoint requires a bearer token belonging to a member with
+/// permission to invite members.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/invite-links",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = InviteLinkBuilder, description = "The link's role or permissions, uses and expiry", content_type = "application/json"),
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully created the invite link", body = CreatedInviteLink, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 400, description = "Bad request, give either a role or permissions"),
+        (status = 403, description = "Forbidden, you don't have permission to invite members to this project or give this role or these permissions"),
+        (status = 404, description = "Not found, the role does not exist"),
+        (status = 422, description = "Unprocessable entity, the expiry is in the past or the maximum uses is below 1"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn create_invite_link(
+    State(ctx): State<ApiContext>,
+    membership: ProjectMember,
+    Json(form): Json<InviteLinkBuilder>,
+) -> Result<Json<CreatedInviteLink>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let link = InviteLink::create(form, &membership, &ctx.settings.frontend_url, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(link))
+}
+
+/// Revokes one of the project's invite links so it can no longer
+/// be used, members who already joined through it stay.
+/// 
+/// This endpoint requires a bearer token belonging to a member with
+/// permission to invite members.
+/// 
+#[utoipa::path(
+    delete,
+    path = "/projects/{id}/invite-links/{link_id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(
+        ("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8),
+        ("link_id" = String, Path, description = "The id of the invite link", max_length = 12, min_length = 12)
+    ),
+    responses(
+        (status = 200, description = "Successfully revoked the invite link"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you don't have permission to invite members to this project"),
+        (status = 404, description = "Not found, the project has no invite link with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn revoke_invite_link(
+    State(ctx): State<ApiContext>,
+    Path((project_id, link_id)): Path<(ProjectId, InviteLinkId)>,
+    membership: ProjectMember,
+) -> Result<()> {
+    membership.check_permissions(Permissions::INVITE_MEMBERS)?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let link = InviteLink::get(link_id, project_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    link.revoke(&membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Joins a project through an invite link, the current user becomes
+/// a member with the link's role or permissions straight away. Any
+/// pending invitation they have to the project is replaced.
+/// 
+/// This endpoint requires a bearer token.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/join",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = JoinProject, description = "The invite link's token", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully joined the project", body = ProjectMember, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, your email address is unverified or the access token is restricted to certain projects"),
+        (status = 404, description = "Not found, the link does not exist, has expired or has been used up"),
+        (status = 409, description = "Conflict, you are already a member of the project"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn join_project(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+    user: User,
+    Json(form): Json<JoinProject>,
+) -> Result<Json<ProjectMember>> {
+    credentials.require_unrestricted()?;
+
+    if ctx.settings.require_verified_email && !user.verified {
+        return Err(ApiError::EmailNotVerified);
+    }
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let member = InviteLink::redeem(&form.token, user.id, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(member))
+}
+
+/// Denies the current user's pending invitation to the project.
+/// 
+/// This endpoint requires a bearer token.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/invitation/deny",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully denied the invitation"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 404, description = "Not found, you have no pending invitation to this project"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn deny_invitation(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    credentials: Credentials,
+) -> Result<()> {
+    credentials.check_project(&project_id)?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let invitation = ProjectMember::get_from_user(credentials.user_id().clone(), project_id, &mut *transaction)
+        .await?
+        .filter(|member| !member.accepted)
+        .ok_or(ApiError::NotFound)?;
+
+    invitation.deny_invitation(&mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Fetches the project's pending ownership transfer.
+/// 
+/// This endpoint requires a bearer token belonging to a member of
+/// the project.
+/// 
+#[utoipa::path(
+    get,
+    path = "/projects/{id}/transfer",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully retrieved the pending transfer", body = OwnershipTransfer, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you are not a member of this project"),
+        (status = 404, description = "Not found, the project has no pending transfer"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn get_transfer(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    membership: ProjectMember,
+) -> Result<Json<OwnershipTransfer>> {
+    // Visitors to public projects cannot see transfers
+    if !membership.accepted {
+        return Err(ApiError::Forbidden);
+    }
+
+    OwnershipTransfer::get_from_project(project_id, &ctx.pool)
+        .await?
+        .ok_or(ApiError::NotFound)
+        .map(Json)
+}
+
+/// Nominates a member to take over the project, replacing any
+/// transfer which is already pending. The member is sent a
+/// notification with actions to accept or deny it, the project
+/// only changes hands once they accept. The nomination is recorded
+/// in the project's audit log.
+/// 
+/// This endpoint requires a bearer token belonging to the owner of
+/// the project.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/transfer",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = NominateOwner, description = "The member to transfer the project to", content_type = "application/json"),
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully nominated the member", body = OwnershipTransfer, content_type = "application/json"),
+        (status = 400, description = "Bad request, you already own the project"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you don't own this project"),
+        (status = 404, description = "Not found, the project has no member with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn transfer_project(
+    State(ctx): State<ApiContext>,
+    membership: ProjectMember,
+    Json(form): Json<NominateOwner>,
+) -> Result<Json<OwnershipTransfer>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let nominee = ProjectMember::get(form.member_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let transfer = OwnershipTransfer::create(&nominee, &membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(transfer))
+}
+
+/// Withdraws the project's pending ownership transfer. The
+/// withdrawal is recorded in the project's audit log.
+/// 
+/// This endpoint requires a bearer token belonging to the owner of
+/// the project.
+/// 
+#[utoipa::path(
+    delete,
+    path = "/projects/{id}/transfer",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully withdrew the transfer"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you don't own this project"),
+        (status = 404, description = "Not found, the project has no pending transfer"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn cancel_transfer(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    membership: ProjectMember,
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let project = Project::get(project_id.clone(), &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if project.owner.0 != membership.user_id.0 {
+        return Err(ApiError::Forbidden);
+    }
+
+    let transfer = OwnershipTransfer::get_from_project(project_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let nominee = ProjectMember::get(transfer.nominee.clone(), &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    transfer.remove(&nominee, &mut transaction).await?;
+
+    let username = nominee.username(&mut transaction).await?;
+
+    Audit::create(
+        &membership,
+        format!("Withdrew the ownership transfer to {}", username),
+        &mut transaction
+    ).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Accepts the current user's nomination to take over the project,
+/// after which they own it and have every permission. The previous
+/// owner stays a member and is notified. The transfer is recorded
+/// in the project's audit log.
+/// 
+/// This endpoint requires a bearer token belonging to the nominated
+/// member.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/transfer/accept",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully took over the project", body = ProjectMember, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, enable two-factor authentication before taking over the project"),
+        (status = 404, description = "Not found, you have no pending nomination for this project"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn accept_transfer(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    membership: ProjectMember,
+) -> Result<Json<ProjectMember>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let transfer = OwnershipTransfer::get_from_project(project_id, &mut *transaction)
+        .await?
+        .filter(|transfer| transfer.nominee.0 == membership.id.0)
+        .ok_or(ApiError::NotFound)?;
+
+    // Owners are required to use two-factor authentication
+    if ctx.settings.require_two_factor && !TwoFactor::get(membership.user_id.clone(), &mut *transaction).await?.enabled {
+        return Err(ApiError::TwoFactorRequired);
+    }
+
+    let owner = transfer.accept(membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(owner))
+}
+
+/// Declines the current user's nomination to take over the project,
+/// the owner is notified. The refusal is recorded in the project's
+/// audit log.
+/// 
+/// This endpoint requires a bearer token belonging to the nominated
+/// member.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/transfer/deny",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully declined the nomination"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 404, description = "Not found, you have no pending nomination for this project"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn deny_transfer(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    membership: ProjectMember,
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let transfer = OwnershipTransfer::get_from_project(project_id, &mut *transaction)
+        .await?
+        .filter(|transfer| transfer.nominee.0 == membership.id.0)
+        .ok_or(ApiError::NotFound)?;
+
+    transfer.deny(&membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Moves the project to the trash. Trashed projects can't be
+/// accessed but can be restored until they have been in the trash
+/// for longer than the instance's retention period, after which
+/// they are permanently removed.
+/// 
+/// This endpoint requires a bearer token belonging to a member
+/// with permission to delete the project.
+/// 
+#[utoipa::path(
+    delete,
+    path = "/projects/{id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully moved the project to the trash"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you don't have permission to delete this project"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn remove_project(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    membership: ProjectMember
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    if !membership.permissions.contains(Permissions::DELETE_RPOJECT) {
+        return Err(ApiError::Forbidden);
+    }
+
+    let mut project = Project::get(project_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::Forbidden)?;
+
+    project.trash(&membership, &mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Restores a project from the trash.
+/// 
+/// This endpoint requires a bearer token belonging to a member
+/// with permission to delete the project.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/restore",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully restored the project", body = Project, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you don't have permission to restore this project or haven't enabled two-factor authentication"),
+        (status = 404, description = "Not found, the project is not in the trash"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn restore_project(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    credentials: Credentials,
+) -> Result<Json<Project>> {
+    credentials.check_project(&project_id)?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    // The membership extractor treats trashed projects as missing
+    // so the membership has to be looked up directly
+    let membership = ProjectMember::get_accepted_from_user(
+        credentials.user_id().clone(),
+        project_id.clone(),
+        &mut *transaction
+    )
+        .await?
+        .ok_or(ApiError::Forbidden)?;
+
+    membership.check_permissions(Permissions::DELETE_RPOJECT)?;
+
+    let mut project = Project::get(project_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::Forbidden)?;
+
+    require_two_factor(&membership, &project, &ctx).await?;
+
+    project.restore(&membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(project))
+}
+
+/// Archives the project, making it read-only and leaving it out of
+/// members' project lists by default. Archiving a project which is
+/// already archived does nothing.
+/// 
+/// This endpoint requires a bearer token belonging to a member
+/// with permission to edit the project.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/archive",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully archived the project", body = Project, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you don't have permission to edit this project"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn archive_project(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    membership: ProjectMember,
+) -> Result<Json<Project>> {
+    set_archived(ctx, project_id, membership, true).await
+}
+
+/// Unarchives the project so it can be edited again. Unarchiving a
+/// project which isn't archived does nothing.
+/// 
+/// This endpoint requires a bearer token belonging to a member
+/// with permission to edit the project.
+/// 
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/unarchive",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully unarchived the project", body = Project, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you don't have permission to edit this project"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn unarchive_project(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    membership: ProjectMember,
+) -> Result<Json<Project>> {
+    set_archived(ctx, project_id, membership, false).await
+}
+
+async fn set_archived(
+    ctx: ApiContext,
+    project_id: ProjectId,
+    membership: ProjectMember,
+    archived: bool,
+) -> Result<Json<Project>> {
+    membership.check_permissions(Permissions::EDIT_PROJECT)?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let mut project = Project::get(project_id, &mut *transaction)
+        .await?
+        .ok_or(ApiError::Forbidden)?;
+
+    project.set_archived(archived, &membership, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(project))
+}
+
+#[utoipa::path(
+    put,
+    path = "/projects/{id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = EditProject, description = "The values to update", content_type = "application/json"),
+    params(("id" = String, Path, description = "The id of the project", max_length = 8, min_length = 8)),
+    responses(
+        (status = 200, description = "Successfully edited the project", body = Project, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you don't have permission to edit this project"),
+        (status = 409, description = "Conflict, the project is archived"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn update_project(
+    State(ctx): State<ApiContext>,
+    Path(project_id): Path<ProjectId>,
+    membership: ProjectMember,
+    Json(form): Json<EditProject>,
+) -> Result<Json<Project>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    if !membership.permissions.contains(Permissions::EDIT_PROJECT) {
+        return Err(ApiError::Forbidden);
+    }
+
+    let archived = Project::get(project_id.clone(), &mut *transaction)
+        .await?
+        .is_some_and(|project| project.archived);
+
+    if archived {
+        return Err(ApiError::Conflict("archived projects can't be edited, unarchive the project first"));
     }
 
     Project::edit(project_id.clone(), form, &mut transaction).await?;
@@ -313,7 +1462,7 @@ async fn get_task_groups(
 
     TaskGroup::get_from_project(project_id, &ctx.pool)
         .await
-        .map(|task_groups| Json(task_groups))
+        .map(Json)
         .map_err(|error| error.into())
 }
 
@@ -53,7 +53,7 @@ async fn get_sub_task_by_id(
     SubTask::get(id, &ctx.pool)
         .await?
         .ok_or(ApiError::Forbidden)
-        .map(|task| Json(task))
+        .map(Json)
 }
 
 /// Edits the values of a sub task such as it's body or weight
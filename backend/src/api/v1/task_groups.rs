@@ -44,7 +44,7 @@ async fn get_task_group_by_id(
     TaskGroup::get(id, &ctx.pool)
         .await?
         .ok_or(ApiError::Forbidden)
-        .map(|task_group| Json(task_group))
+        .map(Json)
 }
 
 #[utoipa::path(
@@ -139,6 +139,6 @@ async fn get_tasks(
 
     Task::get_many_from_task_group(id, &ctx.pool)
         .await
-        .map(|tasks| Json(tasks))
+        .map(Json)
         .map_err(|error| error.into())
 }
\ No newline at end of file
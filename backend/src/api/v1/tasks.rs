@@ -58,7 +58,7 @@ async fn get_task(
     Task::get_full(id, &ctx.pool)
         .await?
         .ok_or(ApiError::NotFound)
-        .map(|task| Json(task))
+        .map(Json)
 }
 
 /// Edits the values of a task such as it's name or description,
@@ -186,7 +186,7 @@ async fn get_sub_tasks(
 
     SubTask::get_from_task(id, &ctx.pool)
         .await
-        .map(|sub_tasks| Json(sub_tasks))
+        .map(Json)
         .map_err(|error| error.into())
 }
 
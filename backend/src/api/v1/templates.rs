@@ -0,0 +1,128 @@
+use axum::extract::{State, Path};
+use axum::routing::get;
+use axum::{Router, Json};
+
+use crate::error::ApiError;
+use crate::middleware::user_authentication::Credentials;
+use crate::models::id::ProjectTemplateId;
+use crate::models::templates::ProjectTemplate;
+use crate::response::Result;
+use crate::api::ApiContext;
+
+/// Create a router to be nested on the main api router with
+/// endpoints for retrieving and removing the logged in user's
+/// project templates, templates are saved from projects
+///
+pub fn configure() -> Router<ApiContext> {
+    Router::new()
+        .route(
+            "/templates",
+            get(get_templates)
+        )
+        .route(
+            "/templates/:id",
+            get(get_template)
+            .delete(remove_template)
+        )
+}
+
+/// Fetches the logged in user's project templates ordered by name.
+///
+/// This endpoint requires a bearer token.
+///
+#[utoipa::path(
+    get,
+    path = "/templates",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully retrieved the templates", body = [ProjectTemplate], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn get_templates(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+) -> Result<Json<Vec<ProjectTemplate>>> {
+    credentials.require_unrestricted()?;
+
+    ProjectTemplate::get_many_from_user(credentials.user_id().clone(), &ctx.pool)
+        .await
+        .map_err(|error| error.into())
+        .map(Json)
+}
+
+/// Fetches one of the logged in user's project templates.
+///
+/// This endpoint requires a bearer token.
+///
+#[utoipa::path(
+    get,
+    path = "/templates/{id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the template", max_length = 12, min_length = 12)),
+    responses(
+        (status = 200, description = "Successfully retrieved the template", body = ProjectTemplate, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 404, description = "Not found, you have no template with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn get_template(
+    State(ctx): State<ApiContext>,
+    Path(template_id): Path<ProjectTemplateId>,
+    credentials: Credentials,
+) -> Result<Json<ProjectTemplate>> {
+    credentials.require_unrestricted()?;
+
+    ProjectTemplate::get(template_id, credentials.user_id().clone(), &ctx.pool)
+        .await?
+        .ok_or(ApiError::NotFound)
+        .map(Json)
+}
+
+/// Removes one of the logged in user's project templates, projects
+/// already created from it are unaffected.
+///
+/// This endpoint requires a bearer token.
+///
+#[utoipa::path(
+    delete,
+    path = "/templates/{id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the template", max_length = 12, min_length = 12)),
+    responses(
+        (status = 200, description = "Successfully removed the template"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 404, description = "Not found, you have no template with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = []))
+)]
+async fn remove_template(
+    State(ctx): State<ApiContext>,
+    Path(template_id): Path<ProjectTemplateId>,
+    credentials: Credentials,
+) -> Result<()> {
+    credentials.require_unrestricted()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let template = ProjectTemplate::get(template_id, credentials.user_id().clone(), &mut *transaction)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    template.remove(&mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
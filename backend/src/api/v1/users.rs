@@ -1,13 +1,30 @@
-use axum::extract::{Path, State};
-use axum::routing::{get, post};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::{get, post, put, delete};
 use axum::{Json, Router};
 use tower_cookies::Cookies;
 
 use crate::api::ApiContext;
+use crate::api::v1::PageQuery;
 use crate::error::ApiError;
-use crate::models::id::UserId;
-use crate::models::tokens::Token;
-use crate::models::users::{User, Login, Register};
+use crate::database::Database;
+use crate::middleware::user_authentication::Credentials;
+use crate::models::access_tokens::{PersonalAccessToken, PersonalAccessTokenBuilder, CreatedPersonalAccessToken};
+use crate::models::data_exports::DataExport;
+use crate::models::id::{UserId, PersonalAccessTokenId, SessionId, DataExportId};
+use crate::models::login_attempts::LoginAttempt;
+use crate::models::notifications::{Notification, NotificationBuilder};
+use crate::models::login_history::{ClientInfo, LoginHistory};
+use crate::models::oidc::{OidcCallback, OidcLogin, UserIdentity};
+use crate::models::projects::ProjectMember;
+use crate::models::sessions::{Session, ActiveSession};
+use crate::models::tokens::{self, Token, RefreshToken, OidcState};
+use crate::mail::Mail;
+use crate::models::email_verifications::EmailVerification;
+use crate::models::password_resets::PasswordReset;
+use crate::models::two_factor::{TwoFactor, TwoFactorStatus, TotpEnrolment, RecoveryCodes, TotpCode, TotpLogin, MfaChallenge};
+use crate::models::users::{User, PublicUser, UserSearch, Login, Register, EditUser, ChangeEmail, ChangePassword, DeleteAccount, ForgotPassword, ResetPassword, VerifyEmail, ResendVerification};
 use crate::response::Result;
 
 /// Create a router to be nested on the main api router with
@@ -15,10 +32,46 @@ use crate::response::Result;
 /// 
 pub fn configure() -> Router<ApiContext> {
     Router::new()
-        .route("/users", get(get_current_user))
+        .route("/users", 
+            get(get_current_user)
+            .patch(edit_current_user)
+            .delete(delete_current_user)
+        )
+        .route("/users/email", put(change_email))
+        .route("/users/password", put(change_password))
+        .route("/users/search", get(search_users))
         .route("/users/:id", get(get_user_by_id))
         .route("/users/register", post(register))
         .route("/users/login", post(login))
+        .route("/users/login/totp", post(login_totp))
+        .route("/users/oidc/authorize", get(oidc_authorize))
+        .route("/users/oidc/callback", get(oidc_callback))
+        .route("/users/refresh", post(refresh))
+        .route("/users/logout", post(logout))
+        .route("/users/logout-all", post(logout_all))
+        .route("/users/logins", get(get_login_history))
+        .route("/users/sessions", get(get_sessions))
+        .route("/users/sessions/:id", delete(revoke_session))
+        .route("/users/tokens", 
+            get(get_access_tokens)
+            .post(create_access_token)
+        )
+        .route("/users/tokens/:id", delete(revoke_access_token))
+        .route("/users/exports", 
+            get(get_data_exports)
+            .post(create_data_export)
+        )
+        .route("/users/exports/:id", get(get_data_export))
+        .route("/users/exports/:id/download", get(download_data_export))
+        .route("/users/forgot-password", post(forgot_password))
+        .route("/users/reset-password", post(reset_password))
+        .route("/users/verify-email", post(verify_email))
+        .route("/users/verify-email/resend", post(resend_verification))
+        .route("/users/2fa", get(get_two_factor_status))
+        .route("/users/2fa/enrol", post(enrol_two_factor))
+        .route("/users/2fa/confirm", post(confirm_two_factor))
+        .route("/users/2fa/disable", post(disable_two_factor))
+        .route("/users/2fa/recovery-codes", post(regenerate_recovery_codes))
 }
 
 /// Fetches information about the user provided by the given
@@ -47,15 +100,310 @@ async fn get_current_user(
     User::get(user_id, &ctx.pool)
         .await?
         .ok_or(ApiError::Unauthorized)
-        .map(|user| Json(user))
+        .map(Json)
 }
 
-/// Fetches information about a user given their id. If the user
-/// does not exist 403 forbidden will be retured instead of 404
-/// not found for security.
+/// Changes the current user's username, profile picture and or whether
+/// they can be found by searching, any fields which are not given are
+/// left unchanged. The user is notified of every field that changed.
 /// 
-/// This endpoint may require authentication depending on the
-/// privicy level set by the user. 
+/// This endpoint requires a bearer token to be provided in the
+/// request headers.
+/// 
+#[utoipa::path(
+    patch,
+    path = "/users",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = EditUser, description = "The fields to change", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully updated user", body = User, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 422, description = "Unprocessable entity, the username is taken or invalid"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn edit_current_user(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+    mut user: User,
+    Json(form): Json<EditUser>,
+) -> Result<Json<User>> {
+    credentials.require_unrestricted()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let previous_username = user.username.clone();
+    let previous_icon_url = user.icon_url.clone();
+    let previous_discoverable = user.discoverable;
+    user.edit(form, &mut transaction).await?;
+
+    let mut changes = Vec::new();
+
+    if user.username != previous_username {
+        changes.push(format!("Your username was changed from {} to {}.", previous_username, user.username));
+    }
+
+    if user.icon_url != previous_icon_url {
+        changes.push("Your profile picture was changed.".to_string());
+    }
+
+    if user.discoverable != previous_discoverable {
+        changes.push(match user.discoverable {
+            true => "You can now be found by searching for you.".to_string(),
+            false => "You can no longer be found by searching for you.".to_string(),
+        });
+    }
+
+    if !changes.is_empty() {
+        notify_security_change(&user, changes.join(" "), &mut transaction).await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(Json(user))
+}
+
+/// Changes the current user's email address given their current
+/// password. The new address has to be verified again using the
+/// link sent to it, and the user is notified of the change.
+/// 
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to change credentials.
+/// 
+#[utoipa::path(
+    put,
+    path = "/users/email",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = ChangeEmail, description = "The new email address and current password", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully changed email address", body = User, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token, or the password is incorrect"),
+        (status = 403, description = "Forbidden, personal access tokens cannot change credentials"),
+        (status = 422, description = "Unprocessable entity, the email address is taken"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn change_email(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+    Json(form): Json<ChangeEmail>,
+) -> Result<Json<User>> {
+    credentials.require_session()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let mut user = User::get(credentials.user_id().clone(), &mut *transaction)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    if !user.check_password(&form.password) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let previous_email = user.email.clone();
+    user.set_email(form.email, &mut transaction).await?;
+
+    let (_, token) = EmailVerification::create(user.id.clone(), user.email.clone(), &mut transaction).await?;
+
+    notify_security_change(
+        &user,
+        format!("Your email address was changed from {} to {}.", previous_email, user.email),
+        &mut transaction,
+    ).await?;
+
+    transaction.commit().await?;
+
+    send_verification_email(&ctx, &user, &token).await;
+
+    Ok(Json(user))
+}
+
+/// Changes the current user's password given their current one.
+/// Every other session is logged out and the user is notified of
+/// the change.
+/// 
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to change credentials.
+/// 
+#[utoipa::path(
+    put,
+    path = "/users/password",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = ChangePassword, description = "The current and new passwords", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully changed password"),
+        (status = 401, description = "Unauthorized, provide a bearer token, or the current password is incorrect"),
+        (status = 403, description = "Forbidden, personal access tokens cannot change credentials"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn change_password(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+    Json(form): Json<ChangePassword>,
+) -> Result<()> {
+    let session_id = credentials.require_session()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let user = User::get(credentials.user_id().clone(), &mut *transaction)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    if !user.check_password(&form.current_password) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    User::set_password(user.id.clone(), form.new_password, &mut transaction).await?;
+    Session::revoke_others(user.id.clone(), session_id, &mut transaction).await?;
+
+    notify_security_change(
+        &user,
+        "Your password was changed and every other session was logged out.".into(),
+        &mut transaction,
+    ).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Permanently deletes the current user's account given their
+/// password, and a two-factor code if enabled. Tasks they created
+/// and their audit entries are kept but attributed to a deleted
+/// user, and they leave every project they are a member of.
+/// 
+/// Users who own projects have to transfer their ownership or
+/// delete them first. Projects they own in the trash are permanently
+/// removed with the account, which has to be confirmed with
+/// `purge_trash`.
+/// 
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to delete accounts.
+/// 
+#[utoipa::path(
+    delete,
+    path = "/users",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = DeleteAccount, description = "Confirmation of the deletion", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully deleted the account"),
+        (status = 401, description = "Unauthorized, provide a bearer token, or the password or code is incorrect"),
+        (status = 403, description = "Forbidden, personal access tokens cannot delete accounts"),
+        (status = 409, description = "Conflict, the user still owns projects, or owns projects in the trash and didn't confirm purging them"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn delete_current_user(
+    State(ctx): State<ApiContext>,
+    cookies: Cookies,
+    credentials: Credentials,
+    Json(form): Json<DeleteAccount>,
+) -> Result<()> {
+    credentials.require_session()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let user = User::get(credentials.user_id().clone(), &mut *transaction)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    if !user.check_password(&form.password) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let two_factor = TwoFactor::get(user.id.clone(), &mut *transaction).await?;
+
+    if two_factor.enabled {
+        let code = form.code.ok_or(ApiError::Unauthorized)?;
+
+        if !two_factor.verify(&code, &mut transaction).await? {
+            return Err(ApiError::Unauthorized);
+        }
+    }
+
+    user.remove(form.purge_trash, &mut transaction).await?;
+    transaction.commit().await?;
+
+    tokens::clear_cookies(&cookies);
+
+    Ok(())
+}
+
+/// Searches for users to invite by the start of their username or
+/// email address. Users who have turned off discoverability are not
+/// included, and only public information is returned.
+/// 
+/// Members of, and users already invited to, a project can be left
+/// out by giving its id, as long as the current user is a member.
+/// 
+/// This endpoint requires a bearer token to be provided in the
+/// request headers.
+/// 
+#[utoipa::path(
+    get,
+    path = "/users/search",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(
+        ("query" = String, Query, description = "The start of the username or email address, at least 2 characters"),
+        ("exclude_project" = Option<String>, Query, description = "Leave out members of, and users invited to, this project", max_length = 8, min_length = 8),
+        ("limit" = Option<u32>, Query, description = "The maximum number of users to fetch, by default 50"),
+        ("page" = Option<u32>, Query, description = "The page to fetch"),
+    ),
+    responses(
+        (status = 200, description = "Successfully searched users", body = [PublicUser], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, you are not a member of the excluded project"),
+        (status = 422, description = "Unprocessable entity, the query is too short"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn search_users(
+    State(ctx): State<ApiContext>,
+    Query(search): Query<UserSearch>,
+    Query(page): Query<PageQuery>,
+    credentials: Credentials,
+) -> Result<Json<Vec<PublicUser>>> {
+    let query = search.query.trim();
+
+    if query.chars().count() < 2 {
+        return Err(ApiError::unprocessable_entity([("query", "must be at least 2 characters")]));
+    }
+
+    // Leaving out members reveals who they are, so only
+    // members can do so
+    if let Some(project_id) = &search.exclude_project {
+        credentials.check_project(project_id)?;
+
+        ProjectMember::get_accepted_from_user(credentials.user_id().clone(), project_id.clone(), &ctx.pool)
+            .await?
+            .ok_or(ApiError::Forbidden)?;
+    }
+
+    let (limit, offset) = page.limit_and_offset();
+
+    User::search(query, search.exclude_project, limit, offset, &ctx.pool)
+        .await
+        .map(Json)
+        .map_err(|error| error.into())
+}
+
+/// Fetches the public information of a user given their id, the
+/// rest of a user's details are only returned to themself from
+/// `/users`.
+/// 
+/// This endpoint does not require authentication.
 /// 
 #[utoipa::path(
     get,
@@ -64,9 +412,8 @@ async fn get_current_user(
     tag = "v1",
     params(("id" = String, Path, description = "The user's id", max_length = 8, min_length = 8)),
     responses(
-        (status = 200, description = "Successfully retrieved user", body = User, content_type = "application/json"),
-        (status = 401, description = "Unauthorized, provide a bearer token"),
-        (status = 403, description = "Forbidden, you don't have permission to access this user"),
+        (status = 200, description = "Successfully retrieved user", body = PublicUser, content_type = "application/json"),
+        (status = 404, description = "Not found, there is no user with this id"),
         (status = 500, description = "Internal server error")
     ),
     security((), ("Bearer" = [])),
@@ -74,17 +421,21 @@ async fn get_current_user(
 async fn get_user_by_id(
     State(ctx): State<ApiContext>,
     Path(user_id): Path<UserId>,
-) -> Result<Json<User>> {
-    User::get(user_id, &ctx.pool)
+) -> Result<Json<PublicUser>> {
+    User::get_public(user_id, &ctx.pool)
         .await?
         .ok_or(ApiError::NotFound)
-        .map(|user| Json(user))
+        .map(Json)
 }
 
 /// Registers a new user and returns their information aswell as
 /// an authorised bearer token to prevent the need to login with 
 /// a subsequent request.
 /// 
+/// A link to verify the user's email address is sent to them. If
+/// the instance requires verified addresses no token is returned
+/// and the user has to verify their address before logging in.
+/// 
 #[utoipa::path(
     post,
     path = "/users/register",
@@ -99,17 +450,23 @@ async fn get_user_by_id(
 )]
 async fn register(
     State(ctx): State<ApiContext>,
+    client: ClientInfo,
     cookies: Cookies,
     Json(form): Json<Register>,
 ) -> Result<Json<User>> {
     let mut transaction = ctx.pool.begin().await?;
 
     let user = User::register(form, &mut transaction).await?;
+    let (_, token) = EmailVerification::create(user.id.clone(), user.email.clone(), &mut transaction).await?;
+
+    if !ctx.settings.require_verified_email {
+        start_session(&ctx, &user, &client, &cookies, &mut transaction).await?;
+    }
+
     transaction.commit().await?;    
 
-    let token = Token::encode(&user);
-    cookies.add(token.into_cookie());
-    
+    send_verification_email(&ctx, &user, &token).await;
+
     Ok(Json(user))
 }
 
@@ -121,30 +478,1149 @@ async fn register(
 /// 
 /// Authorization: Bearer <token>
 /// 
+/// If the user has enabled two-factor authentication no token is
+/// returned, instead a challenge is returned with 202 accepted which
+/// must be completed with a code at `/users/login/totp`.
+/// 
+/// Repeated failures to log in to an account, or from an ip address,
+/// are met with an increasing delay before another attempt is allowed.
+/// 
 #[utoipa::path(
-    get,
+    post,
     path = "/users/login",
     context_path = "/api/v1",
     tag = "v1",
     request_body(content = Login, description = "A login form", content_type = "application/json"),
     responses(
         (status = 200, description = "Successfully retrieved user", body = User, content_type = "application/json"),
+        (status = 202, description = "A TOTP code is required to complete logging in", body = LoginChallenge, content_type = "application/json"),
         (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the user's email address must be verified before logging in"),
+        (status = 429, description = "Too many failed attempts, retry after the number of seconds in the Retry-After header"),
         (status = 500, description = "Internal server error")
     )
 )]
 async fn login(
     State(ctx): State<ApiContext>,
+    client: ClientInfo,
     cookies: Cookies,
     Json(form): Json<Login>,
-) -> Result<Json<User>> {
+) -> Result<Response> {
+    // Committed before the password is checked so parallel
+    // attempts cannot all get past the throttle
+    let mut transaction = ctx.pool.begin().await?;
+    let mut attempt = LoginAttempt::start(&form.username_or_email, None, &client, &mut transaction).await?;
+    transaction.commit().await?;
+
     let mut transaction = ctx.pool.begin().await?;
 
     let user = User::login(form, &mut transaction).await?;
+
+    if ctx.settings.require_verified_email && !user.verified {
+        attempt.remove(&mut transaction).await?;
+        transaction.commit().await?;
+
+        return Err(ApiError::EmailNotVerified);
+    }
+
+    if TwoFactor::get(user.id.clone(), &mut *transaction).await?.enabled {
+        attempt.remove(&mut transaction).await?;
+
+        let challenge = MfaChallenge::create(user.id, &mut transaction).await?;
+        transaction.commit().await?;
+
+        return Ok((StatusCode::ACCEPTED, Json(challenge)).into_response());
+    }
+
+    attempt.succeed(&mut transaction).await?;
+    start_session(&ctx, &user, &client, &cookies, &mut transaction).await?;
+
     transaction.commit().await?;
-    
-    let token = Token::encode(&user);
-    cookies.add(token.into_cookie());
  
+    Ok(Json(user).into_response())
+}
+
+/// Completes logging in for a user with two-factor authentication
+/// enabled, given the challenge returned by `/users/login` and either
+/// a code from their authenticator app or one of their recovery codes.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/login/totp",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = TotpLogin, description = "The login challenge and a code", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully logged in", body = User, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, the challenge has expired or the code is incorrect"),
+        (status = 429, description = "Too many failed attempts, retry after the number of seconds in the Retry-After header"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+async fn login_totp(
+    State(ctx): State<ApiContext>,
+    client: ClientInfo,
+    cookies: Cookies,
+    Json(form): Json<TotpLogin>,
+) -> Result<Json<User>> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let challenge = MfaChallenge::get_active(&form.challenge, &mut *transaction)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    let user = User::get(challenge.user_id.clone(), &mut *transaction)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    // Committed before the code is checked so parallel
+    // attempts cannot all get past the throttle
+    let mut attempt = LoginAttempt::start(&user.username, Some(user.id.clone()), &client, &mut transaction).await?;
+    transaction.commit().await?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let two_factor = TwoFactor::get(user.id.clone(), &mut *transaction).await?;
+
+    if !two_factor.verify(&form.code, &mut transaction).await? {
+        transaction.commit().await?;
+
+        return Err(ApiError::Unauthorized);
+    }
+
+    if user.suspended {
+        return Err(ApiError::AccountSuspended);
+    }
+
+    challenge.complete(&mut transaction).await?;
+
+    attempt.succeed(&mut transaction).await?;
+    start_session(&ctx, &user, &client, &cookies, &mut transaction).await?;
+
+    transaction.commit().await?;
+
     Ok(Json(user))
+}
+
+/// Starts logging in with the instance's OpenID Connect provider by
+/// redirecting the user to it. Once they have logged in the provider
+/// redirects them back to `/users/oidc/callback`.
+///
+/// This endpoint should be navigated to by the browser rather than
+/// fetched, so the provider's login page can be shown.
+///
+#[utoipa::path(
+    get,
+    path = "/users/oidc/authorize",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 303, description = "Redirect to the OpenID Connect provider"),
+        (status = 404, description = "Logging in with OpenID Connect is not enabled on this instance"),
+        (status = 500, description = "Internal server error, the provider could not be reached")
+    )
+)]
+async fn oidc_authorize(
+    State(ctx): State<ApiContext>,
+    cookies: Cookies,
+) -> Result<Redirect> {
+    let oidc = ctx.oidc.as_ref().ok_or(ApiError::NotFound)?;
+
+    let request = oidc.authorization_request().await?;
+
+    let mut transaction = ctx.pool.begin().await?;
+    OidcLogin::create(&request, &mut transaction).await?;
+    transaction.commit().await?;
+
+    cookies.add(OidcState(request.state).into_cookie());
+
+    Ok(Redirect::to(&request.url))
+}
+
+/// Completes logging in with the instance's OpenID Connect provider,
+/// which redirects the user here with an authorization code.
+///
+/// The user is found by the identity they logged in with. The first
+/// time an identity is seen it is linked to the user with the same
+/// email address, or a new user is registered. Both the provider and
+/// the existing user must have verified the address.
+///
+/// The user is then redirected to the front end with the usual token
+/// cookies set, unless they have enabled two-factor authentication in
+/// which case they are redirected to `/login/totp` with a challenge to
+/// complete at `/users/login/totp`.
+///
+#[utoipa::path(
+    get,
+    path = "/users/oidc/callback",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(
+        ("code" = Option<String>, Query, description = "The authorization code issued by the provider"),
+        ("state" = String, Query, description = "The state sent to the provider"),
+        ("error" = Option<String>, Query, description = "Why the provider did not log the user in"),
+    ),
+    responses(
+        (status = 303, description = "Successfully logged in, redirect to the front end"),
+        (status = 401, description = "Unauthorized, the login expired, was started elsewhere or was rejected by the provider"),
+        (status = 403, description = "Forbidden, the provider has not verified the user's email address"),
+        (status = 404, description = "Logging in with OpenID Connect is not enabled on this instance"),
+        (status = 409, description = "Conflict, an account with the same email address exists but hasn't verified it"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+async fn oidc_callback(
+    State(ctx): State<ApiContext>,
+    client: ClientInfo,
+    cookies: Cookies,
+    Query(callback): Query<OidcCallback>,
+) -> Result<Redirect> {
+    let oidc = ctx.oidc.as_ref().ok_or(ApiError::NotFound)?;
+
+    // The login must have been started in this browser
+    let state = OidcState::from_jar(&cookies);
+    OidcState::clear(&cookies);
+
+    if state.map(|state| state.0).as_deref() != Some(callback.state.as_str()) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    if let Some(error) = callback.error {
+        tracing::info!("OpenID Connect provider did not log the user in: {}", error);
+        return Err(ApiError::Unauthorized);
+    }
+
+    let code = callback.code.ok_or(ApiError::BadRequest)?;
+
+    let mut transaction = ctx.pool.begin().await?;
+    let login = OidcLogin::redeem(&callback.state, &mut transaction).await?;
+    transaction.commit().await?;
+
+    // The transaction isn't held open while waiting on the provider
+    let claims = oidc.exchange(&code, &login.code_verifier, &login.nonce).await?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let user = UserIdentity::resolve(oidc.issuer(), claims, &mut transaction).await?;
+
+    if user.suspended {
+        return Err(ApiError::AccountSuspended);
+    }
+
+    if ctx.settings.require_verified_email && !user.verified {
+        return Err(ApiError::EmailNotVerified);
+    }
+
+    let frontend_url = ctx.settings.frontend_url.trim_end_matches('/');
+
+    if TwoFactor::get(user.id.clone(), &mut *transaction).await?.enabled {
+        let challenge = MfaChallenge::create(user.id, &mut transaction).await?;
+        transaction.commit().await?;
+
+        return Ok(Redirect::to(&format!(
+            "{}/login/totp?challenge={}",
+            frontend_url, challenge.challenge
+        )));
+    }
+
+    LoginAttempt::record(&user.username, Some(user.id.clone()), &client, true, &mut transaction).await?;
+    start_session(&ctx, &user, &client, &cookies, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Redirect::to(frontend_url))
+}
+
+/// Exchanges the refresh token cookie for a new access token and
+/// a new refresh token. Each refresh token can only be used once,
+/// after which it is replaced by the one returned here. Using one
+/// a second time revokes its session.
+/// 
+/// This endpoint does not require a valid access token so it can
+/// be used once the current one has expired.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/refresh",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully refreshed the session", body = User, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, the refresh token is missing, expired or revoked"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+async fn refresh(
+    State(ctx): State<ApiContext>,
+    cookies: Cookies,
+) -> Result<Json<User>> {
+    let refresh_token = RefreshToken::from_jar(&cookies)
+        .ok_or(ApiError::Unauthorized)?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let Some((session, refresh_token)) = Session::refresh(&refresh_token.0, &mut transaction).await? else {
+        // The replayed token's session was revoked, which has to
+        // be kept even though the request fails
+        transaction.commit().await?;
+
+        return Err(ApiError::Unauthorized);
+    };
+
+    let user = User::get(session.user_id.clone(), &mut *transaction)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    transaction.commit().await?;
+
+    cookies.add(Token::encode(&user, &session, &ctx.keyring).into_cookie());
+    cookies.add(RefreshToken(refresh_token).into_cookie());
+
+    Ok(Json(user))
+}
+
+/// Logs out of the current session, revoking it so that neither
+/// its access token nor its refresh token can be used again.
+/// 
+/// This endpoint requires a bearer token to be provided in the
+/// request headers.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/logout",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully logged out"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn logout(
+    State(ctx): State<ApiContext>,
+    cookies: Cookies,
+    credentials: Credentials,
+) -> Result<()> {
+    let session_id = credentials.require_session()?.clone();
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    Session::revoke(session_id, credentials.user_id().clone(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    tokens::clear_cookies(&cookies);
+
+    Ok(())
+}
+
+/// Logs out of every session belonging to the current user, on
+/// every device, including the one making the request.
+/// 
+/// This endpoint requires a bearer token to be provided in the
+/// request headers.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/logout-all",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully logged out of every session"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn logout_all(
+    State(ctx): State<ApiContext>,
+    cookies: Cookies,
+    credentials: Credentials,
+) -> Result<()> {
+    credentials.require_session()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    Session::revoke_all(credentials.user_id().clone(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    tokens::clear_cookies(&cookies);
+
+    Ok(())
+}
+
+/// Lists the most recent logins of the current user, along with
+/// the ip address and user agent of each, newest first.
+/// 
+/// This endpoint requires a bearer token to be provided in the
+/// request headers.
+/// 
+#[utoipa::path(
+    get,
+    path = "/users/logins",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully retrieved login history", body = [LoginHistory], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn get_login_history(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+) -> Result<Json<Vec<LoginHistory>>> {
+    credentials.require_unrestricted()?;
+
+    LoginHistory::get_many_from_user(credentials.user_id().clone(), &ctx.pool)
+        .await
+        .map(Json)
+        .map_err(|error| error.into())
+}
+
+/// Lists the current user's sessions which have neither been
+/// revoked nor expired, marking the one the request was made with.
+/// 
+/// This endpoint requires a bearer token to be provided in the
+/// request headers.
+/// 
+#[utoipa::path(
+    get,
+    path = "/users/sessions",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully retrieved sessions", body = [ActiveSession], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn get_sessions(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+) -> Result<Json<Vec<ActiveSession>>> {
+    credentials.require_unrestricted()?;
+
+    let current = credentials.require_session().ok();
+
+    let sessions = Session::get_active_from_user(credentials.user_id().clone(), &ctx.pool)
+        .await?
+        .into_iter()
+        .map(|session| ActiveSession {
+            current: current.is_some_and(|id| id.0 == session.id.0),
+            session,
+        })
+        .collect();
+
+    Ok(Json(sessions))
+}
+
+/// Ends one of the current user's sessions, after which neither
+/// its access token nor its refresh token can be used. Ending the
+/// session the request was made with logs the user out.
+/// 
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to end sessions.
+/// 
+#[utoipa::path(
+    delete,
+    path = "/users/sessions/{id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the session", max_length = 16, min_length = 16)),
+    responses(
+        (status = 200, description = "Successfully ended the session"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, personal access tokens cannot end sessions"),
+        (status = 404, description = "Not found, you have no session with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn revoke_session(
+    State(ctx): State<ApiContext>,
+    Path(session_id): Path<SessionId>,
+    cookies: Cookies,
+    credentials: Credentials,
+) -> Result<()> {
+    let is_current = credentials.require_session()?.0 == session_id.0;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    if !Session::revoke(session_id, credentials.user_id().clone(), &mut transaction).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    transaction.commit().await?;
+
+    if is_current {
+        tokens::clear_cookies(&cookies);
+    }
+
+    Ok(())
+}
+
+/// Starts exporting everything the instance stores about the current
+/// user. The archive is put together in the background, its status
+/// can be checked at `/users/exports/{id}` and the user is notified
+/// once it can be downloaded.
+///
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to export data.
+///
+#[utoipa::path(
+    post,
+    path = "/users/exports",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 202, description = "Successfully started the export", body = DataExport, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, personal access tokens cannot export data"),
+        (status = 409, description = "Conflict, an export is already being prepared"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn create_data_export(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+) -> Result<(StatusCode, Json<DataExport>)> {
+    credentials.require_session()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+    let export = DataExport::create(credentials.user_id().clone(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    let pool = ctx.pool.clone();
+    let mut pending = export.clone();
+
+    tokio::spawn(async move {
+        if let Err(e) = pending.complete(&pool).await {
+            tracing::error!("Failed to export data for user {}: {:?}", pending.user_id.0, e);
+
+            if let Err(e) = pending.fail(&pool).await {
+                tracing::error!("Failed to mark data export {} as failed: {}", pending.id.0, e);
+            }
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(export)))
+}
+
+/// Lists the current user's data exports, newest first.
+///
+/// This endpoint requires a bearer token to be provided in the
+/// request headers.
+///
+#[utoipa::path(
+    get,
+    path = "/users/exports",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully retrieved exports", body = [DataExport], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn get_data_exports(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+) -> Result<Json<Vec<DataExport>>> {
+    credentials.require_unrestricted()?;
+
+    let exports = DataExport::get_many_from_user(credentials.user_id().clone(), &ctx.pool).await?;
+
+    Ok(Json(exports))
+}
+
+/// Fetches the status of one of the current user's data exports.
+///
+/// This endpoint requires a bearer token to be provided in the
+/// request headers.
+///
+#[utoipa::path(
+    get,
+    path = "/users/exports/{id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the export", max_length = 12, min_length = 12)),
+    responses(
+        (status = 200, description = "Successfully retrieved the export", body = DataExport, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 404, description = "Not found, you have no export with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn get_data_export(
+    State(ctx): State<ApiContext>,
+    Path(export_id): Path<DataExportId>,
+    credentials: Credentials,
+) -> Result<Json<DataExport>> {
+    credentials.require_unrestricted()?;
+
+    DataExport::get(export_id, credentials.user_id().clone(), &ctx.pool)
+        .await?
+        .ok_or(ApiError::NotFound)
+        .map(Json)
+}
+
+/// Downloads the JSON archive of one of the current user's data
+/// exports, once it is complete and until it expires.
+///
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to export data.
+///
+#[utoipa::path(
+    get,
+    path = "/users/exports/{id}/download",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the export", max_length = 12, min_length = 12)),
+    responses(
+        (status = 200, description = "The export's archive", content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, personal access tokens cannot export data"),
+        (status = 404, description = "Not found, you have no export with this id or it is not ready or has expired"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn download_data_export(
+    State(ctx): State<ApiContext>,
+    Path(export_id): Path<DataExportId>,
+    credentials: Credentials,
+) -> Result<Response> {
+    credentials.require_session()?;
+
+    let filename = format!("attachment; filename=\"nineyards-export-{}.json\"", export_id.0);
+
+    let archive = DataExport::get_archive(export_id, credentials.user_id().clone(), &ctx.pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let headers = [
+        (header::CONTENT_TYPE, "application/json".to_string()),
+        (header::CONTENT_DISPOSITION, filename),
+    ];
+
+    Ok((headers, archive).into_response())
+}
+
+/// Lists the personal access tokens belonging to the current user.
+/// The tokens themselves are never returned, only their details.
+/// 
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to manage other tokens.
+/// 
+#[utoipa::path(
+    get,
+    path = "/users/tokens",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully retrieved tokens", body = [PersonalAccessToken], content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, personal access tokens cannot manage tokens"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn get_access_tokens(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+) -> Result<Json<Vec<PersonalAccessToken>>> {
+    credentials.require_session()?;
+
+    PersonalAccessToken::get_many_from_user(credentials.user_id().clone(), &ctx.pool)
+        .await
+        .map(Json)
+        .map_err(|error| error.into())
+}
+
+/// Creates a personal access token for scripts and integrations to
+/// authenticate with, by placing it in the request headers like so
+/// 
+/// Authorization: Bearer <token>
+/// 
+/// The token is only returned by this request and cannot be fetched
+/// again later.
+/// 
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to manage other tokens.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/tokens",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = PersonalAccessTokenBuilder, description = "Details of the token to create", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully created a token", body = CreatedPersonalAccessToken, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, personal access tokens cannot manage tokens"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn create_access_token(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+    Json(form): Json<PersonalAccessTokenBuilder>,
+) -> Result<Json<CreatedPersonalAccessToken>> {
+    credentials.require_session()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let token = PersonalAccessToken::create(form, credentials.user_id().clone(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(Json(token))
+}
+
+/// Revokes one of the current user's personal access tokens, after
+/// which it can no longer be used.
+/// 
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to manage other tokens.
+/// 
+#[utoipa::path(
+    delete,
+    path = "/users/tokens/{id}",
+    context_path = "/api/v1",
+    tag = "v1",
+    params(("id" = String, Path, description = "The id of the token", max_length = 12, min_length = 12)),
+    responses(
+        (status = 200, description = "Successfully revoked the token"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, personal access tokens cannot manage tokens"),
+        (status = 404, description = "Not found, you have no token with this id"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn revoke_access_token(
+    State(ctx): State<ApiContext>,
+    Path(token_id): Path<PersonalAccessTokenId>,
+    credentials: Credentials,
+) -> Result<()> {
+    credentials.require_session()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    if !PersonalAccessToken::revoke(token_id, credentials.user_id().clone(), &mut transaction).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Sends an email containing a single use password reset link to the
+/// given address. To avoid revealing which addresses have accounts this
+/// always succeeds, even if no user has the given address.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/forgot-password",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = ForgotPassword, description = "The account to recover", content_type = "application/json"),
+    responses(
+        (status = 200, description = "If an account exists a reset email has been sent"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+async fn forgot_password(
+    State(ctx): State<ApiContext>,
+    Json(form): Json<ForgotPassword>,
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let Some(user) = User::get_by_email(&form.email, &mut *transaction).await? else {
+        return Ok(());
+    };
+
+    let (_, token) = PasswordReset::create(user.id.clone(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    let mail = Mail {
+        to: user.email,
+        subject: "Reset your Nine Yards password".into(),
+        body: format!(
+            "Hi {},\n\n\
+            Someone asked to reset the password for your account. If this was you, \
+            use the link below to choose a new password. The link expires in one hour.\n\n\
+            {}/reset-password?token={}\n\n\
+            If you did not ask for this you can ignore this email.",
+            user.username, ctx.settings.frontend_url, token
+        ),
+    };
+
+    if let Err(e) = ctx.mailer.send(mail).await {
+        tracing::error!("Failed to send password reset email: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Sets a new password using a token sent by the forgot password
+/// endpoint. Each token can only be used once, and every existing
+/// session of the user is logged out.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/reset-password",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = ResetPassword, description = "The reset token and new password", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully reset the password"),
+        (status = 401, description = "Unauthorized, the reset token is invalid, used or expired"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+async fn reset_password(
+    State(ctx): State<ApiContext>,
+    Json(form): Json<ResetPassword>,
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let user_id = PasswordReset::redeem(&form.token, &mut transaction).await?;
+
+    User::set_password(user_id.clone(), form.password, &mut transaction).await?;
+    Session::revoke_all(user_id, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Verifies the user's email address using a token sent to it on
+/// registration. Each token can only be used once.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/verify-email",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = VerifyEmail, description = "The verification token", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully verified the email address"),
+        (status = 401, description = "Unauthorized, the verification token is invalid, used or expired"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+async fn verify_email(
+    State(ctx): State<ApiContext>,
+    Json(form): Json<VerifyEmail>,
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let verification = EmailVerification::redeem(&form.token, &mut transaction).await?;
+
+    // The token is for an address the user no longer uses
+    if !User::mark_verified(verification.user_id, &verification.email, &mut transaction).await? {
+        return Err(ApiError::Unauthorized);
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Sends a new verification link to the given address, replacing
+/// any sent before. To avoid revealing which addresses have accounts
+/// this always succeeds, even if no unverified user has the address.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/verify-email/resend",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = ResendVerification, description = "The address to verify", content_type = "application/json"),
+    responses(
+        (status = 200, description = "If an unverified account exists a verification email has been sent"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+async fn resend_verification(
+    State(ctx): State<ApiContext>,
+    Json(form): Json<ResendVerification>,
+) -> Result<()> {
+    let mut transaction = ctx.pool.begin().await?;
+
+    let Some(user) = User::get_by_email(&form.email, &mut *transaction)
+        .await?
+        .filter(|user| !user.verified) else {
+        return Ok(());
+    };
+
+    let (_, token) = EmailVerification::create(user.id.clone(), user.email.clone(), &mut transaction).await?;
+    transaction.commit().await?;
+
+    send_verification_email(&ctx, &user, &token).await;
+
+    Ok(())
+}
+
+/// Fetches whether the current user has enabled two-factor
+/// authentication and how many recovery codes they have left.
+/// 
+#[utoipa::path(
+    get,
+    path = "/users/2fa",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully retrieved two-factor status", body = TwoFactorStatus, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, the access token is restricted to certain projects"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn get_two_factor_status(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+) -> Result<Json<TwoFactorStatus>> {
+    credentials.require_unrestricted()?;
+
+    TwoFactor::get_status(credentials.user_id().clone(), &ctx.pool)
+        .await
+        .map(Json)
+        .map_err(|error| error.into())
+}
+
+/// Starts enrolling in two-factor authentication, returning a new
+/// secret and an `otpauth://` uri to be shown as a QR code. Codes
+/// are not required until the enrolment is confirmed.
+/// 
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to manage two-factor authentication.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/2fa/enrol",
+    context_path = "/api/v1",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully started enrolment", body = TotpEnrolment, content_type = "application/json"),
+        (status = 400, description = "Bad request, two-factor authentication is already enabled"),
+        (status = 401, description = "Unauthorized, provide a bearer token"),
+        (status = 403, description = "Forbidden, personal access tokens cannot manage two-factor authentication"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn enrol_two_factor(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+) -> Result<Json<TotpEnrolment>> {
+    credentials.require_session()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let user = User::get(credentials.user_id().clone(), &mut *transaction)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    let enrolment = TwoFactor::enrol(user.id, user.email, &mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(Json(enrolment))
+}
+
+/// Confirms enrolment with a code from the user's authenticator
+/// app, enabling two-factor authentication. A set of single use
+/// recovery codes is returned, which are not shown again.
+/// 
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to manage two-factor authentication.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/2fa/confirm",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = TotpCode, description = "A code from the authenticator app", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully enabled two-factor authentication", body = RecoveryCodes, content_type = "application/json"),
+        (status = 400, description = "Bad request, enrolment has not been started or is already confirmed"),
+        (status = 401, description = "Unauthorized, provide a bearer token, or the code is incorrect"),
+        (status = 403, description = "Forbidden, personal access tokens cannot manage two-factor authentication"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn confirm_two_factor(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+    Json(form): Json<TotpCode>,
+) -> Result<Json<RecoveryCodes>> {
+    credentials.require_session()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let two_factor = TwoFactor::get(credentials.user_id().clone(), &mut *transaction).await?;
+    let codes = two_factor.confirm(&form.code, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(Json(codes))
+}
+
+/// Disables two-factor authentication given a code from the user's
+/// authenticator app or one of their recovery codes.
+/// 
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to manage two-factor authentication.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/2fa/disable",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = TotpCode, description = "A code from the authenticator app or a recovery code", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully disabled two-factor authentication"),
+        (status = 401, description = "Unauthorized, provide a bearer token, or the code is incorrect"),
+        (status = 403, description = "Forbidden, personal access tokens cannot manage two-factor authentication"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn disable_two_factor(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+    Json(form): Json<TotpCode>,
+) -> Result<()> {
+    credentials.require_session()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let two_factor = TwoFactor::get(credentials.user_id().clone(), &mut *transaction).await?;
+    two_factor.disable(&form.code, &mut transaction).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Replaces the current user's recovery codes with a new set given a
+/// code from their authenticator app, any unused codes stop working.
+/// 
+/// This endpoint requires a session, personal access tokens cannot
+/// be used to manage two-factor authentication.
+/// 
+#[utoipa::path(
+    post,
+    path = "/users/2fa/recovery-codes",
+    context_path = "/api/v1",
+    tag = "v1",
+    request_body(content = TotpCode, description = "A code from the authenticator app or a recovery code", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Successfully generated new recovery codes", body = RecoveryCodes, content_type = "application/json"),
+        (status = 401, description = "Unauthorized, provide a bearer token, or the code is incorrect"),
+        (status = 403, description = "Forbidden, personal access tokens cannot manage two-factor authentication"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(("Bearer" = [])),
+)]
+async fn regenerate_recovery_codes(
+    State(ctx): State<ApiContext>,
+    credentials: Credentials,
+    Json(form): Json<TotpCode>,
+) -> Result<Json<RecoveryCodes>> {
+    credentials.require_session()?;
+
+    let mut transaction = ctx.pool.begin().await?;
+
+    let two_factor = TwoFactor::get(credentials.user_id().clone(), &mut *transaction).await?;
+
+    if !two_factor.verify(&form.code, &mut transaction).await? {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let codes = two_factor.generate_recovery_codes(&mut transaction).await?;
+    transaction.commit().await?;
+
+    Ok(Json(codes))
+}
+
+/// Notifies the user of a change to their account, so they can
+/// act on it if it was not them
+/// 
+async fn notify_security_change(
+    user: &User,
+    change: String,
+    transaction: &mut sqlx::Transaction<'_, Database>,
+) -> Result<()> {
+    let builder = NotificationBuilder {
+        body: format!(
+            "{} If this wasn't you, reset your password and log out of every session.",
+            change
+        ),
+        actions: Vec::new(),
+    };
+
+    Notification::send(builder, user.id.clone(), transaction).await?;
+
+    Ok(())
+}
+
+/// Emails the user a link to verify their address with, failing to
+/// send it is logged rather than failing the request
+/// 
+async fn send_verification_email(
+    ctx: &ApiContext,
+    user: &User,
+    token: &str,
+) {
+    let mail = Mail {
+        to: user.email.clone(),
+        subject: "Verify your Nine Yards email address".into(),
+        body: format!(
+            "Hi {},\n\n\
+            Please confirm this is your email address by following the link below. \
+            The link expires in 24 hours.\n\n\
+            {}/verify-email?token={}\n\n\
+            If you did not create a Nine Yards account you can ignore this email.",
+            user.username, ctx.settings.frontend_url, token
+        ),
+    };
+
+    if let Err(e) = ctx.mailer.send(mail).await {
+        tracing::error!("Failed to send verification email: {}", e);
+    }
+}
+
+/// Starts a new session for the given user, records the login in
+/// their history and adds the session's access and refresh tokens
+/// to the response's cookies
+/// 
+async fn start_session(
+    ctx: &ApiContext,
+    user: &User,
+    client: &ClientInfo,
+    cookies: &Cookies,
+    transaction: &mut sqlx::Transaction<'_, Database>,
+) -> Result<()> {
+    let (session, refresh_token) = Session::create(user.id.clone(), client, transaction).await?;
+    LoginHistory::create(user.id.clone(), session.id.clone(), client, transaction).await?;
+
+    cookies.add(Token::encode(user, &session, &ctx.keyring).into_cookie());
+    cookies.add(RefreshToken(refresh_token).into_cookie());
+
+    Ok(())
 }
\ No newline at end of file
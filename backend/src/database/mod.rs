@@ -1,19 +1,49 @@
 pub mod sql;
 
-#[cfg(any(feature = "sqlite"))]
+#[cfg(feature = "sqlite")]
 pub type SqlPool = sqlx::SqlitePool;
-#[cfg(any(feature = "sqlite"))]
+#[cfg(feature = "sqlite")]
 pub type PoolOptions = sqlx::sqlite::SqlitePoolOptions;
-#[cfg(any(feature = "sqlite"))]
+#[cfg(feature = "sqlite")]
 pub type Database = sqlx::Sqlite;
-#[cfg(any(feature = "sqlite"))]
+#[cfg(feature = "sqlite")]
 pub type TypeInfo = sqlx::sqlite::SqliteTypeInfo;
 
-#[cfg(any(feature = "postgres"))]
+#[cfg(feature = "postgres")]
 pub type SqlPool = sqlx::PgPool;
-#[cfg(any(feature = "postgres"))]
+#[cfg(feature = "postgres")]
 pub type PoolOptions = sqlx::postgres::PgPoolOptions;
-#[cfg(any(feature = "postgres"))]
+#[cfg(feature = "postgres")]
 pub type Database = sqlx::Postgres;
-#[cfg(any(feature = "postgres"))]
-pub type TypeInfo = sqlx::postgres::PostgresTypeInfo;
\ No newline at end of file
+#[cfg(feature = "postgres")]
+pub type TypeInfo = sqlx::postgres::PostgresTypeInfo;
+
+/// Implements the conversions needed to read a type stored as JSON in
+/// a text column with a type override, e.g. `content AS "content: Type"`.
+/// Unlike a `From<String>` conversion, JSON which can't be read fails the
+/// query rather than being silently replaced.
+///
+#[macro_export]
+macro_rules! json_column {
+    ($struct:ty) => {
+        impl sqlx::Type<$crate::database::Database> for $struct {
+            fn type_info() -> $crate::database::TypeInfo {
+                <::std::string::String as sqlx::Type<$crate::database::Database>>::type_info()
+            }
+
+            fn compatible(ty: &$crate::database::TypeInfo) -> ::std::primitive::bool {
+                <::std::string::String as sqlx::Type<$crate::database::Database>>::compatible(ty)
+            }
+        }
+
+        impl<'r> sqlx::Decode<'r, $crate::database::Database> for $struct {
+            fn decode(
+                value: <$crate::database::Database as sqlx::database::HasValueRef<'r>>::ValueRef,
+            ) -> ::std::result::Result<Self, sqlx::error::BoxDynError> {
+                let value = <&str as sqlx::Decode<$crate::database::Database>>::decode(value)?;
+
+                Ok(serde_json::from_str(value)?)
+            }
+        }
+    };
+}
@@ -1,4 +1,4 @@
-use axum::http::header::WWW_AUTHENTICATE;
+use axum::http::header::{RETRY_AFTER, WWW_AUTHENTICATE};
 use axum::http::{HeaderMap, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
@@ -33,10 +33,41 @@ pub enum ApiError {
     #[error("insufficient permissions to perform this action")]
     Forbidden,
 
+    // Return '403 Forbidden' for when the instance requires
+    // verified email addresses and the user, or a user they
+    // are acting on, has not verified theirs yet
+    #[error("the email address of this account has not been verified")]
+    EmailNotVerified,
+
+    // Return '403 Forbidden' for when the instance requires
+    // members who can delete or own a project to have enabled
+    // two-factor authentication and they have not
+    #[error("two-factor authentication must be enabled to access this project")]
+    TwoFactorRequired,
+
+    // Return '403 Forbidden' for when an administrator has
+    // suspended the account
+    #[error("this account has been suspended")]
+    AccountSuspended,
+
     // Return '404' Not Found
     #[error("resource not found")]
     NotFound,
 
+    // Return '409 Conflict' for when a request cannot be carried
+    // out in the current state of the resource, with a message
+    // explaining what has to change first
+    #[error("{0}")]
+    Conflict(&'static str),
+
+    // Return '429 Too Many Requests' with a `Retry-After`
+    // header, for when a client has failed to log in too many
+    // times and has to wait before trying again
+    #[error("too many failed attempts, try again in {retry_after} seconds")]
+    TooManyRequests {
+        retry_after: u64,
+    },
+
     /// Return `422 Unprocessable Entity`
     ///
     /// This also serializes the `errors` map to JSON to satisfy the requirement for
@@ -116,8 +147,13 @@ impl ApiError {
             Self::BadRequest => StatusCode::BAD_REQUEST,
             Self::Unauthorized => StatusCode::UNAUTHORIZED,
             Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::EmailNotVerified => StatusCode::FORBIDDEN,
+            Self::TwoFactorRequired => StatusCode::FORBIDDEN,
+            Self::AccountSuspended => StatusCode::FORBIDDEN,
             Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
             Self::UnprocessableEntity { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
             _ => StatusCode::INTERNAL_SERVER_ERROR, 
         }
     }
@@ -151,6 +187,17 @@ impl IntoResponse for ApiError {
             }
 
 
+            Self::TooManyRequests { retry_after } => {
+                return (
+                    self.status_code(),
+                    [(RETRY_AFTER, HeaderValue::from(retry_after))]
+                        .into_iter()
+                        .collect::<HeaderMap>(),
+                    self.to_string(),
+                )
+                    .into_response();
+            }
+
             Self::Sqlx(ref e) => {
                 tracing::error!("SQLx error: {:?}", e);
             }
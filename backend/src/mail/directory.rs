@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use axum::async_trait;
+use lettre::message::Mailbox;
+use lettre::{AsyncFileTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::{Mail, MailError, Mailer};
+
+/// Writes every email to a directory as an `.eml` file instead of
+/// sending it, for development and testing without a mail server
+///
+pub struct DirectoryMailer {
+    transport: AsyncFileTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl DirectoryMailer {
+    pub fn new(directory: PathBuf, from: Mailbox) -> Result<Self, MailError> {
+        std::fs::create_dir_all(&directory)?;
+
+        Ok(Self {
+            transport: AsyncFileTransport::new(directory),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for DirectoryMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(mail.to.parse()?)
+            .subject(mail.subject)
+            .body(mail.body)?;
+
+        self.transport.send(message).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use lettre::address::AddressError;
+use lettre::message::Mailbox;
+
+use crate::cli::{MailArguments, MailTransport};
+
+pub mod smtp;
+pub mod directory;
+
+#[derive(thiserror::Error, Debug)]
+pub enum MailError {
+    #[error("Invalid email address: {0}")]
+    Address(#[from] AddressError),
+    #[error("Failed to build email: {0}")]
+    Build(#[from] lettre::error::Error),
+    #[error("Failed to send email over smtp: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("Failed to write email to directory: {0}")]
+    File(#[from] lettre::transport::file::Error),
+    #[error("Failed to create mail directory: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("No smtp url was provided, set SMTP_URL or use another transport")]
+    MissingSmtpUrl,
+}
+
+/// A plain text email to be sent to a single recipient
+///
+pub struct Mail {
+    /// The recipient's email address
+    pub to: String,
+    /// The subject line of the email
+    pub subject: String,
+    /// The plain text body of the email
+    pub body: String,
+}
+
+/// Something that can deliver emails, such as an smtp server or,
+/// when testing offline, a directory the emails are written to.
+///
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+/// Creates the mailer selected by the start command's arguments
+///
+pub fn configure(
+    MailArguments {
+        mail_transport,
+        mail_from,
+        smtp_url,
+        mail_directory,
+    }: MailArguments
+) -> Result<Arc<dyn Mailer>, MailError> {
+    let from: Mailbox = mail_from.parse()?;
+
+    let mailer: Arc<dyn Mailer> = match mail_transport {
+        MailTransport::Smtp => {
+            let url = smtp_url.ok_or(MailError::MissingSmtpUrl)?;
+            Arc::new(smtp::SmtpMailer::new(&url, from)?)
+        }
+        MailTransport::Directory => {
+            Arc::new(directory::DirectoryMailer::new(mail_directory, from)?)
+        }
+    };
+
+    Ok(mailer)
+}
@@ -15,6 +15,8 @@ pub mod error;
 pub mod utilities;
 pub mod cli;
 pub mod openapi;
+pub mod mail;
+pub mod oidc;
 
 #[tokio::main]
 async fn main() -> ExitCode {
@@ -1,37 +1,75 @@
-use axum::extract::{FromRequestParts, Path, FromRef};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, FromRequestParts, Path, FromRef};
+use axum::http::{header, Method};
 use axum::http::request::Parts;
 use axum::{async_trait, RequestPartsExt, Extension};
 use tower_cookies::Cookies;
 
 use crate::error::ApiError;
-use crate::models::id::{ProjectId, UserId};
-use crate::models::projects::ProjectMember;
+use crate::middleware::user_authentication::Credentials;
+use crate::models::access_tokens::Scopes;
+use crate::models::id::{OrganisationId, ProjectId, UserId};
+use crate::models::login_history::ClientInfo;
+use crate::models::organisations::OrganisationMember;
+use crate::models::projects::{Permissions, Project, ProjectMember};
+use crate::models::two_factor::TwoFactor;
 use crate::models::tokens::Token;
 use crate::models::users::User;
 use crate::api::ApiContext;
 
 #[async_trait]
-impl<S> FromRequestParts<S> for UserId
+impl<S> FromRequestParts<S> for Credentials
 where
     S: Send + Sync,
+    ApiContext: FromRef<S>
 {
     type Rejection = ApiError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        let Extension(cookies) = parts
-            .extract::<Extension<Cookies>>()
-            .await
-            .map_err(|_| ApiError::Unauthorized)?;
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // Requests with an authorization header have already been
+        // authenticated by the `UserAuthenticationLayer`
+        let credentials = match parts.extensions.get::<Credentials>() {
+            Some(credentials) => credentials.clone(),
+            None => {
+                let Extension(cookies) = parts
+                    .extract::<Extension<Cookies>>()
+                    .await
+                    .map_err(|_| ApiError::Unauthorized)?;
 
-        let token = Token::from_jar(cookies)
-            .ok_or(ApiError::Unauthorized)?;
+                let token = Token::from_jar(&cookies)
+                    .ok_or(ApiError::Unauthorized)?;
+
+                let ctx = ApiContext::from_ref(state);
 
-        let claims = token
-            .decode()
-            .map_err(|_| ApiError::Unauthorized)?
-            .claims;
+                Credentials::from_access_token(&token, &ctx).await?
+            }
+        };
+
+        let required = match parts.method {
+            Method::GET | Method::HEAD | Method::OPTIONS => Scopes::READ,
+            _ => Scopes::WRITE,
+        };
 
-        Ok(UserId(claims.user_id))
+        credentials.check_scopes(required)?;
+
+        Ok(credentials)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for UserId
+where
+    S: Send + Sync,
+    ApiContext: FromRef<S>
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let credentials = Credentials::from_request_parts(parts, state).await?;
+
+        Ok(credentials.user_id().clone())
     }
 }
 
@@ -56,6 +94,72 @@ where
     }
 }
 
+/// An administrator of the instance. Administration can only be done
+/// from a session, never with a personal access token.
+///
+pub struct Admin(pub User);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Admin
+where
+    S: Send + Sync,
+    ApiContext: FromRef<S>
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let credentials = Credentials::from_request_parts(parts, state).await?;
+        credentials.require_session()?;
+
+        let ctx = ApiContext::from_ref(state);
+
+        let user = User::get(credentials.user_id().clone(), &ctx.pool)
+            .await?
+            .ok_or(ApiError::Unauthorized)?;
+
+        if !user.is_admin {
+            return Err(ApiError::Forbidden);
+        }
+
+        Ok(Admin(user))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+    ApiContext: FromRef<S>
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ctx = ApiContext::from_ref(state);
+
+        let header = |name| parts.headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+
+        // The left most address is the client, any others are
+        // proxies the request passed through
+        let forwarded_for = ctx.settings.trust_forwarded_for
+            .then(|| header(header::HeaderName::from_static("x-forwarded-for")))
+            .flatten()
+            .and_then(|value| value.split(',').next().map(|ip| ip.trim().to_string()));
+
+        let ip_address = forwarded_for.or_else(|| parts.extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string())
+        );
+
+        Ok(ClientInfo {
+            ip_address,
+            user_agent: header(header::USER_AGENT),
+        })
+    }
+}
+
 #[async_trait]
 impl<S> FromRequestParts<S> for ProjectMember
 where
@@ -65,33 +169,127 @@ where
     type Rejection = ApiError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let user_id = UserId::from_request_parts(parts, state).await?;
+        let credentials = visitor_credentials(parts, state).await?;
 
-        let project_id = parts
-            .extract::<Path<String>>()
-            .await
-            .map(|p| ProjectId(p.0))
-            .map_err(|_| ApiError::Forbidden)?;
+        let project_id = ProjectId(extract_id(parts).await?);
 
         let ctx = ApiContext::from_ref(state);
 
-        let project_member = ProjectMember::get_from_user(user_id, project_id, &ctx.pool)
+        resolve_membership(credentials, project_id, &ctx).await
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for OrganisationMember
+where
+    ApiContext: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let credentials = Credentials::from_request_parts(parts, state).await?;
+        credentials.require_unrestricted()?;
+
+        let organisation_id = OrganisationId(extract_id(parts).await?);
+
+        let ctx = ApiContext::from_ref(state);
+
+        OrganisationMember::get_from_user(credentials.user_id().clone(), organisation_id, &ctx.pool)
             .await?
-            .ok_or(ApiError::Forbidden)?;
+            .ok_or(ApiError::Forbidden)
+    }
+}
 
-        if !project_member.accepted {
-            return Err(ApiError::Forbidden);
-        }
+/// Extracts the credentials of a request which may be made by a visitor
+/// to a public project. Requests without credentials are only let through
+/// to read projects if the instance allows anonymous access, requests with
+/// invalid credentials are always rejected.
+/// 
+async fn visitor_credentials<S>(parts: &mut Parts, state: &S) -> Result<Option<Credentials>, ApiError>
+where
+    ApiContext: FromRef<S>,
+    S: Send + Sync,
+{
+    let has_cookie = parts.extensions
+        .get::<Cookies>()
+        .is_some_and(|cookies| Token::from_jar(cookies).is_some());
 
-        Ok(project_member)
+    if parts.extensions.get::<Credentials>().is_some() || has_cookie {
+        return Credentials::from_request_parts(parts, state).await.map(Some);
     }
+
+    let ctx = ApiContext::from_ref(state);
+    let reading = matches!(parts.method, Method::GET | Method::HEAD | Method::OPTIONS);
+
+    if !ctx.settings.allow_anonymous_access || !reading {
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(None)
+}
+
+/// Resolves the membership a request acts with in the given project, which
+/// is a visitor membership for users who are not members of public projects
+/// 
+async fn resolve_membership(
+    credentials: Option<Credentials>,
+    project_id: ProjectId,
+    ctx: &ApiContext,
+) -> Result<ProjectMember, ApiError> {
+    if let Some(credentials) = &credentials {
+        credentials.check_project(&project_id)?;
+    }
+
+    // Projects in the trash are treated as if they no longer exist
+    let project = Project::get(project_id, &ctx.pool)
+        .await?
+        .filter(|project| project.trashed.is_none())
+        .ok_or(ApiError::Forbidden)?;
+
+    let user_id = credentials.map(|credentials| credentials.user_id().clone());
+
+    let member = ProjectMember::resolve(user_id, &project, &ctx.pool)
+        .await?
+        .ok_or(ApiError::Forbidden)?;
+
+    require_two_factor(&member, &project, ctx).await?;
+
+    Ok(member)
 }
 
+/// Checks the member has enabled two-factor authentication if the
+/// instance requires it of them, which is the case for the project's
+/// owner and anyone who can delete it. Handlers which look up the
+/// membership themselves have to call this too
+/// 
+pub async fn require_two_factor(member: &ProjectMember, project: &Project, ctx: &ApiContext) -> Result<(), ApiError> {
+    if !ctx.settings.require_two_factor {
+        return Ok(());
+    }
+
+    let is_owner = project.owner.0 == member.user_id.0;
 
-async fn extract_id(parts: &mut Parts) -> Result<Path<String>, ApiError> {
-    parts.extract::<Path<String>>()
+    if !is_owner && !member.permissions.contains(Permissions::DELETE_RPOJECT) {
+        return Ok(());
+    }
+
+    if !TwoFactor::get(member.user_id.clone(), &ctx.pool).await?.enabled {
+        return Err(ApiError::TwoFactorRequired);
+    }
+
+    Ok(())
+}
+
+/// Takes the `:id` parameter from the request's path, routes can
+/// have further parameters such as a member's id after it
+/// 
+async fn extract_id(parts: &mut Parts) -> Result<String, ApiError> {
+    parts.extract::<Path<HashMap<String, String>>>()
         .await
-        .map_err(|_| ApiError::Forbidden)
+        .ok()
+        .and_then(|Path(mut params)| params.remove("id"))
+        .ok_or(ApiError::Forbidden)
 }
 
 macro_rules! impl_from_request_parts {
@@ -113,25 +311,23 @@ macro_rules! impl_from_request_parts {
             type Rejection = crate::error::ApiError;
 
             async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-                let user_id = crate::models::id::UserId::from_request_parts(parts, state).await?;
+                let credentials = visitor_credentials(parts, state).await?;
         
-                let Path(path_id) = extract_id(parts).await?;
+                let path_id = extract_id(parts).await?;
                 
                 let ctx = ApiContext::from_ref(state);
                 
-                let sql = format!("SELECT id FROM {} WHERE id = $1", $table_name);
+                let sql = format!("SELECT project_id FROM {} WHERE id = $1", $table_name);
         
                 let project_id = sqlx::query_as::<_, ProjectId>(&sql)
                     .bind(path_id)
                     .fetch_optional(&ctx.pool)
                     .await?
                     .ok_or(ApiError::Forbidden)?;
-        
-                let member = ProjectMember::get_accepted_from_user(user_id, project_id, &ctx.pool)
-                    .await?
-                    .ok_or(ApiError::Forbidden)?;
 
-                Ok($struct(member))
+                resolve_membership(credentials, project_id, &ctx)
+                    .await
+                    .map($struct)
             }
         }
     )*};
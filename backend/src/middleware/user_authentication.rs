@@ -4,36 +4,187 @@ use axum::response::IntoResponse;
 use futures_util::future::BoxFuture;
 use tower_http::auth::AsyncAuthorizeRequest;
 
+use crate::api::ApiContext;
 use crate::error::ApiError;
+use crate::models::access_tokens::{PersonalAccessToken, Scopes, TokenProjects};
+use crate::models::id::{PersonalAccessTokenId, ProjectId, SessionId, UserId};
+use crate::models::sessions::Session;
 use crate::models::tokens::Token;
 
-#[derive(Clone, Copy)]
-struct UserAuthenticationLayer;
+/// The identity a request is being made with, resolved either from
+/// the access token cookie or from an `Authorization: Bearer` header.
+///
+#[derive(Clone)]
+pub enum Credentials {
+    /// An access token issued on login, which has the same access
+    /// as the user themselves
+    Session {
+        user_id: UserId,
+        session_id: SessionId,
+    },
+    /// A personal access token, which is limited to its scopes
+    /// and, optionally, a set of projects
+    PersonalAccessToken {
+        user_id: UserId,
+        token_id: PersonalAccessTokenId,
+        scopes: Scopes,
+        projects: TokenProjects,
+    },
+}
+
+impl Credentials {
+    /// Verifies a session access token and checks that the session it
+    /// was issued for has not been revoked
+    ///
+    pub async fn from_access_token(
+        token: &Token,
+        ctx: &ApiContext,
+    ) -> Result<Self, ApiError> {
+        let claims = token
+            .decode(&ctx.keyring)
+            .map_err(|_| ApiError::Unauthorized)?
+            .claims;
+
+        // Access tokens are short lived but are still rejected
+        // as soon as the session they were issued for is revoked
+        let session = Session::get(SessionId(claims.session_id), &ctx.pool)
+            .await?
+            .filter(|session| session.is_active() && session.user_id.0 == claims.user_id)
+            .ok_or(ApiError::Unauthorized)?;
+
+        Ok(Self::Session {
+            user_id: session.user_id,
+            session_id: session.id,
+        })
+    }
+
+    /// Resolves the contents of an `Authorization: Bearer` header, which
+    /// may either be a personal access token or a session access token
+    ///
+    pub async fn from_bearer(
+        bearer: &str,
+        ctx: &ApiContext,
+    ) -> Result<Self, ApiError> {
+        if !bearer.starts_with(PersonalAccessToken::PREFIX) {
+            return Self::from_access_token(&Token(bearer.to_string()), ctx).await;
+        }
+
+        let token = PersonalAccessToken::authenticate(bearer, &ctx.pool)
+            .await?
+            .ok_or(ApiError::Unauthorized)?;
+
+        Ok(Self::PersonalAccessToken {
+            user_id: token.user_id,
+            token_id: token.id,
+            scopes: token.scopes,
+            projects: token.projects,
+        })
+    }
+
+    pub fn user_id(&self) -> &UserId {
+        match self {
+            Self::Session { user_id, .. } => user_id,
+            Self::PersonalAccessToken { user_id, .. } => user_id,
+        }
+    }
+
+    /// Returns the session the request was made with, personal access
+    /// tokens are forbidden from using endpoints that require this such
+    /// as those managing the account's credentials
+    ///
+    pub fn require_session(&self) -> Result<&SessionId, ApiError> {
+        match self {
+            Self::Session { session_id, .. } => Ok(session_id),
+            Self::PersonalAccessToken { .. } => Err(ApiError::Forbidden),
+        }
+    }
+
+    /// Checks the credentials are not limited to a set of projects,
+    /// personal access tokens which are can't be used for anything
+    /// outside those projects such as creating or joining projects,
+    /// managing organisations or editing the account
+    ///
+    pub fn require_unrestricted(&self) -> Result<(), ApiError> {
+        match self {
+            Self::PersonalAccessToken { projects, .. } if !projects.0.is_empty() => {
+                Err(ApiError::Forbidden)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the credentials have the given scopes, sessions have
+    /// every scope
+    ///
+    pub fn check_scopes(&self, required: Scopes) -> Result<(), ApiError> {
+        match self {
+            Self::PersonalAccessToken { scopes, .. } if !scopes.contains(required) => {
+                Err(ApiError::Forbidden)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the credentials can be used on the given project, this
+    /// only fails for personal access tokens limited to other projects
+    ///
+    pub fn check_project(&self, project_id: &ProjectId) -> Result<(), ApiError> {
+        match self {
+            Self::PersonalAccessToken { projects, .. }
+                if !projects.0.is_empty() && !projects.0.iter().any(|p| p.0 == project_id.0) => {
+                Err(ApiError::Forbidden)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Authenticates requests which carry an `Authorization: Bearer` header
+/// and attaches the resulting `Credentials` to the request. Requests
+/// without the header are passed through unchanged and fall back to the
+/// access token cookie.
+///
+#[derive(Clone)]
+pub struct UserAuthenticationLayer {
+    ctx: ApiContext,
+}
+
+impl UserAuthenticationLayer {
+    pub fn new(ctx: ApiContext) -> Self {
+        Self { ctx }
+    }
+}
 
 impl <B> AsyncAuthorizeRequest<B> for UserAuthenticationLayer
-where   
+where
     B: Send + Sync + 'static
 {
 	type RequestBody = B;
 	type ResponseBody = BoxBody;
 	type Future = BoxFuture<'static, Result<Request<B>, Response<Self::ResponseBody>>>;
 
-    fn authorize(&mut self, request: Request<B>) -> Self::Future {
-        Box::pin(async {
-            let (mut parts, _body) = request.into_parts();
+    fn authorize(&mut self, mut request: Request<B>) -> Self::Future {
+        let ctx = self.ctx.clone();
+
+        Box::pin(async move {
+            let Some(value) = request.headers().get(header::AUTHORIZATION) else {
+                return Ok(request);
+            };
 
-            let token = parts.headers
-                .get(header::AUTHORIZATION)
-                .map(|v| v.to_str().unwrap().to_string())
-                .map(|t| Token(t))
+            let bearer = value
+                .to_str()
+                .ok()
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .map(|v| v.trim().to_string())
                 .ok_or(ApiError::Unauthorized.into_response())?;
 
-            let claims = token.decode()
-                .map_err(|_| ApiError::Unauthorized.into_response())?.claims;
+            let credentials = Credentials::from_bearer(&bearer, &ctx)
+                .await
+                .map_err(|e| e.into_response())?;
 
-            parts.extensions.insert(claims.user_id);
+            request.extensions_mut().insert(credentials);
 
-            Ok(Request::from_parts(parts, _body))
+            Ok(request)
         })
     }
-}
\ No newline at end of file
+}
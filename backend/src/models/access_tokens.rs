@@ -0,0 +1,379 @@
+use chrono::{NaiveDateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::database::Database;
+use crate::utilities::hash_secret;
+
+use super::id::{PersonalAccessTokenId, ProjectId, UserId};
+
+#[derive(Serialize, ToSchema)]
+pub struct PersonalAccessToken {
+    /// The token's id
+    ///
+    #[schema(example="123456789abc", min_length=12, max_length=12)]
+    pub id: PersonalAccessTokenId,
+    /// The id of the user the token acts on behalf of
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub user_id: UserId,
+    /// A name to help the user recognise the token
+    ///
+    #[schema(example="Deployment script")]
+    pub name: String,
+    /// A hash of the token itself
+    ///
+    #[serde(skip_serializing)]
+    pub token: String,
+    /// What the token is allowed to do
+    ///
+    #[schema(value_type = u64, example = 1)]
+    pub scopes: Scopes,
+    /// The datetime the token was created
+    ///
+    pub created: NaiveDateTime,
+    /// The datetime after which the token will no longer be
+    /// accepted, if any
+    ///
+    pub expires: Option<NaiveDateTime>,
+    /// The datetime the token was last used to authenticate a
+    /// request, if ever
+    ///
+    pub last_used: Option<NaiveDateTime>,
+    /// The projects the token is limited to, if empty the token
+    /// can be used on every project the user is a member of
+    ///
+    pub projects: TokenProjects,
+}
+
+/// Additional struct in order to be able to directly
+/// deserialze the projects field of the token
+///
+#[derive(Deserialize, Serialize, ToSchema, Clone, Default)]
+pub struct TokenProjects(pub Vec<ProjectId>);
+
+// The projects are aggregated into a JSON array
+crate::json_column!(TokenProjects);
+
+#[derive(Deserialize, ToSchema)]
+pub struct PersonalAccessTokenBuilder {
+    /// A name to help recognise the token
+    ///
+    #[schema(example="Deployment script")]
+    pub name: String,
+    /// What the token is allowed to do
+    ///
+    #[schema(value_type = u64, example = 1)]
+    pub scopes: Scopes,
+    /// The projects the token should be limited to, if none
+    /// are given it can be used on every project
+    ///
+    #[serde(default)]
+    pub projects: Vec<ProjectId>,
+    /// The datetime after which the token should no longer be
+    /// accepted, if not given the token does not expire
+    ///
+    pub expires: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreatedPersonalAccessToken {
+    /// The token's details
+    ///
+    #[serde(flatten)]
+    pub details: PersonalAccessToken,
+    /// The token to be placed in the authorization header, this
+    /// is not stored and cannot be retrieved again
+    ///
+    #[schema(example="nyp_V1StGXR8Z5jdHi6BmyTq2xLJm7uT3pEwNd2Kq9sF")]
+    pub secret: String,
+}
+
+bitflags::bitflags! {
+    #[derive(Serialize, Deserialize, ToSchema, Clone, Copy)]
+    #[serde(transparent)]
+    pub struct Scopes: u64 {
+        // Permission to make read only requests, such as
+        // fetching projects, tasks and notifications
+        //
+        const READ = 1 << 0;
+        // Permission to make requests that create, edit
+        // or remove data. The user's own project
+        // permissions still apply.
+        //
+        const WRITE = 1 << 1;
+    }
+}
+
+impl From<i64> for Scopes {
+    fn from(value: i64) -> Self {
+        Scopes::from_bits_truncate(value as u64)
+    }
+}
+
+impl PersonalAccessToken {
+    /// The prefix of every personal access token, used to tell them
+    /// apart from session access tokens
+    pub const PREFIX: &'static str = "nyp_";
+
+    /// Creates a new personal access token and inserts it into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: A `PersonalAccessTokenBuilder` containing the token's name, scopes and projects.
+    /// * `user_id`: The `UserId` of the user the token will act on behalf of.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<CreatedPersonalAccessToken, sqlx::error::Error>`, where:
+    /// - `Ok(token)` is returned with the token's details and its plain text secret.
+    /// - An `sqlx::error::Error` is returned if there is an error generating the id or executing the queries.
+    ///
+    pub async fn create(
+        form: PersonalAccessTokenBuilder,
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<CreatedPersonalAccessToken, sqlx::error::Error> {
+        let id = PersonalAccessTokenId::generate(&mut *transaction).await?;
+        let secret = format!("{}{}", Self::PREFIX, nanoid::nanoid!(40));
+
+        let token = Self {
+            id,
+            user_id,
+            name: form.name,
+            token: hash_secret(&secret),
+            scopes: form.scopes,
+            created: Utc::now().naive_utc(),
+            expires: form.expires,
+            last_used: None,
+            projects: TokenProjects(form.projects),
+        };
+
+        token.insert(&mut *transaction).await?;
+
+        Ok(CreatedPersonalAccessToken { details: token, secret })
+    }
+
+    /// Permanently revokes a token so it can no longer be used.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `PersonalAccessTokenId` of the token to revoke.
+    /// * `user_id`: The `UserId` of the token's owner, tokens belonging to other users are left untouched.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<bool, sqlx::error::Error>`, where:
+    /// - `Ok(true)` is returned if a matching token was revoked.
+    /// - `Ok(false)` is returned if no matching token was found.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the queries.
+    ///
+    pub async fn revoke(
+        id: PersonalAccessTokenId,
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<bool, sqlx::error::Error> {
+        sqlx::query!(
+            "
+            DELETE FROM personal_access_token_projects
+            WHERE token_id = (
+                SELECT id
+                FROM personal_access_tokens
+                WHERE id = $1
+                AND user_id = $2
+            )
+            ",
+            id,
+            user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let result = sqlx::query!(
+            "
+            DELETE FROM personal_access_tokens
+            WHERE id = $1
+            AND user_id = $2
+            ",
+            id,
+            user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Finds the token matching the given secret and records that it has
+    /// been used, tokens that have expired are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret`: The plain text token taken from the authorization header.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(token))` is returned if the secret belongs to a token which has not expired.
+    /// - `Ok(None)` is returned if it does not.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the queries.
+    ///
+    pub async fn authenticate<'a, E>(
+        secret: &str,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database> + Copy
+    {
+        let now = Utc::now().naive_utc();
+
+        let token = Self::get_by_secret(secret, executor)
+            .await?
+            .filter(|token| token.expires.is_none_or(|expires| expires > now));
+
+        if let Some(token) = &token {
+            sqlx::query!(
+                "
+                UPDATE personal_access_tokens
+                SET last_used = $1
+                WHERE id = $2
+                ",
+                now,
+                token.id
+            )
+            .execute(executor)
+            .await?;
+        }
+
+        Ok(token)
+    }
+}
+
+impl PersonalAccessToken {
+    /// Inserts the token and its project restrictions into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the queries.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        let scopes = self.scopes.bits() as i64;
+
+        sqlx::query!(
+            "
+            INSERT INTO personal_access_tokens (
+                id, user_id, name, token, scopes,
+                created, expires, last_used
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8
+            )
+            ",
+            self.id,
+            self.user_id,
+            self.name,
+            self.token,
+            scopes,
+            self.created,
+            self.expires,
+            self.last_used
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        for project_id in &self.projects.0 {
+            sqlx::query!(
+                "
+                INSERT INTO personal_access_token_projects (
+                    token_id, project_id
+                )
+                VALUES (
+                    $1, $2
+                )
+                ",
+                self.id,
+                project_id
+            )
+            .execute(&mut **transaction)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Retrieves the token matching the given plain text secret, tokens
+    /// of suspended users are never returned
+    ///
+    pub async fn get_by_secret<'a, E>(
+        secret: &str,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        let hash = hash_secret(secret);
+
+        sqlx::query_as!(
+            PersonalAccessToken,
+            "
+            SELECT t.id, t.user_id, t.name, t.token, t.scopes,
+                   t.created, t.expires, t.last_used,
+                   (
+                       SELECT JSON_GROUP_ARRAY(p.project_id)
+                       FROM personal_access_token_projects p
+                       WHERE p.token_id = t.id
+                   ) AS \"projects!: TokenProjects\"
+            FROM personal_access_tokens t
+            WHERE t.token = $1
+            AND t.user_id NOT IN (
+                SELECT id
+                FROM users
+                WHERE suspended = true
+            )
+            ",
+            hash
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves every token belonging to the given user
+    ///
+    pub async fn get_many_from_user<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            PersonalAccessToken,
+            "
+            SELECT t.id, t.user_id, t.name, t.token, t.scopes,
+                   t.created, t.expires, t.last_used,
+                   (
+                       SELECT JSON_GROUP_ARRAY(p.project_id)
+                       FROM personal_access_token_projects p
+                       WHERE p.token_id = t.id
+                   ) AS \"projects!: TokenProjects\"
+            FROM personal_access_tokens t
+            WHERE t.user_id = $1
+            ORDER BY t.created DESC
+            ",
+            user_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
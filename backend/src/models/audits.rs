@@ -3,7 +3,7 @@ use utoipa::ToSchema;
 
 use crate::database::Database;
 
-use super::{id::{ProjectMemberId, ProjectId, AuditId}, projects::ProjectMember};
+use super::{id::{ProjectMemberId, ProjectId, AuditId, UserId}, projects::ProjectMember};
 
 #[derive(Serialize, ToSchema)]
 pub struct Audit {
@@ -117,7 +117,7 @@ impl Audit {
 
     /// Retrieves multiple `Audit` records from the database that belong to a specific project based on the given `project_id`.
     /// 
-    /// This method is a convenience function that internally calls the `get_many` method, filtering the audits by the `project_id` column.
+    /// The audits are ordered from newest to oldest.
     /// 
     /// # Arguments
     /// 
@@ -136,7 +136,55 @@ impl Audit {
     where
         E: sqlx::Executor<'a, Database = Database>,
     {
-        Self::get_many("project_id", project_id.0, executor).await
+        sqlx::query_as!(
+            Audit,
+            "
+            SELECT id, auditor, project_id, 
+                   body, timestamp
+            FROM audit_log
+            WHERE project_id = $1
+            ORDER BY timestamp DESC
+            ",
+            project_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Retrieves every `Audit` record where the given user was the auditor, across all of their memberships.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `user_id`: A `UserId` representing the unique identifier of the user whose audits are to be retrieved.
+    /// * `executor`: An SQLx Executor implementing the `Executor` trait for the specific database (`Database`) and lifetime `'a`.
+    /// 
+    /// # Returns
+    /// 
+    /// Returns a `Result` containing a `Vec<Audit>` with all matching records if any, or an `sqlx::error::Error` if there was an error executing the query.
+    /// 
+    pub async fn get_many_from_auditor<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error> 
+    where
+        E: sqlx::Executor<'a, Database = Database>,
+    {
+        sqlx::query_as!(
+            Audit,
+            "
+            SELECT id, auditor, project_id, 
+                   body, timestamp
+            FROM audit_log
+            WHERE auditor IN (
+                SELECT id
+                FROM project_members
+                WHERE user_id = $1
+            )
+            ",
+            user_id
+        )
+        .fetch_all(executor)
+        .await
     }
 
     /// Inserts an `Audit` record into the database.
@@ -0,0 +1,423 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::database::{Database, SqlPool};
+use crate::error::ApiError;
+
+use super::access_tokens::PersonalAccessToken;
+use super::audits::Audit;
+use super::id::{DataExportId, UserId};
+use super::login_history::LoginHistory;
+use super::notifications::{FullNotification, Notification, NotificationBuilder};
+use super::organisations::OrganisationMember;
+use super::projects::ProjectMember;
+use super::tasks::{SubTask, Task};
+use super::templates::ProjectTemplate;
+use super::users::User;
+
+/// How long a completed export can be downloaded for (in days)
+const EXPORT_LIFETIME: i64 = 7;
+
+/// How long an export can be pending before it is assumed to have
+/// been interrupted, for example by the server restarting (in minutes)
+const PENDING_TIMEOUT: i64 = 60;
+
+#[derive(Serialize, Deserialize, ToSchema, sqlx::Type, Clone, Copy, PartialEq, Eq, Debug)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ExportStatus {
+    /// The archive is still being put together
+    Pending,
+    /// The archive is ready to download
+    Complete,
+    /// Something went wrong, another export can be started
+    Failed,
+}
+
+#[derive(Serialize, ToSchema, Clone)]
+pub struct DataExport {
+    /// The export's id
+    ///
+    #[schema(example="123456789abc", min_length=12, max_length=12)]
+    pub id: DataExportId,
+    /// The id of the user whose data is exported
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub user_id: UserId,
+    /// Whether the archive is ready to download
+    ///
+    #[schema(example="complete")]
+    pub status: ExportStatus,
+    /// The datetime the export was requested
+    ///
+    pub created: NaiveDateTime,
+    /// The datetime the archive was ready, if it is
+    ///
+    pub completed: Option<NaiveDateTime>,
+    /// The datetime after which the archive can no longer be
+    /// downloaded, if it is ready
+    ///
+    pub expires: Option<NaiveDateTime>,
+}
+
+/// Everything the instance stores about a user, serialized as the
+/// export's archive
+///
+#[derive(Serialize)]
+pub struct PersonalData {
+    /// The datetime the archive was put together
+    pub exported: NaiveDateTime,
+    /// The user themselves, excluding their password hash
+    pub user: User,
+    /// The user's memberships and invitations
+    pub memberships: Vec<ProjectMember>,
+    /// The tasks the user created
+    pub tasks: Vec<Task>,
+    /// The sub-tasks assigned to the user
+    pub sub_tasks: Vec<SubTask>,
+    /// The notifications sent to the user
+    pub notifications: Vec<FullNotification>,
+    /// The audit entries of actions taken by the user
+    pub audits: Vec<Audit>,
+    /// Every time the user has logged in
+    pub logins: Vec<LoginHistory>,
+    /// The user's personal access tokens, excluding the tokens themselves
+    pub access_tokens: Vec<PersonalAccessToken>,
+    /// The user's project templates
+    pub templates: Vec<ProjectTemplate>,
+    /// The user's organisation memberships
+    pub organisations: Vec<OrganisationMember>,
+}
+
+impl DataExport {
+    /// Starts a new export for the given user, any of their expired exports
+    /// are removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user whose data is exported.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, ApiError>`, where:
+    /// - `Ok(export)` is returned with the new, pending, `DataExport`.
+    /// - An `Err(ApiError::Conflict)` is returned if the user already has an export pending.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating the id or executing the queries.
+    ///
+    pub async fn create(
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, ApiError> {
+        let now = Utc::now().naive_utc();
+        let pending_since = now - Duration::minutes(PENDING_TIMEOUT);
+
+        let pending = sqlx::query_scalar!(
+            "
+            SELECT COUNT(*)
+            FROM data_exports
+            WHERE user_id = $1
+            AND status = 'pending'
+            AND created > $2
+            ",
+            user_id,
+            pending_since
+        )
+        .fetch_one(&mut **transaction)
+        .await?;
+
+        if pending > 0 {
+            return Err(ApiError::Conflict("an export is already being prepared"));
+        }
+
+        sqlx::query!(
+            "
+            DELETE FROM data_exports
+            WHERE user_id = $1
+            AND expires < $2
+            ",
+            user_id,
+            now
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let export = Self {
+            id: DataExportId::generate(&mut *transaction).await?,
+            user_id,
+            status: ExportStatus::Pending,
+            created: now,
+            completed: None,
+            expires: None,
+        };
+
+        export.insert(&mut *transaction).await?;
+
+        Ok(export)
+    }
+
+    /// Puts together the user's archive and stores it with the export, then
+    /// notifies the user that it is ready. This reads everything the user
+    /// owns so should be run in the background rather than in a request.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool`: The database pool to read the user's data with.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the archive is stored and the user notified.
+    /// - An `Err(ApiError::NotFound)` is returned if the user no longer exists.
+    /// - An `Err(ApiError::Sqlx)` or `Err(ApiError::Anyhow)` is returned if the data cannot be read or serialized.
+    ///
+    pub async fn complete(
+        &mut self,
+        pool: &SqlPool,
+    ) -> Result<(), ApiError> {
+        let user_id = self.user_id.clone();
+
+        let user = User::get(user_id.clone(), pool)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        let data = PersonalData {
+            exported: Utc::now().naive_utc(),
+            user,
+            memberships: ProjectMember::get_many_from_user(user_id.clone(), pool).await?,
+            tasks: Task::get_many_from_creator(user_id.clone(), pool).await?,
+            sub_tasks: SubTask::get_many_from_assignee(user_id.clone(), pool).await?,
+            notifications: Notification::get_many_full(user_id.clone(), pool).await?,
+            audits: Audit::get_many_from_auditor(user_id.clone(), pool).await?,
+            logins: LoginHistory::get_all_from_user(user_id.clone(), pool).await?,
+            access_tokens: PersonalAccessToken::get_many_from_user(user_id.clone(), pool).await?,
+            templates: ProjectTemplate::get_many_from_user(user_id.clone(), pool).await?,
+            organisations: OrganisationMember::get_many_from_user(user_id.clone(), pool).await?,
+        };
+
+        let archive = serde_json::to_string_pretty(&data).map_err(anyhow::Error::from)?;
+
+        let now = Utc::now().naive_utc();
+        self.status = ExportStatus::Complete;
+        self.completed = Some(now);
+        self.expires = Some(now + Duration::days(EXPORT_LIFETIME));
+
+        let mut transaction = pool.begin().await?;
+
+        sqlx::query!(
+            "
+            UPDATE data_exports
+            SET status = $1,
+                completed = $2,
+                expires = $3,
+                data = $4
+            WHERE id = $5
+            ",
+            self.status,
+            self.completed,
+            self.expires,
+            archive,
+            self.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        let builder = NotificationBuilder {
+            body: format!(
+                "Your data export is ready to download for the next {} days.",
+                EXPORT_LIFETIME
+            ),
+            actions: Vec::new(),
+        };
+
+        Notification::send(builder, user_id, &mut transaction).await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Marks the export as failed so the user can start another one
+    ///
+    pub async fn fail(
+        &mut self,
+        pool: &SqlPool,
+    ) -> Result<(), sqlx::error::Error> {
+        self.status = ExportStatus::Failed;
+
+        sqlx::query!(
+            "
+            UPDATE data_exports
+            SET status = $1
+            WHERE id = $2
+            ",
+            self.status,
+            self.id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl DataExport {
+    /// Inserts the export into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO data_exports (
+                id, user_id, status, created,
+                completed, expires
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6
+            )
+            ",
+            self.id,
+            self.user_id,
+            self.status,
+            self.created,
+            self.completed,
+            self.expires
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the export with the given id, as long as it belongs to the
+    /// given user.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `DataExportId` of the export.
+    /// * `user_id`: The `UserId` of the user the export must belong to.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(export))` is returned if the user has an export with the id.
+    /// - `Ok(None)` is returned if they do not.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get<'a, E>(
+        id: DataExportId,
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            DataExport,
+            "
+            SELECT id, user_id, status AS \"status: ExportStatus\",
+                   created, completed, expires
+            FROM data_exports
+            WHERE id = $1
+            AND user_id = $2
+            ",
+            id,
+            user_id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves every export of the given user, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(exports)` is returned with the user's exports.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_many_from_user<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            DataExport,
+            "
+            SELECT id, user_id, status AS \"status: ExportStatus\",
+                   created, completed, expires
+            FROM data_exports
+            WHERE user_id = $1
+            ORDER BY created DESC
+            ",
+            user_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Retrieves the archive of the export with the given id, as long as it
+    /// belongs to the given user, is complete and has not expired.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `DataExportId` of the export.
+    /// * `user_id`: The `UserId` of the user the export must belong to.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<String>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(archive))` is returned with the JSON archive if it can be downloaded.
+    /// - `Ok(None)` is returned if it cannot.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_archive<'a, E>(
+        id: DataExportId,
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Option<String>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        let now = Utc::now().naive_utc();
+
+        let archive = sqlx::query_scalar!(
+            "
+            SELECT data
+            FROM data_exports
+            WHERE id = $1
+            AND user_id = $2
+            AND status = 'complete'
+            AND expires > $3
+            ",
+            id,
+            user_id,
+            now
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(archive.flatten())
+    }
+}
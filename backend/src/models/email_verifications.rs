@@ -0,0 +1,185 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+
+use crate::database::Database;
+use crate::error::ApiError;
+use crate::utilities::hash_secret;
+
+use super::id::{EmailVerificationId, UserId};
+
+/// How long a verification token can be used for after it is sent (in hours)
+const VERIFICATION_LIFETIME: i64 = 24;
+
+pub struct EmailVerification {
+    /// The id of the verification
+    ///
+    pub id: EmailVerificationId,
+    /// The id of the user whose address is being verified
+    ///
+    pub user_id: UserId,
+    /// The address the token was sent to
+    ///
+    pub email: String,
+    /// A hash of the verification token sent to the user
+    ///
+    pub token: String,
+    /// The datetime the token was sent
+    ///
+    pub created: NaiveDateTime,
+    /// The datetime after which the token can no longer be used
+    ///
+    pub expires: NaiveDateTime,
+    /// Whether the token has already been used, or has been
+    /// replaced by a newer one
+    ///
+    pub used: bool,
+}
+
+impl EmailVerification {
+    /// Creates a new verification for the given user and address, any earlier
+    /// verifications which have not been used yet are invalidated.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user whose address is being verified.
+    /// * `email`: The address the token will be sent to.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(Self, String), sqlx::error::Error>`, where:
+    /// - `Ok((verification, token))` is returned with the new `EmailVerification` and the plain text
+    ///   token to send to the user.
+    /// - An `sqlx::error::Error` is returned if there is an error generating the id or executing the queries.
+    ///
+    pub async fn create(
+        user_id: UserId,
+        email: String,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(Self, String), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            UPDATE email_verifications
+            SET used = true
+            WHERE user_id = $1
+            ",
+            user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let id = EmailVerificationId::generate(&mut *transaction).await?;
+        let token = nanoid::nanoid!(48);
+        let now = Utc::now().naive_utc();
+
+        let verification = Self {
+            id,
+            user_id,
+            email,
+            token: hash_secret(&token),
+            created: now,
+            expires: now + Duration::hours(VERIFICATION_LIFETIME),
+            used: false,
+        };
+
+        verification.insert(&mut *transaction).await?;
+
+        Ok((verification, token))
+    }
+
+    /// Marks the verification belonging to the given token as used and returns it
+    /// so the address it was sent to can be marked as verified.
+    ///
+    /// # Arguments
+    ///
+    /// * `token`: The plain text token sent to the user.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, ApiError>`, where:
+    /// - `Ok(verification)` is returned if the token is valid, unused and has not expired.
+    /// - An `Err(ApiError::Unauthorized)` is returned if it is not.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn redeem(
+        token: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, ApiError> {
+        let hash = hash_secret(token);
+        let now = Utc::now().naive_utc();
+
+        let mut verification = sqlx::query_as!(
+            EmailVerification,
+            "
+            SELECT id, user_id, email, token,
+                   created, expires, used
+            FROM email_verifications
+            WHERE token = $1
+            AND used = false
+            AND expires > $2
+            ",
+            hash,
+            now
+        )
+        .fetch_optional(&mut **transaction)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+        sqlx::query!(
+            "
+            UPDATE email_verifications
+            SET used = true
+            WHERE id = $1
+            ",
+            verification.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        verification.used = true;
+
+        Ok(verification)
+    }
+}
+
+impl EmailVerification {
+    /// Inserts the verification into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO email_verifications (
+                id, user_id, email, token,
+                created, expires, used
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7
+            )
+            ",
+            self.id,
+            self.user_id,
+            self.email,
+            self.token,
+            self.created,
+            self.expires,
+            self.used
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
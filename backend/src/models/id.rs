@@ -200,4 +200,40 @@ id!(pub, AuditId, 12, "audits");
 
 id!(pub, NotificationId, 10, "notifications");
 
-id!(pub, NotificationActionId, 10, "notification_actions");
\ No newline at end of file
+id!(pub, NotificationActionId, 10, "notification_actions");
+
+id!(pub, SessionId, 16, "sessions");
+
+id!(pub, PersonalAccessTokenId, 12, "personal_access_tokens");
+
+id!(pub, PasswordResetId, 12, "password_resets");
+
+id!(pub, EmailVerificationId, 12, "email_verifications");
+
+id!(pub, MfaChallengeId, 16, "mfa_challenges");
+
+id!(pub, LoginHistoryId, 16, "login_history");
+
+id!(pub, LoginAttemptId, 16, "login_attempts");
+
+id!(pub, UserIdentityId, 12, "user_identities");
+
+id!(pub, OidcLoginId, 16, "oidc_logins");
+
+id!(pub, DataExportId, 12, "data_exports");
+
+id!(pub, ProjectRoleId, 10, "project_roles");
+
+id!(pub, OwnershipTransferId, 12, "ownership_transfers");
+
+id!(pub, LabelId, 10, "labels");
+
+id!(pub, ProjectTemplateId, 12, "project_templates");
+
+id!(pub, InviteLinkId, 12, "project_invite_links");
+
+id!(pub, OrganisationId, 8, "organisations");
+
+id!(pub, OrganisationMemberId, 8, "organisation_members");
+
+id!(pub, OrganisationRoleId, 10, "organisation_roles");
\ No newline at end of file
@@ -0,0 +1,496 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::database::Database;
+use crate::error::ApiError;
+use crate::utilities::hash_secret;
+
+use super::audits::Audit;
+use super::id::{InviteLinkId, ProjectId, ProjectMemberId, ProjectRoleId, UserId};
+use super::projects::{Permissions, Project, ProjectMember};
+use super::roles::ProjectRole;
+
+/// How long invite links last when no expiry is given, in days
+///
+const DEFAULT_LINK_LIFETIME: i64 = 7;
+
+#[derive(Serialize, ToSchema)]
+pub struct InviteLink {
+    /// The link's id
+    ///
+    #[schema(example="123456789abc", min_length=12, max_length=12)]
+    pub id: InviteLinkId,
+    /// The id of the project the link joins
+    ///
+    #[schema(example="12345678", min_length=8, max_length=8)]
+    pub project_id: ProjectId,
+    /// The membership id of the member who created the link
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub creator: ProjectMemberId,
+    /// A hash of the link's token
+    ///
+    #[serde(skip_serializing)]
+    pub token: String,
+    /// The id of the role users joining through the link are
+    /// given, if any
+    ///
+    #[schema(example="123456789a", min_length=10, max_length=10)]
+    pub role_id: Option<ProjectRoleId>,
+    /// The permissions users joining through the link are given
+    ///
+    #[schema(value_type = u64, example = 1)]
+    pub permissions: Permissions,
+    /// The number of times the link can be used, if not set it can
+    /// be used until it expires
+    ///
+    #[schema(example = 10)]
+    pub max_uses: Option<i64>,
+    /// The number of times the link has been used
+    ///
+    #[schema(example = 0)]
+    pub uses: i64,
+    /// The datetime after which the link can no longer be used
+    ///
+    pub expires: NaiveDateTime,
+    /// The datetime the link was created
+    ///
+    pub created: NaiveDateTime,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct InviteLinkBuilder {
+    /// The id of the role users joining through the link will
+    /// have, the creator has to hold every one of its permissions.
+    /// Either this or the permissions must be given
+    ///
+    #[schema(example="123456789a", min_length=10, max_length=10)]
+    pub role_id: Option<ProjectRoleId>,
+    /// The permissions users joining through the link will have
+    /// if no role is given, the creator has to hold every one of
+    /// them
+    ///
+    #[schema(value_type = Option<u64>, example = 1)]
+    pub permissions: Option<Permissions>,
+    /// The number of times the link can be used (at least 1), if
+    /// not given it can be used until it expires
+    ///
+    #[schema(example = 10)]
+    pub max_uses: Option<i64>,
+    /// The datetime after which the link can no longer be used,
+    /// by default a week after it is created
+    ///
+    pub expires: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct JoinProject {
+    /// The token of the invite link
+    ///
+    #[schema(example="V1StGXR8Z5jdHi6BmyTq2xLJm7uT3pEw")]
+    pub token: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreatedInviteLink {
+    /// The link's details
+    ///
+    #[serde(flatten)]
+    pub details: InviteLink,
+    /// The token users join the project with, this is not stored
+    /// and cannot be retrieved again
+    ///
+    #[schema(example="V1StGXR8Z5jdHi6BmyTq2xLJm7uT3pEw")]
+    pub token: String,
+    /// The link to the web front end's page for joining with the
+    /// token
+    ///
+    #[schema(example="https://example.com/invite?token=V1StGXR8Z5jdHi6BmyTq2xLJm7uT3pEw")]
+    pub url: String,
+}
+
+impl InviteLink {
+    /// Creates an invite link to the creator's project giving either a role or
+    /// a set of permissions, neither of which can exceed the creator's own
+    /// permissions. The creation is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: An `InviteLinkBuilder` with the link's role or permissions, uses and expiry.
+    /// * `creator`: The `ProjectMember` creating the link.
+    /// * `frontend_url`: The url of the web front end, which the link points to.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<CreatedInviteLink, ApiError>`, where:
+    /// - `Ok(link)` is returned with the link's details and its plain text token.
+    /// - An `Err(ApiError::Forbidden)` is returned if the creator can't invite members or does not hold every permission given.
+    /// - An `Err(ApiError::BadRequest)` is returned if not exactly one of a role or permissions is given.
+    /// - An `Err(ApiError::NotFound)` is returned if the role does not exist.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if the expiry is in the past or the maximum uses is below 1.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating the ID or executing the database queries.
+    ///
+    pub async fn create(
+        form: InviteLinkBuilder,
+        creator: &ProjectMember,
+        frontend_url: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<CreatedInviteLink, ApiError> {
+        creator.check_permissions(Permissions::INVITE_MEMBERS)?;
+
+        let now = Utc::now().naive_utc();
+        let expires = form.expires.unwrap_or(now + Duration::days(DEFAULT_LINK_LIFETIME));
+
+        if expires <= now {
+            return Err(ApiError::unprocessable_entity([("expires", "must be in the future")]));
+        }
+
+        if form.max_uses.is_some_and(|max_uses| max_uses < 1) {
+            return Err(ApiError::unprocessable_entity([("max_uses", "must be at least 1")]));
+        }
+
+        // Members can never hand out permissions they do not have
+        let (role, permissions) = match (form.role_id, form.permissions) {
+            (Some(role_id), None) => {
+                let role = ProjectRole::get(role_id, creator.project_id.clone(), &mut **transaction)
+                    .await?
+                    .ok_or(ApiError::NotFound)?;
+
+                creator.check_permissions(role.permissions)?;
+
+                let permissions = role.permissions;
+
+                (Some(role), permissions)
+            }
+            (None, Some(permissions)) => {
+                creator.check_permissions(permissions)?;
+
+                (None, permissions)
+            }
+            _ => return Err(ApiError::BadRequest),
+        };
+
+        let secret = nanoid::nanoid!(32);
+
+        let link = Self {
+            id: InviteLinkId::generate(&mut *transaction).await?,
+            project_id: creator.project_id.clone(),
+            creator: creator.id.clone(),
+            token: hash_secret(&secret),
+            role_id: role.as_ref().map(|role| role.id.clone()),
+            permissions,
+            max_uses: form.max_uses,
+            uses: 0,
+            expires,
+            created: now,
+        };
+
+        link.insert(transaction).await?;
+
+        let given = match &role {
+            Some(role) => format!("the {} role", role.name),
+            None => format!("the permissions {}", permissions.names()),
+        };
+
+        Audit::create(
+            creator,
+            format!("Created an invite link giving {}", given),
+            transaction
+        ).await?;
+
+        let url = format!("{}/invite?token={}", frontend_url.trim_end_matches('/'), secret);
+
+        Ok(CreatedInviteLink { details: link, token: secret, url })
+    }
+
+    /// Revokes the link so it can no longer be used, this is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `revoker`: The `ProjectMember` revoking the link.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the link was revoked.
+    /// - An `Err(ApiError::Forbidden)` is returned if the revoker can't invite members.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn revoke(
+        &self,
+        revoker: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        revoker.check_permissions(Permissions::INVITE_MEMBERS)?;
+
+        sqlx::query!(
+            "
+            DELETE FROM project_invite_links
+            WHERE id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let creator_username = match ProjectMember::get(self.creator.clone(), &mut **transaction).await? {
+            Some(creator) => creator.username(transaction).await?,
+            None => self.creator.0.clone(),
+        };
+
+        Audit::create(
+            revoker,
+            format!("Revoked an invite link created by {}", creator_username),
+            transaction
+        ).await?;
+
+        Ok(())
+    }
+
+    /// Adds the user to the project of the link with the given token, using
+    /// up one of its uses. A pending invitation the user has to the project
+    /// is replaced. Joining is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `token`: The plain text token of the link.
+    /// * `user_id`: The `UserId` of the user joining.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<ProjectMember, ApiError>`, where:
+    /// - `Ok(member)` is returned with the user's new membership.
+    /// - An `Err(ApiError::NotFound)` is returned if there is no such link, or it has expired or been used up.
+    /// - An `Err(ApiError::Conflict)` is returned if the user is already a member of the project.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating the ID or executing the database queries.
+    ///
+    pub async fn redeem(
+        token: &str,
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<ProjectMember, ApiError> {
+        let link = Self::get_from_token(&hash_secret(token), &mut **transaction)
+            .await?
+            .filter(|link| link.expires > Utc::now().naive_utc())
+            .ok_or(ApiError::NotFound)?;
+
+        Project::get(link.project_id.clone(), &mut **transaction)
+            .await?
+            .filter(|project| project.trashed.is_none())
+            .ok_or(ApiError::NotFound)?;
+
+        if let Some(existing) = ProjectMember::get_from_user(user_id.clone(), link.project_id.clone(), &mut **transaction).await? {
+            if existing.accepted {
+                return Err(ApiError::Conflict("you are already a member of this project"));
+            }
+
+            existing.deny_invitation(transaction).await?;
+        }
+
+        // Checked in the update so concurrent uses can't go over the limit
+        let used = sqlx::query!(
+            "
+            UPDATE project_invite_links
+            SET uses = uses + 1
+            WHERE id = $1
+            AND (max_uses IS NULL OR uses < max_uses)
+            ",
+            link.id
+        )
+        .execute(&mut **transaction)
+        .await?
+        .rows_affected() > 0;
+
+        if !used {
+            return Err(ApiError::NotFound);
+        }
+
+        let member = ProjectMember {
+            id: ProjectMemberId::generate(&mut *transaction).await?,
+            project_id: link.project_id.clone(),
+            user_id,
+            permissions: link.permissions,
+            accepted: true,
+            role_id: link.role_id.clone(),
+        };
+
+        member.insert(&mut *transaction).await?;
+
+        let creator_username = match ProjectMember::get(link.creator.clone(), &mut **transaction).await? {
+            Some(creator) => creator.username(transaction).await?,
+            None => link.creator.0.clone(),
+        };
+
+        Audit::create(
+            &member,
+            format!("Joined the project through an invite link created by {}", creator_username),
+            transaction
+        ).await?;
+
+        Ok(member)
+    }
+}
+
+impl InviteLink {
+    /// Inserts the link into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        let permissions = self.permissions.bits() as i64;
+
+        sqlx::query!(
+            "
+            INSERT INTO project_invite_links (
+                id, project_id, creator, token, role_id,
+                permissions, max_uses, uses, expires, created
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10
+            )
+            ",
+            self.id,
+            self.project_id,
+            self.creator,
+            self.token,
+            self.role_id,
+            permissions,
+            self.max_uses,
+            self.uses,
+            self.expires,
+            self.created
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the link with the given id, as long as it belongs to the
+    /// given project.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `InviteLinkId` of the link.
+    /// * `project_id`: The `ProjectId` of the project the link must belong to.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(link))` is returned if the project has a link with the id.
+    /// - `Ok(None)` is returned if it does not.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get<'a, E>(
+        id: InviteLinkId,
+        project_id: ProjectId,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            InviteLink,
+            "
+            SELECT id, project_id, creator, token,
+                   role_id AS \"role_id: ProjectRoleId\",
+                   permissions, max_uses, uses, expires, created
+            FROM project_invite_links
+            WHERE id = $1
+            AND project_id = $2
+            ",
+            id,
+            project_id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves the link with the given token hash.
+    ///
+    /// # Arguments
+    ///
+    /// * `token`: The hash of the link's token.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(link))` is returned if a link has the token.
+    /// - `Ok(None)` is returned if none does.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_from_token<'a, E>(
+        token: &str,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            InviteLink,
+            "
+            SELECT id, project_id, creator, token,
+                   role_id AS \"role_id: ProjectRoleId\",
+                   permissions, max_uses, uses, expires, created
+            FROM project_invite_links
+            WHERE token = $1
+            ",
+            token
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves every link to the given project, including ones which have
+    /// expired or been used up, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_id`: The `ProjectId` of the project.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(links)` is returned with the project's links.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_many_from_project<'a, E>(
+        project_id: ProjectId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            InviteLink,
+            "
+            SELECT id, project_id, creator, token,
+                   role_id AS \"role_id: ProjectRoleId\",
+                   permissions, max_uses, uses, expires, created
+            FROM project_invite_links
+            WHERE project_id = $1
+            ORDER BY created DESC
+            ",
+            project_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
@@ -0,0 +1,468 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::database::Database;
+use crate::error::ApiError;
+
+use super::id::{LoginAttemptId, UserId};
+use super::login_history::ClientInfo;
+
+/// How many consecutive failures an account is allowed before
+/// attempts start being delayed
+const ACCOUNT_FREE_ATTEMPTS: i64 = 5;
+
+/// How many failures a single ip address is allowed, across every
+/// account, before attempts start being delayed
+const IP_FREE_ATTEMPTS: i64 = 20;
+
+/// The delay after the first failure past the free attempts, which
+/// doubles with every further failure (in seconds)
+const BASE_DELAY: i64 = 1;
+
+/// The longest an account can be locked for (in minutes)
+const MAX_ACCOUNT_LOCKOUT: i64 = 15;
+
+/// The longest an ip address can be locked for (in minutes)
+const MAX_IP_LOCKOUT: i64 = 60;
+
+/// How far back failures are counted, older failures are forgotten
+/// (in hours)
+const FAILURE_WINDOW: i64 = 24;
+
+/// The most attempts returned when reviewing attempts
+const REVIEW_LIMIT: i64 = 100;
+
+/// A single attempt to log in, either with a password or with a
+/// two-factor code
+///
+#[derive(Serialize, ToSchema)]
+pub struct LoginAttempt {
+    /// The attempt's id
+    ///
+    #[schema(example="123456789abcdefg", min_length=16, max_length=16)]
+    pub id: LoginAttemptId,
+    /// The username or email that was entered, lower cased
+    ///
+    #[schema(example="my username")]
+    pub identifier: String,
+    /// The user the identifier belonged to, if any
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub user_id: Option<UserId>,
+    /// The ip address the attempt was made from
+    ///
+    #[schema(example="127.0.0.1")]
+    pub ip_address: Option<String>,
+    /// The user agent of the client the attempt was made with
+    ///
+    #[schema(example="Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/119.0")]
+    pub user_agent: Option<String>,
+    /// The datetime of the attempt
+    ///
+    pub attempted: NaiveDateTime,
+    /// Whether the attempt logged the user in
+    ///
+    #[schema(example=false)]
+    pub successful: bool,
+}
+
+struct RecentAttempt {
+    successful: bool,
+    attempted: NaiveDateTime,
+}
+
+impl LoginAttempt {
+    /// Records an attempt to log in, resolving the user the identifier belongs to
+    /// if it is not already known.
+    ///
+    /// # Arguments
+    ///
+    /// * `identifier`: The username or email that was entered.
+    /// * `user_id`: The `UserId` of the user, if already known.
+    /// * `client`: The `ClientInfo` of the client the attempt was made with.
+    /// * `successful`: Whether the attempt logged the user in.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, sqlx::error::Error>`, where:
+    /// - `Ok(attempt)` is returned with the recorded `LoginAttempt`.
+    /// - An `sqlx::error::Error` is returned if there is an error generating the id or executing the queries.
+    ///
+    pub async fn record(
+        identifier: &str,
+        user_id: Option<UserId>,
+        client: &ClientInfo,
+        successful: bool,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, sqlx::error::Error> {
+        let user_id = match user_id {
+            Some(user_id) => Some(user_id),
+            None => Self::find_user(identifier, &mut **transaction).await?,
+        };
+
+        let attempt = Self {
+            id: LoginAttemptId::generate(&mut *transaction).await?,
+            identifier: identifier.to_lowercase(),
+            user_id,
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            attempted: Utc::now().naive_utc(),
+            successful,
+        };
+
+        attempt.insert(&mut *transaction).await?;
+
+        Ok(attempt)
+    }
+
+    /// Starts an attempt to log in, checking it is allowed yet and recording it
+    /// as failed before the password or code is checked. The transaction should
+    /// be committed straight away so that attempts made in parallel are counted
+    /// against each other, the attempt is then marked as successful once the
+    /// check passes.
+    ///
+    /// # Arguments
+    ///
+    /// * `identifier`: The username or email that was entered.
+    /// * `user_id`: The `UserId` of the user, if already known.
+    /// * `client`: The `ClientInfo` of the client the attempt was made with.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, ApiError>`, where:
+    /// - `Ok(attempt)` is returned with the recorded `LoginAttempt` if the attempt is allowed.
+    /// - An `Err(ApiError::TooManyRequests)` is returned with the number of seconds to wait if it is not.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating the id or executing the queries.
+    ///
+    pub async fn start(
+        identifier: &str,
+        user_id: Option<UserId>,
+        client: &ClientInfo,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, ApiError> {
+        let user_id = match user_id {
+            Some(user_id) => Some(user_id),
+            None => Self::find_user(identifier, &mut **transaction).await?,
+        };
+
+        Self::throttle(identifier, user_id.as_ref(), client, transaction).await?;
+
+        Self::record(identifier, user_id, client, false, transaction)
+            .await
+            .map_err(|error| error.into())
+    }
+
+    /// Marks a started attempt as having logged the user in.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the attempt was updated.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn succeed(
+        &mut self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            UPDATE login_attempts
+            SET successful = true
+            WHERE id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        self.successful = true;
+
+        Ok(())
+    }
+
+    /// Removes a started attempt whose password was correct but which could
+    /// not log the user in yet, such as when a two-factor code is still
+    /// needed, so that it does not count as a failure.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the attempt was removed.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn remove(
+        self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            DELETE FROM login_attempts
+            WHERE id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Checks whether another attempt to log in to the given account from the
+    /// given client should be allowed yet. Each failure past the free attempts
+    /// doubles the time the client has to wait, up to a maximum lockout.
+    /// Failures are counted against the user when the account exists, however
+    /// it was identified, and against the identifier otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `identifier`: The username or email being logged in to.
+    /// * `user_id`: The `UserId` of the account being logged in to, if it exists.
+    /// * `client`: The `ClientInfo` of the client attempting to log in.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the attempt is allowed.
+    /// - An `Err(ApiError::TooManyRequests)` is returned with the number of seconds to wait if it is not.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    async fn throttle(
+        identifier: &str,
+        user_id: Option<&UserId>,
+        client: &ClientInfo,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        let now = Utc::now().naive_utc();
+        let since = now - Duration::hours(FAILURE_WINDOW);
+        let lowercase = identifier.to_lowercase();
+
+        let account_attempts = sqlx::query_as!(
+            RecentAttempt,
+            "
+            SELECT successful, attempted
+            FROM login_attempts
+            WHERE (
+                user_id = $1
+                OR ($1 IS NULL AND identifier = $2)
+            )
+            AND attempted > $3
+            ORDER BY attempted DESC
+            ",
+            user_id,
+            lowercase,
+            since
+        )
+        .fetch_all(&mut **transaction)
+        .await?;
+
+        // Only failures since the last successful login count
+        // towards locking the account
+        let account_failures: Vec<_> = account_attempts
+            .iter()
+            .take_while(|attempt| !attempt.successful)
+            .collect();
+
+        let account_wait = Self::wait(
+            account_failures.len() as i64,
+            account_failures.first().map(|attempt| attempt.attempted),
+            ACCOUNT_FREE_ATTEMPTS,
+            Duration::minutes(MAX_ACCOUNT_LOCKOUT),
+        );
+
+        let ip_wait = match &client.ip_address {
+            Some(ip_address) => {
+                let ip_failures = sqlx::query!(
+                    "
+                    SELECT COUNT(*) AS \"failures!: i64\",
+                           MAX(attempted) AS \"last: NaiveDateTime\"
+                    FROM login_attempts
+                    WHERE ip_address = $1
+                    AND successful = false
+                    AND attempted > $2
+                    ",
+                    ip_address,
+                    since
+                )
+                .fetch_one(&mut **transaction)
+                .await?;
+
+                Self::wait(
+                    ip_failures.failures,
+                    ip_failures.last,
+                    IP_FREE_ATTEMPTS,
+                    Duration::minutes(MAX_IP_LOCKOUT),
+                )
+            }
+            None => Duration::zero(),
+        };
+
+        let wait = account_wait.max(ip_wait);
+
+        if wait > Duration::zero() {
+            tracing::warn!(
+                "Throttled login attempt for '{}' from {}",
+                identifier,
+                client.ip_address.as_deref().unwrap_or("an unknown address")
+            );
+
+            // Round up so clients never retry slightly too early
+            let retry_after = (wait.num_milliseconds() as u64).div_ceil(1000);
+
+            return Err(ApiError::TooManyRequests { retry_after });
+        }
+
+        Ok(())
+    }
+
+    /// Finds the user the given username or email belongs to
+    ///
+    async fn find_user<'a, E>(
+        identifier: &str,
+        executor: E,
+    ) -> Result<Option<UserId>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_scalar!(
+            "
+            SELECT id
+            FROM users
+            WHERE username = $1
+            OR email = $1
+            ",
+            identifier
+        )
+        .fetch_optional(executor)
+        .await
+        .map(|user_id| user_id.map(UserId))
+    }
+
+    /// Works out how much longer a client has to wait given the number of
+    /// failures and the time of the last one
+    ///
+    fn wait(
+        failures: i64,
+        last_failure: Option<NaiveDateTime>,
+        free_attempts: i64,
+        max_lockout: Duration,
+    ) -> Duration {
+        let Some(last_failure) = last_failure.filter(|_| failures >= free_attempts) else {
+            return Duration::zero();
+        };
+
+        // Capped well before overflowing, by which point the
+        // delay is far past any lockout anyway
+        let exponent = (failures - free_attempts).min(30) as u32;
+        let delay = Duration::seconds(BASE_DELAY * 2_i64.pow(exponent)).min(max_lockout);
+
+        (last_failure + delay - Utc::now().naive_utc()).max(Duration::zero())
+    }
+}
+
+impl LoginAttempt {
+    /// Inserts the attempt into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO login_attempts (
+                id, identifier, user_id, ip_address,
+                user_agent, attempted, successful
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7
+            )
+            ",
+            self.id,
+            self.identifier,
+            self.user_id,
+            self.ip_address,
+            self.user_agent,
+            self.attempted,
+            self.successful
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the most recent attempts, optionally only those made for the
+    /// given identifier or from the given ip address.
+    ///
+    /// # Arguments
+    ///
+    /// * `identifier`: Only include attempts for this username or email, if given.
+    /// * `ip_address`: Only include attempts from this ip address, if given.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(attempts)` is returned with the matching attempts, newest first.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_recent<'a, E>(
+        identifier: Option<&str>,
+        ip_address: Option<&str>,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        let identifier = identifier.map(|identifier| identifier.to_lowercase());
+
+        let rows = sqlx::query!(
+            "
+            SELECT id, identifier, user_id, ip_address,
+                   user_agent, attempted, successful
+            FROM login_attempts
+            WHERE ($1 IS NULL OR identifier = $1)
+            AND ($2 IS NULL OR ip_address = $2)
+            ORDER BY attempted DESC
+            LIMIT $3
+            ",
+            identifier,
+            ip_address,
+            REVIEW_LIMIT
+        )
+        .fetch_all(executor)
+        .await?;
+
+        // The user's id is nullable so cannot be decoded
+        // into a `UserId` directly
+        Ok(rows.into_iter().map(|row| Self {
+            id: LoginAttemptId(row.id),
+            identifier: row.identifier,
+            user_id: row.user_id.map(UserId),
+            ip_address: row.ip_address,
+            user_agent: row.user_agent,
+            attempted: row.attempted,
+            successful: row.successful,
+        }).collect())
+    }
+}
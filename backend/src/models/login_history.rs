@@ -0,0 +1,199 @@
+use chrono::{NaiveDateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::database::Database;
+
+use super::id::{LoginHistoryId, SessionId, UserId};
+
+/// The most logins returned when listing a user's history
+const HISTORY_LIMIT: i64 = 50;
+
+/// Details of the client a request was made from, recorded against
+/// logins and sessions so users can recognise their devices
+///
+#[derive(Clone, Default)]
+pub struct ClientInfo {
+    /// The ip address of the client
+    ///
+    pub ip_address: Option<String>,
+    /// The `User-Agent` header sent by the client
+    ///
+    pub user_agent: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LoginHistory {
+    /// The id of the login
+    ///
+    #[schema(example="123456789abcdefg", min_length=16, max_length=16)]
+    pub id: LoginHistoryId,
+    /// The id of the user who logged in
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub user_id: UserId,
+    /// The id of the session started by the login
+    ///
+    #[schema(example="123456789abcdefg", min_length=16, max_length=16)]
+    pub session_id: SessionId,
+    /// The datetime of the login
+    ///
+    pub login_timestamp: NaiveDateTime,
+    /// The ip address the user logged in from
+    ///
+    #[schema(example="127.0.0.1")]
+    pub ip_address: Option<String>,
+    /// The user agent of the client the user logged in with
+    ///
+    #[schema(example="Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/119.0")]
+    pub user_agent: Option<String>,
+}
+
+impl LoginHistory {
+    /// Records a login which started the given session.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user who logged in.
+    /// * `session_id`: The `SessionId` of the session started by the login.
+    /// * `client`: The `ClientInfo` of the client the user logged in with.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, sqlx::error::Error>`, where:
+    /// - `Ok(login)` is returned with the recorded `LoginHistory`.
+    /// - An `sqlx::error::Error` is returned if there is an error generating the id or executing the query.
+    ///
+    pub async fn create(
+        user_id: UserId,
+        session_id: SessionId,
+        client: &ClientInfo,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, sqlx::error::Error> {
+        let login = Self {
+            id: LoginHistoryId::generate(&mut *transaction).await?,
+            user_id,
+            session_id,
+            login_timestamp: Utc::now().naive_utc(),
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+        };
+
+        login.insert(&mut *transaction).await?;
+
+        Ok(login)
+    }
+}
+
+impl LoginHistory {
+    /// Inserts the login into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO login_history (
+                id, user_id, session_id, login_timestamp,
+                ip_address, user_agent
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6
+            )
+            ",
+            self.id,
+            self.user_id,
+            self.session_id,
+            self.login_timestamp,
+            self.ip_address,
+            self.user_agent
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the most recent logins of the given user, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(logins)` is returned with up to the 50 most recent logins.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_many_from_user<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            LoginHistory,
+            "
+            SELECT id, user_id, session_id, login_timestamp,
+                   ip_address, user_agent
+            FROM login_history
+            WHERE user_id = $1
+            ORDER BY login_timestamp DESC
+            LIMIT $2
+            ",
+            user_id,
+            HISTORY_LIMIT
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Retrieves every login of the given user, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(logins)` is returned with all of the user's logins.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_all_from_user<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            LoginHistory,
+            "
+            SELECT id, user_id, session_id, login_timestamp,
+                   ip_address, user_agent
+            FROM login_history
+            WHERE user_id = $1
+            ORDER BY login_timestamp
+            ",
+            user_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
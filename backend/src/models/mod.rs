@@ -4,4 +4,19 @@ pub mod projects;
 pub mod tasks;
 pub mod tokens;
 pub mod audits;
-pub mod notifications;
\ No newline at end of file
+pub mod notifications;
+pub mod sessions;
+pub mod access_tokens;
+pub mod password_resets;
+pub mod email_verifications;
+pub mod two_factor;
+pub mod login_history;
+pub mod login_attempts;
+pub mod oidc;
+pub mod data_exports;
+pub mod statistics;
+pub mod roles;
+pub mod ownership_transfers;
+pub mod templates;
+pub mod invite_links;
+pub mod organisations;
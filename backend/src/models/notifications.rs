@@ -176,7 +176,7 @@ impl Notification {
         sqlx::query!(
             "
             DELETE FROM notification_actions
-            WHERE notification_id = (
+            WHERE notification_id IN (
                 SELECT id 
                 FROM notifications 
                 WHERE user_id = ?
@@ -200,6 +200,36 @@ impl Notification {
         Ok(())
     }
 
+    /// Removes the user's notifications which have an action calling the given
+    /// endpoint, used once whatever the action answers has been dealt with
+    /// 
+    pub async fn remove_with_action(
+        user_id: UserId,
+        action_endpoint: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        let notification_ids = sqlx::query_scalar!(
+            "
+            SELECT n.id
+            FROM notifications n
+            INNER JOIN notification_actions na
+            ON n.id = na.notification_id
+            WHERE n.user_id = ?
+            AND na.action_endpoint = ?
+            ",
+            user_id,
+            action_endpoint
+        )
+        .fetch_all(&mut **transaction)
+        .await?;
+
+        for notification_id in notification_ids {
+            Self::remove(NotificationId(notification_id), transaction).await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn read(
         notification_id: NotificationId, 
         transaction: &mut sqlx::Transaction<'_, Database>,
@@ -0,0 +1,352 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+
+use crate::database::Database;
+use crate::error::ApiError;
+use crate::oidc::{AuthorizationRequest, IdentityClaims};
+use crate::utilities::hash_secret;
+
+use super::id::{OidcLoginId, UserId, UserIdentityId};
+use super::users::User;
+
+/// How long a user has to log in with the provider before the
+/// login has to be started again (in minutes)
+const LOGIN_LIFETIME: i64 = 10;
+
+/// The query the provider redirects back to the callback with
+///
+#[derive(Deserialize)]
+pub struct OidcCallback {
+    /// The authorization code to exchange for an id token
+    ///
+    pub code: Option<String>,
+    /// The state sent with the authorization request
+    ///
+    pub state: String,
+    /// Set instead of the code if the user was not logged in
+    ///
+    pub error: Option<String>,
+}
+
+/// A login which has been sent to the OpenID Connect provider and
+/// is waiting for the provider to redirect back
+///
+pub struct OidcLogin {
+    /// The login's id
+    ///
+    pub id: OidcLoginId,
+    /// A hash of the state sent to the provider
+    ///
+    pub state: String,
+    /// The nonce the provider has to include in the id token
+    ///
+    pub nonce: String,
+    /// The PKCE verifier to exchange the authorization code with
+    ///
+    pub code_verifier: String,
+    /// The datetime the login was started
+    ///
+    pub created: NaiveDateTime,
+    /// The datetime after which the login can no longer be completed
+    ///
+    pub expires: NaiveDateTime,
+    /// Whether the provider has already redirected back
+    ///
+    pub used: bool,
+}
+
+impl OidcLogin {
+    /// Records a login which is about to be sent to the provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `request`: The `AuthorizationRequest` the user will be sent to the provider with.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, sqlx::error::Error>`, where:
+    /// - `Ok(login)` is returned with the recorded `OidcLogin`.
+    /// - An `sqlx::error::Error` is returned if there is an error generating the id or executing the query.
+    ///
+    pub async fn create(
+        request: &AuthorizationRequest,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, sqlx::error::Error> {
+        let now = Utc::now().naive_utc();
+
+        let login = Self {
+            id: OidcLoginId::generate(&mut *transaction).await?,
+            state: hash_secret(&request.state),
+            nonce: request.nonce.clone(),
+            code_verifier: request.code_verifier.clone(),
+            created: now,
+            expires: now + Duration::minutes(LOGIN_LIFETIME),
+            used: false,
+        };
+
+        login.insert(&mut *transaction).await?;
+
+        Ok(login)
+    }
+
+    /// Marks the login belonging to the given state as used and returns it so
+    /// the provider's authorization code can be exchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `state`: The plain text state the provider redirected back with.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, ApiError>`, where:
+    /// - `Ok(login)` is returned if the login exists, is unused and has not expired.
+    /// - An `Err(ApiError::Unauthorized)` is returned if it does not.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn redeem(
+        state: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, ApiError> {
+        let hash = hash_secret(state);
+        let now = Utc::now().naive_utc();
+
+        let mut login = sqlx::query_as!(
+            OidcLogin,
+            "
+            SELECT id, state, nonce, code_verifier,
+                   created, expires, used
+            FROM oidc_logins
+            WHERE state = $1
+            AND used = false
+            AND expires > $2
+            ",
+            hash,
+            now
+        )
+        .fetch_optional(&mut **transaction)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+        sqlx::query!(
+            "
+            UPDATE oidc_logins
+            SET used = true
+            WHERE id = $1
+            ",
+            login.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        login.used = true;
+
+        Ok(login)
+    }
+}
+
+impl OidcLogin {
+    /// Inserts the login into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO oidc_logins (
+                id, state, nonce, code_verifier,
+                created, expires, used
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7
+            )
+            ",
+            self.id,
+            self.state,
+            self.nonce,
+            self.code_verifier,
+            self.created,
+            self.expires,
+            self.used
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// A link between a user and their account with an OpenID Connect
+/// provider
+///
+pub struct UserIdentity {
+    /// The identity's id
+    ///
+    pub id: UserIdentityId,
+    /// The id of the user the identity belongs to
+    ///
+    pub user_id: UserId,
+    /// The issuer url of the provider
+    ///
+    pub issuer: String,
+    /// The provider's identifier for the user
+    ///
+    pub subject: String,
+    /// The datetime the identity was linked
+    ///
+    pub created: NaiveDateTime,
+}
+
+impl UserIdentity {
+    /// Finds the user who logged in with the provider. Identities seen for the
+    /// first time are linked to the user with the same email address, or a new
+    /// user is registered if there is none.
+    ///
+    /// Only addresses the provider has verified are trusted, otherwise anyone
+    /// could take over an account by claiming its address with the provider.
+    /// Likewise identities are only linked to users who have verified their
+    /// address, otherwise someone could register with another person's address
+    /// beforehand and keep access once the owner logs in with the provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer`: The issuer url of the provider.
+    /// * `claims`: The verified `IdentityClaims` of the user who logged in.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<User, ApiError>`, where:
+    /// - `Ok(user)` is returned with the linked, or newly registered, `User`.
+    /// - An `Err(ApiError::EmailNotVerified)` is returned if the identity is new and the provider has not verified its address.
+    /// - An `Err(ApiError::Conflict)` is returned if the identity is new and the user with its address has not verified it.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn resolve(
+        issuer: &str,
+        claims: IdentityClaims,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<User, ApiError> {
+        if let Some(identity) = Self::get_from_subject(issuer, &claims.sub, &mut **transaction).await? {
+            return User::get(identity.user_id, &mut **transaction)
+                .await?
+                .ok_or(ApiError::Unauthorized);
+        }
+
+        let email = claims.email
+            .filter(|_| claims.email_verified)
+            .ok_or(ApiError::EmailNotVerified)?;
+
+        let user = match User::get_by_email(&email, &mut **transaction).await? {
+            Some(user) if user.verified => user,
+            Some(_) => return Err(ApiError::Conflict(
+                "an account with this email address exists but it hasn't been verified, verify it before logging in with the provider"
+            )),
+            None => User::register_external(
+                claims.preferred_username.as_deref(),
+                email,
+                transaction
+            ).await?,
+        };
+
+        let identity = Self {
+            id: UserIdentityId::generate(&mut *transaction).await?,
+            user_id: user.id.clone(),
+            issuer: issuer.to_string(),
+            subject: claims.sub,
+            created: Utc::now().naive_utc(),
+        };
+
+        identity.insert(&mut *transaction).await?;
+
+        Ok(user)
+    }
+}
+
+impl UserIdentity {
+    /// Inserts the identity into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO user_identities (
+                id, user_id, issuer, subject, created
+            )
+            VALUES (
+                $1, $2, $3, $4, $5
+            )
+            ",
+            self.id,
+            self.user_id,
+            self.issuer,
+            self.subject,
+            self.created
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the identity with the given subject from the given provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer`: The issuer url of the provider.
+    /// * `subject`: The provider's identifier for the user.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(identity))` is returned if the identity has been linked to a user.
+    /// - `Ok(None)` is returned if it has not.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_from_subject<'a, E>(
+        issuer: &str,
+        subject: &str,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            UserIdentity,
+            "
+            SELECT id, user_id, issuer, subject, created
+            FROM user_identities
+            WHERE issuer = $1
+            AND subject = $2
+            ",
+            issuer,
+            subject
+        )
+        .fetch_optional(executor)
+        .await
+    }
+}
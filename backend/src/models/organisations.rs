@@ -0,0 +1,1258 @@
+use chrono::{NaiveDateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::database::Database;
+use crate::error::ApiError;
+
+use super::id::{OrganisationId, OrganisationMemberId, OrganisationRoleId, ProjectMemberId, UserId};
+use super::notifications::{Notification, NotificationBuilder};
+use super::projects::{Permissions, Project, ProjectMember};
+use super::roles::ProjectRole;
+use super::users::{User, DELETED_USER};
+
+#[derive(Serialize, ToSchema)]
+pub struct Organisation {
+    /// The organisation's id
+    ///
+    #[schema(example="12345678", min_length=8, max_length=8)]
+    pub id: OrganisationId,
+    /// The organisation's name (1 -> 30 charachters)
+    ///
+    #[schema(example="My organisation", min_length=1, max_length=30)]
+    pub name: String,
+    /// The datetime the organisation was created
+    ///
+    pub created: NaiveDateTime,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct OrganisationBuilder {
+    /// The organisation's name (1 -> 30 charachters)
+    ///
+    #[schema(example="My organisation", min_length=1, max_length=30)]
+    pub name: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EditOrganisation {
+    /// The organisation's new name (1 -> 30 charachters)
+    ///
+    #[schema(example="My organisation", min_length=1, max_length=30)]
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OrganisationMember {
+    /// The organisation member's id
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub id: OrganisationMemberId,
+    /// The id of the organisation the membership is for
+    ///
+    #[schema(example="12345678", min_length=8, max_length=8)]
+    pub organisation_id: OrganisationId,
+    /// The user's id
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub user_id: UserId,
+    /// Whether the member can manage the organisation's details,
+    /// members and default roles
+    ///
+    #[schema(example=false)]
+    pub admin: bool,
+    /// The id of the organisation role the member is invited to the
+    /// organisation's projects with, capped at the project's role of
+    /// the same name. Without one they are invited with the same
+    /// permissions as a new member
+    ///
+    #[schema(example="123456789a", min_length=10, max_length=10)]
+    pub role_id: Option<OrganisationRoleId>,
+    /// The datetime the user joined the organisation
+    ///
+    pub joined: NaiveDateTime,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AddOrganisationMember {
+    /// The id of the user to add
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub user_id: UserId,
+    /// The id of the organisation role the user is invited to the
+    /// organisation's projects with
+    ///
+    #[schema(example="123456789a", min_length=10, max_length=10)]
+    pub role_id: Option<OrganisationRoleId>,
+    /// Whether the user is made an admin, by default false
+    ///
+    #[schema(example=false)]
+    pub admin: Option<bool>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EditOrganisationMember {
+    /// The id of the member's new organisation role
+    ///
+    #[schema(example="123456789a", min_length=10, max_length=10)]
+    pub role_id: Option<OrganisationRoleId>,
+    /// Whether the member is an admin
+    ///
+    #[schema(example=false)]
+    pub admin: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema, Clone)]
+pub struct OrganisationRole {
+    /// The role's id
+    ///
+    #[schema(example="123456789a", min_length=10, max_length=10)]
+    pub id: OrganisationRoleId,
+    /// The id of the organisation the role belongs to
+    ///
+    #[schema(example="12345678", min_length=8, max_length=8)]
+    pub organisation_id: OrganisationId,
+    /// The role's name (1 -> 30 charachters)
+    ///
+    #[schema(example="Contributor", min_length=1, max_length=30)]
+    pub name: String,
+    /// The permissions the role has in the organisation's projects
+    ///
+    #[schema(value_type = u64, example=63)]
+    pub permissions: Permissions,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct OrganisationRoleBuilder {
+    /// The role's name (1 -> 30 charachters)
+    ///
+    #[schema(example="Contributor", min_length=1, max_length=30)]
+    pub name: String,
+    /// The permissions the role has in the organisation's projects
+    ///
+    #[schema(value_type = u64, example=63)]
+    pub permissions: Permissions,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EditOrganisationRole {
+    /// The role's new name (1 -> 30 charachters)
+    ///
+    #[schema(example="Contributor", min_length=1, max_length=30)]
+    pub name: Option<String>,
+    /// The role's new permissions
+    ///
+    #[schema(value_type = Option<u64>, example=63)]
+    pub permissions: Option<Permissions>,
+}
+
+/// Checks a name given to an organisation or one of its roles is
+/// 1 to 30 characters
+///
+fn check_name_length(name: &str) -> Result<(), ApiError> {
+    if !(1..=30).contains(&name.trim().chars().count()) {
+        return Err(ApiError::unprocessable_entity([("name", "must be between 1 and 30 characters")]));
+    }
+
+    Ok(())
+}
+
+impl Organisation {
+    /// Creates an organisation with the creator as its first admin.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: An `OrganisationBuilder` with the organisation's name.
+    /// * `creator`: The `UserId` of the user creating the organisation.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, ApiError>`, where:
+    /// - `Ok(organisation)` is returned with the created `Organisation`.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if the name is not 1 to 30 characters.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating IDs or executing the database queries.
+    ///
+    pub async fn create(
+        form: OrganisationBuilder,
+        creator: UserId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, ApiError> {
+        check_name_length(&form.name)?;
+
+        let organisation = Self {
+            id: OrganisationId::generate(&mut *transaction).await?,
+            name: form.name,
+            created: Utc::now().naive_utc(),
+        };
+
+        organisation.insert(transaction).await?;
+
+        let member = OrganisationMember {
+            id: OrganisationMemberId::generate(&mut *transaction).await?,
+            organisation_id: organisation.id.clone(),
+            user_id: creator,
+            admin: true,
+            role_id: None,
+            joined: organisation.created,
+        };
+
+        member.insert(transaction).await?;
+
+        Ok(organisation)
+    }
+
+    /// Renames the organisation on behalf of one of its admins.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: An `EditOrganisation` with the fields to change, fields which are not given are left unchanged.
+    /// * `editor`: The `OrganisationMember` making the change.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the organisation was updated.
+    /// - An `Err(ApiError::Forbidden)` is returned if the editor is not an admin.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if the new name is not 1 to 30 characters.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database query.
+    ///
+    pub async fn edit(
+        &mut self,
+        form: EditOrganisation,
+        editor: &OrganisationMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        editor.check_admin()?;
+
+        if let Some(name) = form.name {
+            check_name_length(&name)?;
+
+            self.name = name;
+        }
+
+        sqlx::query!(
+            "
+            UPDATE organisations
+            SET name = $1
+            WHERE id = $2
+            ",
+            self.name,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the organisation along with its memberships and roles on behalf
+    /// of one of its admins. Every project of the organisation has to be deleted
+    /// and purged from the trash first.
+    ///
+    /// # Arguments
+    ///
+    /// * `remover`: The `OrganisationMember` removing the organisation.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the organisation was removed.
+    /// - An `Err(ApiError::Forbidden)` is returned if the remover is not an admin.
+    /// - An `Err(ApiError::Conflict)` is returned if the organisation still has projects, including any in the trash.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn remove(
+        &self,
+        remover: &OrganisationMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        remover.check_admin()?;
+
+        let projects = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "total!: i64",
+                   COUNT(trashed) AS "trashed!: i64"
+            FROM projects
+            WHERE organisation_id = $1
+            "#,
+            self.id
+        )
+        .fetch_one(&mut **transaction)
+        .await?;
+
+        if projects.total > projects.trashed {
+            return Err(ApiError::Conflict(
+                "the organisation still has projects, delete them first"
+            ));
+        }
+
+        // Trashed projects can still be restored by their owners
+        // so are left until they are purged
+        if projects.trashed > 0 {
+            return Err(ApiError::Conflict(
+                "the organisation still has projects in the trash, wait for them to be purged"
+            ));
+        }
+
+        // Memberships reference the roles so have to be
+        // removed before them
+        sqlx::query!(
+            "
+            DELETE FROM organisation_members
+            WHERE organisation_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM organisation_roles
+            WHERE organisation_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM organisations
+            WHERE id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl Organisation {
+    /// Inserts the organisation into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO organisations (
+                id, name, created
+            )
+            VALUES (
+                $1, $2, $3
+            )
+            ",
+            self.id,
+            self.name,
+            self.created
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves an organisation by its id.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `OrganisationId` of the organisation.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(organisation))` is returned if the organisation exists.
+    /// - `Ok(None)` is returned if it does not.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get<'a, E>(
+        id: OrganisationId,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            Organisation,
+            "
+            SELECT id, name, created
+            FROM organisations
+            WHERE id = $1
+            ",
+            id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves the organisations the given user is a member of.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(organisations)` is returned with the organisations, ordered by name.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_many_from_user<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            Organisation,
+            "
+            SELECT organisations.id, organisations.name, organisations.created
+            FROM organisations
+            JOIN organisation_members ON organisation_members.organisation_id = organisations.id
+            WHERE organisation_members.user_id = $1
+            ORDER BY organisations.name
+            ",
+            user_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
+
+impl OrganisationMember {
+    /// Adds a user to the admin's organisation, which also invites them to every
+    /// one of the organisation's projects with their organisation role. The
+    /// user is notified.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: An `AddOrganisationMember` with the user to add and their role.
+    /// * `adder`: The `OrganisationMember` adding the user.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, ApiError>`, where:
+    /// - `Ok(member)` is returned with the new `OrganisationMember`.
+    /// - An `Err(ApiError::Forbidden)` is returned if the adder is not an admin.
+    /// - An `Err(ApiError::NotFound)` is returned if the user, or the role, does not exist.
+    /// - An `Err(ApiError::Conflict)` is returned if the user is already a member.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating IDs or executing the database queries.
+    ///
+    pub async fn add(
+        form: AddOrganisationMember,
+        adder: &OrganisationMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, ApiError> {
+        adder.check_admin()?;
+
+        if form.user_id.0 == DELETED_USER {
+            return Err(ApiError::NotFound);
+        }
+
+        User::get(form.user_id.clone(), &mut **transaction)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        if let Some(role_id) = &form.role_id {
+            OrganisationRole::get(role_id.clone(), adder.organisation_id.clone(), &mut **transaction)
+                .await?
+                .ok_or(ApiError::NotFound)?;
+        }
+
+        if Self::get_from_user(form.user_id.clone(), adder.organisation_id.clone(), &mut **transaction).await?.is_some() {
+            return Err(ApiError::Conflict("the user is already a member of the organisation"));
+        }
+
+        let organisation = Organisation::get(adder.organisation_id.clone(), &mut **transaction)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        let member = Self {
+            id: OrganisationMemberId::generate(&mut *transaction).await?,
+            organisation_id: organisation.id.clone(),
+            user_id: form.user_id,
+            admin: form.admin.unwrap_or(false),
+            role_id: form.role_id,
+            joined: Utc::now().naive_utc(),
+        };
+
+        member.insert(transaction).await?;
+
+        let adder_username = User::get(adder.user_id.clone(), &mut **transaction)
+            .await?
+            .map(|user| user.username)
+            .unwrap_or_else(|| adder.user_id.0.clone());
+
+        for project in Project::get_many_from_organisation(organisation.id.clone(), true, &mut **transaction).await? {
+            member.invite_to_project(&project, &adder_username, transaction).await?;
+        }
+
+        let builder = NotificationBuilder {
+            body: format!("{} added you to {}.", adder_username, organisation.name),
+            actions: Vec::new(),
+        };
+
+        Notification::send(builder, member.user_id.clone(), transaction).await?;
+
+        Ok(member)
+    }
+
+    /// Invites the member to one of the organisation's projects, unless they are
+    /// already a member or invited. They are offered the project role with the
+    /// same name as their organisation role, capped at the organisation role's
+    /// permissions, so an organisation never gives more than the project's own
+    /// roles allow. Without a matching role they are offered the same
+    /// permissions as a new member. The invitation is pending until accepted,
+    /// the member is sent a notification with actions to accept or deny it.
+    ///
+    /// # Arguments
+    ///
+    /// * `project`: The organisation's `Project` to invite the member to.
+    /// * `inviter_username`: The username of the user the invitation is sent on behalf of.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the member is now invited to, or already a member of, the project.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating the ID or executing the database queries.
+    ///
+    pub async fn invite_to_project(
+        &self,
+        project: &Project,
+        inviter_username: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        if ProjectMember::get_from_user(self.user_id.clone(), project.id.clone(), &mut **transaction).await?.is_some() {
+            return Ok(());
+        }
+
+        let role = match &self.role_id {
+            Some(role_id) => OrganisationRole::get(role_id.clone(), self.organisation_id.clone(), &mut **transaction).await?,
+            None => None,
+        };
+
+        let project_role = match &role {
+            Some(role) => ProjectRole::get_many_from_project(project.id.clone(), &mut **transaction)
+                .await?
+                .into_iter()
+                .find(|project_role| project_role.name == role.name),
+            None => None,
+        };
+
+        let (role_id, permissions) = match (role, project_role) {
+            (Some(role), Some(project_role)) => {
+                let permissions = project_role.permissions & role.permissions;
+
+                // The role is only kept if capping left it untouched
+                match permissions == project_role.permissions {
+                    true => (Some(project_role.id), permissions),
+                    false => (None, permissions),
+                }
+            }
+            _ => (None, Permissions::default()),
+        };
+
+        let member = ProjectMember {
+            id: ProjectMemberId::generate(&mut *transaction).await?,
+            project_id: project.id.clone(),
+            user_id: self.user_id.clone(),
+            permissions,
+            accepted: false,
+            role_id,
+        };
+
+        member.send_invitation(project, inviter_username, transaction).await?;
+
+        Ok(())
+    }
+
+    /// Changes the member's organisation role or whether they are an admin, on
+    /// behalf of one of the organisation's admins. A new role applies to the
+    /// projects the member is invited to from then on, their permissions in
+    /// projects they are already a member of are managed in those projects.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: An `EditOrganisationMember` with the fields to change, fields which are not given are left unchanged.
+    /// * `editor`: The `OrganisationMember` making the change.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the member was updated.
+    /// - An `Err(ApiError::Forbidden)` is returned if the editor is not an admin.
+    /// - An `Err(ApiError::NotFound)` is returned if the organisation has no role with the given id.
+    /// - An `Err(ApiError::Conflict)` is returned if the organisation would be left without an admin.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn edit(
+        &mut self,
+        form: EditOrganisationMember,
+        editor: &OrganisationMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        editor.check_admin()?;
+
+        if let Some(role_id) = form.role_id {
+            let role = OrganisationRole::get(role_id, self.organisation_id.clone(), &mut **transaction)
+                .await?
+                .ok_or(ApiError::NotFound)?;
+
+            self.role_id = Some(role.id);
+        }
+
+        if let Some(admin) = form.admin {
+            if self.admin && !admin {
+                self.check_not_last_admin(transaction).await?;
+            }
+
+            self.admin = admin;
+        }
+
+        sqlx::query!(
+            "
+            UPDATE organisation_members
+            SET admin = $1,
+                role_id = $2
+            WHERE id = $3
+            ",
+            self.admin,
+            self.role_id,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the member from the organisation and from each of its projects,
+    /// either on behalf of an admin or because the member is leaving. The
+    /// tasks the member created are kept. The member is notified if they were
+    /// removed by someone else.
+    ///
+    /// # Arguments
+    ///
+    /// * `remover`: The `OrganisationMember` removing the member, which is the member themselves if they are leaving.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the member was removed.
+    /// - An `Err(ApiError::Forbidden)` is returned if another member is being removed and the remover is not an admin.
+    /// - An `Err(ApiError::Conflict)` is returned if the member is the last admin or still owns one of the organisation's projects.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn remove(
+        &self,
+        remover: &OrganisationMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        let leaving = remover.id.0 == self.id.0;
+
+        if !leaving {
+            remover.check_admin()?;
+        }
+
+        if self.admin {
+            self.check_not_last_admin(transaction).await?;
+        }
+
+        let owned_projects = sqlx::query_scalar!(
+            "
+            SELECT COUNT(*)
+            FROM projects
+            WHERE organisation_id = $1
+                AND owner = $2
+            ",
+            self.organisation_id,
+            self.user_id
+        )
+        .fetch_one(&mut **transaction)
+        .await?;
+
+        if owned_projects > 0 {
+            return Err(ApiError::Conflict(
+                "the member still owns projects in the organisation, transfer their ownership first"
+            ));
+        }
+
+        for project in Project::get_many_from_organisation(self.organisation_id.clone(), true, &mut **transaction).await? {
+            let Some(membership) = ProjectMember::get_from_user(self.user_id.clone(), project.id, &mut **transaction).await? else {
+                continue;
+            };
+
+            if membership.accepted {
+                membership.hand_over_to_deleted(transaction).await?;
+                membership.leave(transaction).await?;
+            } else {
+                membership.deny_invitation(transaction).await?;
+            }
+        }
+
+        sqlx::query!(
+            "
+            DELETE FROM organisation_members
+            WHERE id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if !leaving {
+            let organisation = Organisation::get(self.organisation_id.clone(), &mut **transaction)
+                .await?
+                .ok_or(ApiError::NotFound)?;
+
+            let builder = NotificationBuilder {
+                body: format!("You were removed from {}.", organisation.name),
+                actions: Vec::new(),
+            };
+
+            Notification::send(builder, self.user_id.clone(), transaction).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the member can manage the organisation
+    ///
+    pub fn check_admin(&self) -> Result<(), ApiError> {
+        match self.admin {
+            true => Ok(()),
+            false => Err(ApiError::Forbidden),
+        }
+    }
+
+    /// Checks the organisation has an admin other than this member, so
+    /// it is never left without anyone to manage it
+    ///
+    async fn check_not_last_admin(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        let other_admins = sqlx::query_scalar!(
+            "
+            SELECT COUNT(*)
+            FROM organisation_members
+            WHERE organisation_id = $1
+                AND admin
+                AND id != $2
+            ",
+            self.organisation_id,
+            self.id
+        )
+        .fetch_one(&mut **transaction)
+        .await?;
+
+        if other_admins == 0 {
+            return Err(ApiError::Conflict(
+                "the organisation needs another admin first"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl OrganisationMember {
+    /// Inserts the membership into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO organisation_members (
+                id, organisation_id, user_id, admin, role_id, joined
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6
+            )
+            ",
+            self.id,
+            self.organisation_id,
+            self.user_id,
+            self.admin,
+            self.role_id,
+            self.joined
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the membership with the given id, as long as it belongs
+    /// to the given organisation.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `OrganisationMemberId` of the membership.
+    /// * `organisation_id`: The `OrganisationId` of the organisation the membership must belong to.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(member))` is returned if the organisation has a membership with the id.
+    /// - `Ok(None)` is returned if it does not.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get<'a, E>(
+        id: OrganisationMemberId,
+        organisation_id: OrganisationId,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            OrganisationMember,
+            r#"
+            SELECT id, organisation_id, user_id, admin,
+                   role_id AS "role_id: OrganisationRoleId", joined
+            FROM organisation_members
+            WHERE id = $1
+                AND organisation_id = $2
+            "#,
+            id,
+            organisation_id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves the given user's membership of the organisation.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user.
+    /// * `organisation_id`: The `OrganisationId` of the organisation.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(member))` is returned if the user is a member of the organisation.
+    /// - `Ok(None)` is returned if they are not.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_from_user<'a, E>(
+        user_id: UserId,
+        organisation_id: OrganisationId,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            OrganisationMember,
+            r#"
+            SELECT id, organisation_id, user_id, admin,
+                   role_id AS "role_id: OrganisationRoleId", joined
+            FROM organisation_members
+            WHERE user_id = $1
+                AND organisation_id = $2
+            "#,
+            user_id,
+            organisation_id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves the given user's organisation memberships.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(members)` is returned with the user's memberships.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_many_from_user<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            OrganisationMember,
+            r#"
+            SELECT id, organisation_id, user_id, admin,
+                   role_id AS "role_id: OrganisationRoleId", joined
+            FROM organisation_members
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Retrieves the organisation's members.
+    ///
+    /// # Arguments
+    ///
+    /// * `organisation_id`: The `OrganisationId` of the organisation.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(members)` is returned with the members, in the order they joined.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_many_from_organisation<'a, E>(
+        organisation_id: OrganisationId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            OrganisationMember,
+            r#"
+            SELECT id, organisation_id, user_id, admin,
+                   role_id AS "role_id: OrganisationRoleId", joined
+            FROM organisation_members
+            WHERE organisation_id = $1
+            ORDER BY joined
+            "#,
+            organisation_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
+
+impl OrganisationRole {
+    /// Creates a default role in the admin's organisation, new projects in the
+    /// organisation start with a copy of each of its roles.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: An `OrganisationRoleBuilder` with the role's name and permissions.
+    /// * `creator`: The `OrganisationMember` creating the role.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, ApiError>`, where:
+    /// - `Ok(role)` is returned with the created `OrganisationRole`.
+    /// - An `Err(ApiError::Forbidden)` is returned if the creator is not an admin.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if the name is taken or not 1 to 30 characters.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating the ID or executing the database queries.
+    ///
+    pub async fn create(
+        form: OrganisationRoleBuilder,
+        creator: &OrganisationMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, ApiError> {
+        creator.check_admin()?;
+
+        Self::check_name(&form.name, &creator.organisation_id, None, transaction).await?;
+
+        let role = Self {
+            id: OrganisationRoleId::generate(&mut *transaction).await?,
+            organisation_id: creator.organisation_id.clone(),
+            name: form.name,
+            permissions: form.permissions,
+        };
+
+        role.insert(transaction).await?;
+
+        Ok(role)
+    }
+
+    /// Renames the role or changes its permissions on behalf of an admin. The
+    /// change applies to projects created from then on, copies of the role in
+    /// existing projects are left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: An `EditOrganisationRole` with the fields to change, fields which are not given are left unchanged.
+    /// * `editor`: The `OrganisationMember` making the change.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the role was updated.
+    /// - An `Err(ApiError::Forbidden)` is returned if the editor is not an admin.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if the new name is taken or not 1 to 30 characters.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database query.
+    ///
+    pub async fn edit(
+        &mut self,
+        form: EditOrganisationRole,
+        editor: &OrganisationMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        editor.check_admin()?;
+
+        if let Some(name) = form.name {
+            Self::check_name(&name, &self.organisation_id, Some(&self.id), transaction).await?;
+
+            self.name = name;
+        }
+
+        if let Some(permissions) = form.permissions {
+            self.permissions = permissions;
+        }
+
+        let bits = self.permissions.bits() as i64;
+
+        sqlx::query!(
+            "
+            UPDATE organisation_roles
+            SET name = $1,
+                permissions = $2
+            WHERE id = $3
+            ",
+            self.name,
+            bits,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the role on behalf of an admin. Members with the role are
+    /// invited to projects with the same permissions as a new member from
+    /// then on.
+    ///
+    /// # Arguments
+    ///
+    /// * `remover`: The `OrganisationMember` removing the role.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the role was removed.
+    /// - An `Err(ApiError::Forbidden)` is returned if the remover is not an admin.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn remove(
+        &self,
+        remover: &OrganisationMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        remover.check_admin()?;
+
+        sqlx::query!(
+            "
+            UPDATE organisation_members
+            SET role_id = NULL
+            WHERE role_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM organisation_roles
+            WHERE id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Checks the name is 1 to 30 characters and not used by another of the
+    /// organisation's roles
+    ///
+    async fn check_name(
+        name: &str,
+        organisation_id: &OrganisationId,
+        role_id: Option<&OrganisationRoleId>,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        check_name_length(name)?;
+
+        let role_id = role_id.map(|id| id.0.as_str());
+
+        let taken = sqlx::query_scalar!(
+            "
+            SELECT COUNT(*)
+            FROM organisation_roles
+            WHERE organisation_id = $1
+            AND name = $2
+            AND id IS NOT $3
+            ",
+            organisation_id,
+            name,
+            role_id
+        )
+        .fetch_one(&mut **transaction)
+        .await? > 0;
+
+        if taken {
+            return Err(ApiError::unprocessable_entity([("name", "already taken")]));
+        }
+
+        Ok(())
+    }
+}
+
+impl OrganisationRole {
+    /// Inserts the role into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        let permissions = self.permissions.bits() as i64;
+
+        sqlx::query!(
+            "
+            INSERT INTO organisation_roles (
+                id, organisation_id, name, permissions
+            )
+            VALUES (
+                $1, $2, $3, $4
+            )
+            ",
+            self.id,
+            self.organisation_id,
+            self.name,
+            permissions
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the role with the given id, as long as it belongs to the
+    /// given organisation.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `OrganisationRoleId` of the role.
+    /// * `organisation_id`: The `OrganisationId` of the organisation the role must belong to.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(role))` is returned if the organisation has a role with the id.
+    /// - `Ok(None)` is returned if it does not.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get<'a, E>(
+        id: OrganisationRoleId,
+        organisation_id: OrganisationId,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            OrganisationRole,
+            "
+            SELECT id, organisation_id, name, permissions
+            FROM organisation_roles
+            WHERE id = $1
+                AND organisation_id = $2
+            ",
+            id,
+            organisation_id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves the organisation's roles.
+    ///
+    /// # Arguments
+    ///
+    /// * `organisation_id`: The `OrganisationId` of the organisation.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(roles)` is returned with the roles, ordered by name.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_many_from_organisation<'a, E>(
+        organisation_id: OrganisationId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            OrganisationRole,
+            "
+            SELECT id, organisation_id, name, permissions
+            FROM organisation_roles
+            WHERE organisation_id = $1
+            ORDER BY name
+            ",
+            organisation_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
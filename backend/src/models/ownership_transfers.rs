@@ -0,0 +1,374 @@
+use chrono::{NaiveDateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::database::Database;
+use crate::error::ApiError;
+
+use super::audits::Audit;
+use super::id::{OwnershipTransferId, ProjectId, ProjectMemberId};
+use super::notifications::{Notification, NotificationActionBuilder, NotificationBuilder};
+use super::projects::{Permissions, Project, ProjectMember};
+use super::users::DELETED_USER;
+
+#[derive(Serialize, ToSchema)]
+pub struct OwnershipTransfer {
+    /// The transfer's id
+    ///
+    #[schema(example="123456789abc", min_length=12, max_length=12)]
+    pub id: OwnershipTransferId,
+    /// The id of the project being transferred
+    ///
+    #[schema(example="12345678", min_length=8, max_length=8)]
+    pub project_id: ProjectId,
+    /// The id of the membership of the member nominated to
+    /// take over the project
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub nominee: ProjectMemberId,
+    /// The datetime the member was nominated
+    ///
+    pub created: NaiveDateTime,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct NominateOwner {
+    /// The id of the membership of the member to transfer
+    /// the project to
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub member_id: ProjectMemberId,
+}
+
+/// The endpoint a notification action calls to answer a nomination
+/// to take over the given project
+///
+fn transfer_endpoint(project_id: &ProjectId, answer: &str) -> String {
+    format!("/api/v1/projects/{}/transfer/{}", project_id.0, answer)
+}
+
+impl OwnershipTransfer {
+    /// Nominates a member to take over the project from its owner, replacing
+    /// any transfer which is already pending. The member is sent a notification
+    /// with actions to accept or deny it, and the nomination is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `nominee`: The `ProjectMember` to transfer the project to.
+    /// * `owner`: The `ProjectMember` of the project's owner.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, ApiError>`, where:
+    /// - `Ok(transfer)` is returned with the pending `OwnershipTransfer`.
+    /// - An `Err(ApiError::BadRequest)` is returned if the owner nominates themselves.
+    /// - An `Err(ApiError::Forbidden)` is returned if the nominating member does not own the project.
+    /// - An `Err(ApiError::NotFound)` is returned if the nominee is not a member of the project.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating the ID or executing the database queries.
+    ///
+    pub async fn create(
+        nominee: &ProjectMember,
+        owner: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, ApiError> {
+        let project = Project::get(owner.project_id.clone(), &mut **transaction)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        if project.owner.0 != owner.user_id.0 {
+            return Err(ApiError::Forbidden);
+        }
+
+        if nominee.id.0 == owner.id.0 {
+            return Err(ApiError::BadRequest);
+        }
+
+        if nominee.project_id.0 != project.id.0 || !nominee.accepted || nominee.user_id.0 == DELETED_USER {
+            return Err(ApiError::NotFound);
+        }
+
+        if let Some(pending) = Self::get_from_project(project.id.clone(), &mut **transaction).await? {
+            if let Some(pending_nominee) = ProjectMember::get(pending.nominee.clone(), &mut **transaction).await? {
+                pending.remove(&pending_nominee, transaction).await?;
+            }
+        }
+
+        let transfer = Self {
+            id: OwnershipTransferId::generate(&mut *transaction).await?,
+            project_id: project.id.clone(),
+            nominee: nominee.id.clone(),
+            created: Utc::now().naive_utc(),
+        };
+
+        transfer.insert(&mut *transaction).await?;
+
+        let owner_username = owner.username(transaction).await?;
+        let nominee_username = nominee.username(transaction).await?;
+
+        let builder = NotificationBuilder {
+            body: format!(
+                "{} wants to transfer ownership of {} to you.",
+                owner_username,
+                project.name
+            ),
+            actions: vec![
+                NotificationActionBuilder {
+                    title: "Accept".to_string(),
+                    action_endpoint: transfer_endpoint(&project.id, "accept"),
+                },
+                NotificationActionBuilder {
+                    title: "Deny".to_string(),
+                    action_endpoint: transfer_endpoint(&project.id, "deny"),
+                },
+            ],
+        };
+
+        Notification::send(builder, nominee.user_id.clone(), transaction).await?;
+
+        Audit::create(
+            owner,
+            format!("Nominated {} to take over ownership of the project", nominee_username),
+            transaction
+        ).await?;
+
+        Ok(transfer)
+    }
+
+    /// Makes the nominee the project's owner and gives them every permission.
+    /// The previous owner stays a member with their permissions and is
+    /// notified. The transfer is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `nominee`: The nominee's `ProjectMember`.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<ProjectMember, ApiError>`, where:
+    /// - `Ok(member)` is returned with the new owner's updated `ProjectMember`.
+    /// - An `Err(ApiError::NotFound)` is returned if the project no longer exists.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn accept(
+        self,
+        mut nominee: ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<ProjectMember, ApiError> {
+        let project = Project::get(self.project_id.clone(), &mut **transaction)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        let previous_owner = ProjectMember::get_from_user(
+            project.owner.clone(),
+            project.id.clone(),
+            &mut **transaction
+        ).await?;
+
+        self.remove(&nominee, transaction).await?;
+
+        sqlx::query!(
+            "
+            UPDATE projects
+            SET owner = $1
+            WHERE id = $2
+            ",
+            nominee.user_id,
+            project.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let permissions = Permissions::all().bits() as i64;
+
+        sqlx::query!(
+            "
+            UPDATE project_members
+            SET permissions = $1,
+                role_id = NULL
+            WHERE id = $2
+            ",
+            permissions,
+            nominee.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        nominee.permissions = Permissions::all();
+        nominee.role_id = None;
+
+        let nominee_username = nominee.username(transaction).await?;
+
+        let previous_username = match &previous_owner {
+            Some(member) => member.username(transaction).await?,
+            None => project.owner.0.clone(),
+        };
+
+        Audit::create(
+            &nominee,
+            format!("Took over ownership of the project from {}", previous_username),
+            transaction
+        ).await?;
+
+        let builder = NotificationBuilder {
+            body: format!("{} accepted ownership of {}.", nominee_username, project.name),
+            actions: Vec::new(),
+        };
+
+        Notification::send(builder, project.owner, transaction).await?;
+
+        Ok(nominee)
+    }
+
+    /// Declines the nomination, the project's owner is notified and the
+    /// refusal is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `nominee`: The nominee's `ProjectMember`.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the nomination was declined.
+    /// - An `Err(ApiError::NotFound)` is returned if the project no longer exists.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn deny(
+        self,
+        nominee: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        let project = Project::get(self.project_id.clone(), &mut **transaction)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        self.remove(nominee, transaction).await?;
+
+        let nominee_username = nominee.username(transaction).await?;
+
+        Audit::create(
+            nominee,
+            "Declined ownership of the project".to_string(),
+            transaction
+        ).await?;
+
+        let builder = NotificationBuilder {
+            body: format!("{} declined ownership of {}.", nominee_username, project.name),
+            actions: Vec::new(),
+        };
+
+        Notification::send(builder, project.owner, transaction).await?;
+
+        Ok(())
+    }
+
+    /// Removes the transfer along with the notification the nominee was sent,
+    /// once it has been answered or withdrawn its actions no longer do anything.
+    ///
+    /// # Arguments
+    ///
+    /// * `nominee`: The nominee's `ProjectMember`.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the transfer was removed.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries.
+    ///
+    pub async fn remove(
+        &self,
+        nominee: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            DELETE FROM ownership_transfers
+            WHERE id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Notification::remove_with_action(
+            nominee.user_id.clone(),
+            &transfer_endpoint(&self.project_id, "accept"),
+            transaction
+        ).await
+    }
+}
+
+impl OwnershipTransfer {
+    /// Inserts the transfer into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO ownership_transfers (
+                id, project_id, nominee, created
+            )
+            VALUES (
+                $1, $2, $3, $4
+            )
+            ",
+            self.id,
+            self.project_id,
+            self.nominee,
+            self.created
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the project's pending transfer.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_id`: The `ProjectId` of the project.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(transfer))` is returned if the project has a pending transfer.
+    /// - `Ok(None)` is returned if it does not.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_from_project<'a, E>(
+        project_id: ProjectId,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            OwnershipTransfer,
+            "
+            SELECT id, project_id, nominee, created
+            FROM ownership_transfers
+            WHERE project_id = $1
+            ",
+            project_id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+}
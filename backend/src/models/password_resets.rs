@@ -0,0 +1,176 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+
+use crate::database::Database;
+use crate::error::ApiError;
+use crate::utilities::hash_secret;
+
+use super::id::{PasswordResetId, UserId};
+
+/// How long a reset token can be used for after it is sent (in minutes)
+const RESET_LIFETIME: i64 = 60;
+
+pub struct PasswordReset {
+    /// The id of the reset request
+    ///
+    pub id: PasswordResetId,
+    /// The id of the user whose password can be reset
+    ///
+    pub user_id: UserId,
+    /// A hash of the reset token sent to the user
+    ///
+    pub token: String,
+    /// The datetime the reset was requested
+    ///
+    pub created: NaiveDateTime,
+    /// The datetime after which the token can no longer be used
+    ///
+    pub expires: NaiveDateTime,
+    /// Whether the token has already been used, each token can
+    /// only be used once
+    ///
+    pub used: bool,
+}
+
+impl PasswordReset {
+    /// Creates a new password reset for the given user, any earlier resets which
+    /// have not been used yet are invalidated.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user who requested the reset.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(Self, String), sqlx::error::Error>`, where:
+    /// - `Ok((reset, token))` is returned with the new `PasswordReset` and the plain text token to
+    ///   send to the user.
+    /// - An `sqlx::error::Error` is returned if there is an error generating the id or executing the queries.
+    ///
+    pub async fn create(
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(Self, String), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            UPDATE password_resets
+            SET used = true
+            WHERE user_id = $1
+            ",
+            user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let id = PasswordResetId::generate(&mut *transaction).await?;
+        let token = nanoid::nanoid!(48);
+        let now = Utc::now().naive_utc();
+
+        let reset = Self {
+            id,
+            user_id,
+            token: hash_secret(&token),
+            created: now,
+            expires: now + Duration::minutes(RESET_LIFETIME),
+            used: false,
+        };
+
+        reset.insert(&mut *transaction).await?;
+
+        Ok((reset, token))
+    }
+
+    /// Marks the reset belonging to the given token as used and returns the user
+    /// whose password may be changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `token`: The plain text token sent to the user.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<UserId, ApiError>`, where:
+    /// - `Ok(user_id)` is returned if the token is valid, unused and has not expired.
+    /// - An `Err(ApiError::Unauthorized)` is returned if it is not.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn redeem(
+        token: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<UserId, ApiError> {
+        let hash = hash_secret(token);
+        let now = Utc::now().naive_utc();
+
+        let reset = sqlx::query_as!(
+            PasswordReset,
+            "
+            SELECT id, user_id, token, created,
+                   expires, used
+            FROM password_resets
+            WHERE token = $1
+            AND used = false
+            AND expires > $2
+            ",
+            hash,
+            now
+        )
+        .fetch_optional(&mut **transaction)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+        sqlx::query!(
+            "
+            UPDATE password_resets
+            SET used = true
+            WHERE id = $1
+            ",
+            reset.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(reset.user_id)
+    }
+}
+
+impl PasswordReset {
+    /// Inserts the password reset into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO password_resets (
+                id, user_id, token, created,
+                expires, used
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6
+            )
+            ",
+            self.id,
+            self.user_id,
+            self.token,
+            self.created,
+            self.expires,
+            self.used
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -1,9 +1,21 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
 use utoipa::ToSchema;
 
-use crate::database::Database;
+use crate::database::{Database, SqlPool};
 use crate::error::ApiError;
+use crate::utilities::deserialize_some;
 
-use super::id::{ProjectId, UserId, ProjectMemberId};
+use super::audits::Audit;
+use super::id::{ProjectId, UserId, ProjectMemberId, ProjectRoleId, ProjectTemplateId, OrganisationId};
+use super::notifications::{Notification, NotificationActionBuilder, NotificationBuilder};
+use super::organisations::{Organisation, OrganisationMember};
+use super::ownership_transfers::OwnershipTransfer;
+use super::roles::ProjectRole;
+use super::tasks::Task;
+use super::templates::{ContentOptions, ProjectContent, ProjectTemplate};
+use super::users::{User, DELETED_USER};
 
 #[derive(Serialize, ToSchema)]
 pub struct Project {
@@ -25,10 +37,76 @@ pub struct Project {
     pub icon_url: String,
     /// The permissions of non-member by default this is none.
     /// And a membership would be needed to interact with the 
-    /// project in any capcacity.
+    /// project in any capcacity. Only permission to read the
+    /// project is given to non-members, anything more still
+    /// needs a membership.
     /// 
     #[schema(value_type = u64, example = 0)]
-    pub public_permissions: Permissions
+    pub public_permissions: Permissions,
+    /// Whether the project has been archived, archived projects
+    /// are read-only until they are unarchived
+    /// 
+    #[schema(example = false)]
+    pub archived: bool,
+    /// The datetime the project was moved to the trash, if it
+    /// has been
+    /// 
+    pub trashed: Option<NaiveDateTime>,
+    /// The id of the organisation the project belongs to, if
+    /// it belongs to one
+    /// 
+    #[schema(example="12345678", min_length=8, max_length=8)]
+    pub organisation_id: Option<OrganisationId>,
+    /// A short markdown summary of the project (0 -> 500 charachters)
+    /// 
+    #[schema(example = "A **rocket** to the moon", max_length = 500)]
+    pub description: String,
+    /// The project's markdown README (0 -> 20000 charachters)
+    /// 
+    #[schema(example = "# Getting started", max_length = 20000)]
+    pub readme: String,
+    /// Links to places related to the project such as its
+    /// repository or website
+    /// 
+    #[schema(value_type = Vec<ProjectLink>)]
+    pub links: ProjectLinks,
+    /// The date work on the project starts
+    /// 
+    #[schema(example = "2023-11-21")]
+    pub start_date: Option<NaiveDate>,
+    /// The date the project is aimed to be finished by
+    /// 
+    #[schema(example = "2024-01-31")]
+    pub target_date: Option<NaiveDate>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Clone)]
+pub struct ProjectLink {
+    /// The link's title (1 -> 30 charachters)
+    /// 
+    #[schema(example = "Repository", min_length = 1, max_length = 30)]
+    pub title: String,
+    /// The url the link points to, which must be http or https
+    /// 
+    #[schema(example = "https://example.com/repository")]
+    pub url: String,
+}
+
+/// Wrapper around the project's links, this is needed to
+/// deserialze the links field of the project
+///
+#[derive(Deserialize, Serialize, ToSchema, Clone, Default)]
+pub struct ProjectLinks(pub Vec<ProjectLink>);
+
+// The links are stored as JSON
+crate::json_column!(ProjectLinks);
+
+#[derive(Deserialize)]
+pub struct ListProjects {
+    /// Whether to include archived projects, by default they are
+    /// left out
+    /// 
+    pub include_archived: Option<bool>,
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -44,7 +122,36 @@ pub struct EditProject {
     /// The project's new visibility
     ///
     #[schema(value_type = Option<u64>, example = 0)]
-    pub public_permissions: Option<Permissions>
+    pub public_permissions: Option<Permissions>,
+    /// The project's new markdown description (0 -> 500 charachters),
+    /// null clears it
+    /// 
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<String>, nullable, example = "A **rocket** to the moon", max_length = 500)]
+    pub description: Option<Option<String>>,
+    /// The project's new markdown README (0 -> 20000 charachters),
+    /// null clears it
+    /// 
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<String>, nullable, example = "# Getting started", max_length = 20000)]
+    pub readme: Option<Option<String>>,
+    /// The project's new links, replacing every existing link
+    /// (at most 10), null clears them
+    /// 
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<Vec<ProjectLink>>, nullable)]
+    pub links: Option<Option<Vec<ProjectLink>>>,
+    /// The project's new start date, null clears it
+    /// 
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<NaiveDate>, nullable, example = "2023-11-21")]
+    pub start_date: Option<Option<NaiveDate>>,
+    /// The project's new target date, which can't be before the
+    /// start date, null clears it
+    /// 
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<NaiveDate>, nullable, example = "2024-01-31")]
+    pub target_date: Option<Option<NaiveDate>>,
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -60,11 +167,194 @@ pub struct ProjectBuilder {
     /// The project's visibility,
     /// 
     #[schema(value_type = u64, example = 0)]
-    pub public_permissions: Permissions
+    pub public_permissions: Permissions,
+    /// The id of one of the user's templates to create the project's
+    /// task groups, tasks, sub-tasks and labels from
+    /// 
+    #[schema(example="123456789abc", min_length=12, max_length=12)]
+    pub template_id: Option<ProjectTemplateId>,
+    /// The id of an organisation the user is a member of to create
+    /// the project in. The project starts with the organisation's
+    /// default roles and every member of the organisation is invited to it
+    /// 
+    #[schema(example="12345678", min_length=8, max_length=8)]
+    pub organisation_id: Option<OrganisationId>,
+    /// The project's markdown description (0 -> 500 charachters)
+    /// 
+    #[schema(example = "A **rocket** to the moon", max_length = 500)]
+    pub description: Option<String>,
+    /// The project's markdown README (0 -> 20000 charachters)
+    /// 
+    #[schema(example = "# Getting started", max_length = 20000)]
+    pub readme: Option<String>,
+    /// The project's links (at most 10)
+    /// 
+    pub links: Option<Vec<ProjectLink>>,
+    /// The date work on the project starts
+    /// 
+    #[schema(example = "2023-11-21")]
+    pub start_date: Option<NaiveDate>,
+    /// The date the project is aimed to be finished by, which
+    /// can't be before the start date
+    /// 
+    #[schema(example = "2024-01-31")]
+    pub target_date: Option<NaiveDate>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CloneProject {
+    /// The new project's name (3 -> 30 charachters)
+    /// 
+    #[schema(example = "My project", min_length = 3, max_length = 30)]
+    pub name: String,
+    /// The new project's icon's url, by default the original's
+    /// 
+    #[schema(example = "https://example.com/icon.png")]
+    pub icon_url: Option<String>,
+    /// Whether to copy the task groups, by default true
+    /// 
+    #[schema(example = true)]
+    pub task_groups: Option<bool>,
+    /// Whether to copy the tasks and their dependencies, these are
+    /// only copied along with the task groups. By default true
+    /// 
+    #[schema(example = true)]
+    pub tasks: Option<bool>,
+    /// Whether to copy the sub-tasks, these are only copied along
+    /// with the tasks. By default true
+    /// 
+    #[schema(example = true)]
+    pub sub_tasks: Option<bool>,
+    /// Whether to copy the labels, these are only copied along
+    /// with the tasks. By default true
+    /// 
+    #[schema(example = true)]
+    pub labels: Option<bool>,
+    /// Whether to invite the members to the new project with the
+    /// same roles and permissions, this needs permission to invite
+    /// members. By default false
+    /// 
+    #[schema(example = false)]
+    pub members: Option<bool>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct InviteMembers {
+    /// The ids of the users to invite
+    /// 
+    #[schema(example=json!(["03082007"]))]
+    pub user_ids: Vec<UserId>,
+    /// The id of the role the users will have once they accept,
+    /// the inviter has to hold every one of its permissions
+    /// 
+    #[schema(example="123456789a", min_length=10, max_length=10)]
+    pub role_id: Option<ProjectRoleId>,
+    /// The permissions the users will have once they accept if no
+    /// role is given, by default the same as a new member's. These
+    /// are capped at the inviter's own permissions.
+    /// 
+    #[schema(value_type = Option<u64>, example = 0)]
+    pub permissions: Option<Permissions>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EditMember {
+    /// The id of the member's new role, which they will have
+    /// the permissions of
+    /// 
+    #[schema(example="123456789a", min_length=10, max_length=10)]
+    pub role_id: Option<ProjectRoleId>,
+    /// The member's new permissions if no role is given, which
+    /// overrides their role. Only permissions the editor holds
+    /// themselves can be given or taken away
+    /// 
+    #[schema(value_type = Option<u64>, example = 0)]
+    pub permissions: Option<Permissions>,
+}
+
+#[derive(Deserialize)]
+pub struct RemoveMember {
+    /// Whether to delete the tasks the member created rather
+    /// than keeping them, by default they are kept
+    /// 
+    pub delete_tasks: Option<bool>,
+}
+
+/// The permissions a public project can give users who are not members,
+/// anything more requires a membership to audit their actions against
+///
+const VISITOR_PERMISSIONS: Permissions = Permissions::READ_PROJECT;
+
+/// The permissions members keep in archived projects, enough to read
+/// the project and to unarchive or delete it
+///
+const ARCHIVED_PERMISSIONS: Permissions = Permissions::READ_PROJECT
+    .union(Permissions::EDIT_PROJECT)
+    .union(Permissions::DELETE_RPOJECT);
+
+/// The most links a project can have
+///
+const MAX_LINKS: usize = 10;
+
+/// Checks the details describing a project are within their limits and
+/// that the project's target date is not before its start date
+///
+fn check_details(
+    description: Option<&str>,
+    readme: Option<&str>,
+    links: Option<&[ProjectLink]>,
+    start_date: Option<NaiveDate>,
+    target_date: Option<NaiveDate>,
+) -> Result<(), ApiError> {
+    let mut errors = Vec::new();
+
+    if description.is_some_and(|description| description.chars().count() > 500) {
+        errors.push(("description", "must be at most 500 characters"));
+    }
+
+    if readme.is_some_and(|readme| readme.chars().count() > 20000) {
+        errors.push(("readme", "must be at most 20000 characters"));
+    }
+
+    if let Some(links) = links {
+        let invalid = links.iter().any(|link| {
+            !(1..=30).contains(&link.title.trim().chars().count())
+                || !(link.url.starts_with("https://") || link.url.starts_with("http://"))
+                || link.url.len() > 2000
+        });
+
+        if links.len() > MAX_LINKS {
+            errors.push(("links", "must be at most 10 links"));
+        } else if invalid {
+            errors.push(("links", "must have a 1 to 30 character title and an http or https url"));
+        }
+    }
+
+    if let (Some(start_date), Some(target_date)) = (start_date, target_date) {
+        if target_date < start_date {
+            errors.push(("target_date", "must not be before the start date"));
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(ApiError::unprocessable_entity(errors));
+    }
+
+    Ok(())
+}
+
+/// The endpoint a notification action calls to answer an invitation
+/// to the given project
+///
+fn invitation_endpoint(project_id: &ProjectId, answer: &str) -> String {
+    format!("/api/v1/projects/{}/invitation/{}", project_id.0, answer)
 }
 
 impl Project {
-    /// Creates a new project and inserts it into the database.
+    /// Creates a new project and inserts it into the database. If a template
+    /// is given the project starts with the template's content. Projects
+    /// created in an organisation start with its default roles, falling back
+    /// to the built-in ones, and the organisation's members are invited to them.
     ///
     /// # Arguments
     ///
@@ -74,15 +364,50 @@ impl Project {
     ///
     /// # Returns
     ///
-    /// This method returns `Result<Self, sqlx::error::Error>`, where:
+    /// This method returns `Result<Self, ApiError>`, where:
     /// - `Ok(project)` is returned with the created `Project` instance if the creation and insertion are successful.
-    /// - An `sqlx::error::Error` is returned if there is an error generating IDs or executing the database queries.
+    /// - An `Err(ApiError::NotFound)` is returned if the creator has no template with the given id,
+    ///   or is not a member of the given organisation.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if the project's details are invalid.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating IDs or executing the database queries.
     ///
     pub async fn create(
         form: ProjectBuilder,
         creator: UserId,
         transaction: &mut sqlx::Transaction<'_, Database>,
-    ) -> Result<Self, sqlx::error::Error> {
+    ) -> Result<Self, ApiError> {
+        let template = match form.template_id {
+            Some(template_id) => Some(
+                ProjectTemplate::get(template_id, creator.clone(), &mut **transaction)
+                    .await?
+                    .ok_or(ApiError::NotFound)?
+            ),
+            None => None,
+        };
+
+        check_details(
+            form.description.as_deref(),
+            form.readme.as_deref(),
+            form.links.as_deref(),
+            form.start_date,
+            form.target_date
+        )?;
+
+        let organisation = match form.organisation_id {
+            Some(organisation_id) => {
+                OrganisationMember::get_from_user(creator.clone(), organisation_id.clone(), &mut **transaction)
+                    .await?
+                    .ok_or(ApiError::NotFound)?;
+
+                Some(
+                    Organisation::get(organisation_id, &mut **transaction)
+                        .await?
+                        .ok_or(ApiError::NotFound)?
+                )
+            }
+            None => None,
+        };
+
         let id = ProjectId::generate(&mut *transaction).await?;
 
         let project = Self {
@@ -90,7 +415,15 @@ impl Project {
             name: form.name.clone(),
             owner: creator.clone(),
             icon_url: form.icon_url.clone(),
-            public_permissions: form.public_permissions
+            public_permissions: form.public_permissions,
+            archived: false,
+            trashed: None,
+            organisation_id: organisation.as_ref().map(|organisation| organisation.id.clone()),
+            description: form.description.unwrap_or_default(),
+            readme: form.readme.unwrap_or_default(),
+            links: ProjectLinks(form.links.unwrap_or_default()),
+            start_date: form.start_date,
+            target_date: form.target_date,
         };
 
         project.insert(transaction).await?;
@@ -102,32 +435,232 @@ impl Project {
             project_id: project.id.clone(),
             user_id: creator,
             permissions: Permissions::all(),
-            accepted: true
+            accepted: true,
+            role_id: None,
         };
 
         project_member.insert(&mut *transaction).await?;
 
+        let roles = match &organisation {
+            Some(organisation) => ProjectRole::copy_from_organisation(
+                organisation.id.clone(),
+                project.id.clone(),
+                transaction
+            ).await?,
+            None => Vec::new(),
+        };
+
+        if roles.is_empty() {
+            ProjectRole::create_defaults(project.id.clone(), transaction).await?;
+        }
+
+        if let Some(organisation) = &organisation {
+            let creator_username = User::get(project.owner.clone(), &mut **transaction)
+                .await?
+                .ok_or(ApiError::NotFound)?
+                .username;
+
+            for member in OrganisationMember::get_many_from_organisation(organisation.id.clone(), &mut **transaction).await? {
+                if member.user_id.0 != project.owner.0 {
+                    member.invite_to_project(&project, &creator_username, transaction).await?;
+                }
+            }
+        }
+
+        if let Some(template) = template {
+            template.content.build(&project.id, &project_member.id, &HashMap::new(), transaction).await?;
+
+            Audit::create(
+                &project_member,
+                format!("Created the project from the {} template", template.name),
+                transaction
+            ).await?;
+        }
+
         Ok(project)
     }
 
+    /// Creates a new project owned by the cloner as a copy of the given
+    /// project. Its description, README and links are copied along with
+    /// the parts of the project's structure chosen,
+    /// and its members can be invited to the copy with the same roles and
+    /// permissions. Sub-tasks stay assigned to members who are invited.
+    ///
+    /// # Arguments
+    ///
+    /// * `source`: The `Project` to clone.
+    /// * `form`: A `CloneProject` with the new project's details and what to copy.
+    /// * `cloner`: The `ProjectMember` of the user cloning the project in the source project.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, ApiError>`, where:
+    /// - `Ok(project)` is returned with the new `Project`.
+    /// - An `Err(ApiError::Forbidden)` is returned if members are copied and the cloner can't invite members.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating IDs or executing the database queries.
+    ///
+    pub async fn create_clone(
+        source: &Project,
+        form: CloneProject,
+        cloner: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, ApiError> {
+        let copy_members = form.members.unwrap_or(false);
+
+        if copy_members {
+            cloner.check_permissions(Permissions::INVITE_MEMBERS)?;
+        }
+
+        let options = ContentOptions {
+            task_groups: form.task_groups.unwrap_or(true),
+            tasks: form.tasks.unwrap_or(true),
+            sub_tasks: form.sub_tasks.unwrap_or(true),
+            labels: form.labels.unwrap_or(true),
+        };
+
+        let builder = ProjectBuilder {
+            name: form.name,
+            icon_url: form.icon_url.unwrap_or_else(|| source.icon_url.clone()),
+            public_permissions: source.public_permissions,
+            template_id: None,
+            organisation_id: None,
+            description: Some(source.description.clone()),
+            readme: Some(source.readme.clone()),
+            links: Some(source.links.0.clone()),
+            start_date: None,
+            target_date: None,
+        };
+
+        let project = Self::create(builder, cloner.user_id.clone(), transaction).await?;
+
+        let owner = ProjectMember::get_from_user(cloner.user_id.clone(), project.id.clone(), &mut **transaction)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        // The memberships in the new project keyed by the membership
+        // id in the source, so sub-tasks can keep their assignees
+        let mut assignees = HashMap::new();
+
+        if copy_members {
+            let roles = ProjectRole::copy_from_project(source.id.clone(), project.id.clone(), transaction).await?;
+
+            for member in ProjectMember::get_many_from_project(source.id.clone(), &mut **transaction).await? {
+                if !member.accepted {
+                    continue;
+                }
+
+                if member.user_id.0 == cloner.user_id.0 {
+                    assignees.insert(member.id.0, owner.id.clone());
+                    continue;
+                }
+
+                let form = InviteMembers {
+                    user_ids: vec![member.user_id],
+                    role_id: member.role_id.and_then(|role_id| roles.get(&role_id.0).cloned()),
+                    permissions: Some(member.permissions),
+                };
+
+                for invitation in ProjectMember::invite_users(form, &owner, transaction).await? {
+                    assignees.insert(member.id.0.clone(), invitation.id);
+                }
+            }
+        }
+
+        ProjectContent::read(&source.id, options, transaction)
+            .await?
+            .build(&project.id, &owner.id, &assignees, transaction)
+            .await?;
+
+        Audit::create(
+            &owner,
+            format!("Cloned the project from {}", source.name),
+            transaction
+        ).await?;
+
+        Ok(project)
+    }
+
+    /// The permissions everyone has in the project, whether or not they
+    /// are a member
+    ///
+    pub fn visitor_permissions(&self) -> Permissions {
+        self.public_permissions & VISITOR_PERMISSIONS
+    }
+
+    /// Updates the project's details, fields which are not given are left
+    /// unchanged while optional fields given as null are cleared. Links given
+    /// replace every existing link.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_id`: The `ProjectId` of the project to edit.
+    /// * `form`: An `EditProject` with the fields to change.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the project was updated.
+    /// - An `Err(ApiError::NotFound)` is returned if the project does not exist.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if the new details are invalid.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
     pub async fn edit(
         project_id: ProjectId,
         form: EditProject,
         transaction: &mut sqlx::Transaction<'_, Database>
-    ) -> Result<(), sqlx::error::Error> {
+    ) -> Result<(), ApiError> {
+        let project = Self::get(project_id.clone(), &mut **transaction)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        // Optional fields are worked out here rather than coalesced
+        // in the query so that they can be cleared
+        let description = form.description
+            .map(Option::unwrap_or_default)
+            .unwrap_or(project.description);
+        let readme = form.readme
+            .map(Option::unwrap_or_default)
+            .unwrap_or(project.readme);
+        let links = form.links
+            .map(Option::unwrap_or_default)
+            .unwrap_or(project.links.0);
+        let start_date = form.start_date.unwrap_or(project.start_date);
+        let target_date = form.target_date.unwrap_or(project.target_date);
+
+        check_details(
+            Some(&description),
+            Some(&readme),
+            Some(&links),
+            start_date,
+            target_date
+        )?;
+
         let permissions = form.public_permissions.map(|p| p.bits() as i64);
+        let links = serde_json::to_string(&links).map_err(anyhow::Error::from)?;
 
         sqlx::query!(
             "
             UPDATE projects
             SET name = coalesce($1, name),
                 icon_url = coalesce($2, icon_url),
-                public_permissions = coalesce($3, public_permissions)
-            WHERE id = $4
+                public_permissions = coalesce($3, public_permissions),
+                description = $4,
+                readme = $5,
+                links = $6,
+                start_date = $7,
+                target_date = $8
+            WHERE id = $9
             ",
             form.name,
             form.icon_url,
             permissions,
+            description,
+            readme,
+            links,
+            start_date,
+            target_date,
             project_id
         )
         .execute(&mut **transaction)
@@ -136,7 +669,186 @@ impl Project {
         Ok(())
     }
 
-    /// Removes the project and associated data from the database.
+    /// Archives or unarchives the project. Archived projects are read-only
+    /// and left out of members' project lists unless asked for. Changing
+    /// the state is audited, setting it to what it already is does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `archived`: Whether the project should be archived.
+    /// * `editor`: The `ProjectMember` of the member changing the state.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the project is in the requested state.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries.
+    ///
+    pub async fn set_archived(
+        &mut self,
+        archived: bool,
+        editor: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        if self.archived == archived {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            "
+            UPDATE projects
+            SET archived = $1
+            WHERE id = $2
+            ",
+            archived,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        self.archived = archived;
+
+        let action = match archived {
+            true => "Archived the project",
+            false => "Unarchived the project",
+        };
+
+        Audit::create(editor, action.to_string(), transaction).await?;
+
+        Ok(())
+    }
+
+    /// Moves the project to the trash, it can be restored until it has been
+    /// there longer than the instance's retention period after which it is
+    /// purged. This is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `remover`: The `ProjectMember` of the member trashing the project.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the project was moved to the trash.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries.
+    ///
+    pub async fn trash(
+        &mut self,
+        remover: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        let trashed = Utc::now().naive_utc();
+
+        sqlx::query!(
+            "
+            UPDATE projects
+            SET trashed = $1
+            WHERE id = $2
+            ",
+            trashed,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        self.trashed = Some(trashed);
+
+        Audit::create(remover, "Moved the project to the trash".to_string(), transaction).await?;
+
+        Ok(())
+    }
+
+    /// Restores the project from the trash, this is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `restorer`: The `ProjectMember` of the member restoring the project.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the project was restored.
+    /// - An `Err(ApiError::NotFound)` is returned if the project is not in the trash.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn restore(
+        &mut self,
+        restorer: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        if self.trashed.is_none() {
+            return Err(ApiError::NotFound);
+        }
+
+        sqlx::query!(
+            "
+            UPDATE projects
+            SET trashed = NULL
+            WHERE id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        self.trashed = None;
+
+        Audit::create(restorer, "Restored the project from the trash".to_string(), transaction).await?;
+
+        Ok(())
+    }
+
+    /// Permanently removes every project which has been in the trash for
+    /// longer than the retention period. Each project is removed in its own
+    /// transaction so one failing doesn't hold back the rest.
+    ///
+    /// # Arguments
+    ///
+    /// * `retention_days`: The number of days projects are kept in the trash.
+    /// * `pool`: The `SqlPool` to remove the projects with.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<usize, sqlx::error::Error>`, where:
+    /// - `Ok(count)` is returned with the number of projects removed.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries.
+    ///
+    pub async fn purge_trash(
+        retention_days: i64,
+        pool: &SqlPool,
+    ) -> Result<usize, sqlx::error::Error> {
+        let cutoff = Utc::now().naive_utc() - Duration::days(retention_days);
+
+        let expired = sqlx::query_scalar!(
+            r#"
+            SELECT id AS "id: ProjectId"
+            FROM projects
+            WHERE trashed IS NOT NULL
+                AND trashed <= $1
+            "#,
+            cutoff
+        )
+        .fetch_all(pool)
+        .await?;
+
+        for id in &expired {
+            let mut transaction = pool.begin().await?;
+
+            Self::remove(id.clone(), &mut transaction).await?;
+
+            transaction.commit().await?;
+        }
+
+        Ok(expired.len())
+    }
+
+    /// Permanently removes the project and associated data from the database.
+    /// Deleting a project through the api moves it to the trash, this is only
+    /// called once it has been purged from there.
+    ///
     /// # Arguments
     ///
     /// * `id`: The `ProjectId` of the project to be removed.
@@ -152,6 +864,46 @@ impl Project {
         id: ProjectId,
         transaction: &mut sqlx::Transaction<'_, Database>,
     ) -> Result<(), sqlx::error::Error> {
+        // Remove all associated labels
+        sqlx::query!(
+            "
+            DELETE FROM labels
+            WHERE project_id = $1
+            ",
+            id
+        )
+        .execute(&mut **transaction)
+        .await?;
+        // Remove all associated task edges
+        sqlx::query!(
+            "
+            DELETE FROM task_edges
+            WHERE project_id = $1
+            ",
+            id
+        )
+        .execute(&mut **transaction)
+        .await?;
+        // Remove all associated audits
+        sqlx::query!(
+            "
+            DELETE FROM audit_log
+            WHERE project_id = $1
+            ",
+            id
+        )
+        .execute(&mut **transaction)
+        .await?;
+        // Remove all associated access token scopes
+        sqlx::query!(
+            "
+            DELETE FROM personal_access_token_projects
+            WHERE project_id = $1
+            ",
+            id
+        )
+        .execute(&mut **transaction)
+        .await?;
         // Remove all associated sub tasks
         sqlx::query!(
             "
@@ -182,6 +934,26 @@ impl Project {
         )
         .execute(&mut **transaction)
         .await?;
+        // Remove all invite links
+        sqlx::query!(
+            "
+            DELETE FROM project_invite_links
+            WHERE project_id = $1
+            ",
+            id
+        )
+        .execute(&mut **transaction)
+        .await?;
+        // Remove any pending ownership transfer
+        sqlx::query!(
+            "
+            DELETE FROM ownership_transfers
+            WHERE project_id = $1
+            ",
+            id
+        )
+        .execute(&mut **transaction)
+        .await?;
         // Remove all memberships
         sqlx::query!(
             "
@@ -192,6 +964,16 @@ impl Project {
         )
         .execute(&mut **transaction)
         .await?;
+        // Remove all roles
+        sqlx::query!(
+            "
+            DELETE FROM project_roles
+            WHERE project_id = $1
+            ",
+            id
+        )
+        .execute(&mut **transaction)
+        .await?;
         // Remove the project itself
         sqlx::query!(
             "
@@ -216,31 +998,43 @@ impl Project {
     ///
     /// # Returns
     ///
-    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// This method returns `Result<(), ApiError>`, where:
     /// - `Ok(())` is returned if the insertion is successful.
-    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    /// - An `Err(ApiError::Anyhow)` is returned if the links can't be serialized.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database query.
     ///
     pub async fn insert(
         &self,
         transaction: &mut sqlx::Transaction<'_, Database>,
-    ) -> Result<(), sqlx::error::Error> {
+    ) -> Result<(), ApiError> {
         let permssions = self.public_permissions.bits() as i64;
+        let links = serde_json::to_string(&self.links).map_err(anyhow::Error::from)?;
 
         sqlx::query!(
             "
             INSERT INTO projects (
                 id, name, owner, icon_url,
-                public_permissions
+                public_permissions, archived, trashed,
+                organisation_id, description, readme,
+                links, start_date, target_date
             )
             VALUES (
-                $1, $2, $3, $4, $5
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13
             )
             ",
             self.id,
             self.name,
             self.owner,
             self.icon_url,
-            permssions
+            permssions,
+            self.archived,
+            self.trashed,
+            self.organisation_id,
+            self.description,
+            self.readme,
+            links,
+            self.start_date,
+            self.target_date
         )
         .execute(&mut **transaction)
         .await?;
@@ -271,12 +1065,15 @@ impl Project {
     {
         let project = sqlx::query_as!(
             Project,
-            "
+            r#"
             SELECT id, name, owner, icon_url, 
-                   public_permissions
+                   public_permissions, archived, trashed,
+                   organisation_id AS "organisation_id: OrganisationId",
+                   description, readme, links AS "links: ProjectLinks",
+                   start_date, target_date
             FROM projects
             WHERE id = $1
-            ",
+            "#,
             id
         )
         .fetch_optional(executor)
@@ -285,6 +1082,48 @@ impl Project {
         Ok(project)
     }
 
+    /// Retrieves a page of every project on the instance, ordered by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit`: The maximum number of projects to retrieve.
+    /// * `offset`: The number of projects to skip.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(projects)` is returned with the page of projects.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_all<'a, E>(
+        limit: u32,
+        offset: u32,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            Project,
+            r#"
+            SELECT id, name, owner, icon_url, 
+                   public_permissions, archived, trashed,
+                   organisation_id AS "organisation_id: OrganisationId",
+                   description, readme, links AS "links: ProjectLinks",
+                   start_date, target_date
+            FROM projects
+            ORDER BY name
+            LIMIT $1
+            OFFSET $2
+            "#,
+            limit,
+            offset
+        )
+        .fetch_all(executor)
+        .await
+    }
+
     /// Retrieves multiple projects from the database based on the specified column and value.
     ///
     /// # Arguments
@@ -309,12 +1148,15 @@ impl Project {
     {
         let results = sqlx::query_as!(
             Project,
-            "
+            r#"
             SELECT id, name, owner, icon_url, 
-                   public_permissions
+                   public_permissions, archived, trashed,
+                   organisation_id AS "organisation_id: OrganisationId",
+                   description, readme, links AS "links: ProjectLinks",
+                   start_date, target_date
             FROM projects
             WHERE $1 = $2
-            ",
+            "#,
             column,
             value
         )
@@ -323,10 +1165,102 @@ impl Project {
 
         Ok(results)
     }
+
+    /// Retrieves the organisation's projects, leaving out projects in the
+    /// trash.
+    ///
+    /// # Arguments
+    ///
+    /// * `organisation_id`: The `OrganisationId` of the organisation.
+    /// * `include_archived`: Whether archived projects are included.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(projects)` is returned with the projects, ordered by name.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_many_from_organisation<'a, E>(
+        organisation_id: OrganisationId,
+        include_archived: bool,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            Project,
+            r#"
+            SELECT id, name, owner, icon_url, 
+                   public_permissions, archived, trashed,
+                   organisation_id AS "organisation_id: OrganisationId",
+                   description, readme, links AS "links: ProjectLinks",
+                   start_date, target_date
+            FROM projects
+            WHERE organisation_id = $1
+                AND trashed IS NULL
+                AND (NOT archived OR $2)
+            ORDER BY name
+            "#,
+            organisation_id,
+            include_archived
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Retrieves the projects in the trash which the given user has
+    /// permission to restore.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(projects)` is returned with the trashed projects, most recently trashed first.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_trashed_from_user<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        let permission = Permissions::DELETE_RPOJECT.bits() as i64;
+
+        sqlx::query_as!(
+            Project,
+            r#"
+            SELECT projects.id, projects.name, projects.owner,
+                   projects.icon_url, projects.public_permissions,
+                   projects.archived, projects.trashed,
+                   projects.organisation_id AS "organisation_id: OrganisationId",
+                   projects.description, projects.readme,
+                   projects.links AS "links: ProjectLinks",
+                   projects.start_date, projects.target_date
+            FROM projects
+            JOIN project_members ON project_members.project_id = projects.id
+            WHERE project_members.user_id = $1
+                AND project_members.accepted
+                AND project_members.permissions & $2 = $2
+                AND projects.trashed IS NOT NULL
+            ORDER BY projects.trashed DESC
+            "#,
+            user_id,
+            permission
+        )
+        .fetch_all(executor)
+        .await
+    }
 }
 
 bitflags::bitflags! {
-    #[derive(Serialize, Deserialize, ToSchema)]
+    #[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq)]
     #[serde(transparent)]
     pub struct Permissions: u64 {
         // Permission to read any information about
@@ -398,6 +1332,21 @@ impl Permissions {
             Err(ApiError::Forbidden)
         }
     }
+
+    /// The names of the set flags separated by `|`, as written in
+    /// audit entries
+    ///
+    pub fn names(&self) -> String {
+        if self.is_empty() {
+            return "none".to_string();
+        }
+
+        let mut names = String::new();
+        // Writing to a string cannot fail
+        let _ = bitflags::parser::to_writer(self, &mut names);
+
+        names
+    }
 }
 
 impl From<i64> for Permissions {
@@ -462,47 +1411,139 @@ pub struct ProjectMember {
     /// 
     #[schema(example=true)]
     pub accepted: bool,
+    /// The id of the member's role, if their permissions have
+    /// been set by hand this is null
+    /// 
+    #[schema(example="123456789a", min_length=10, max_length=10)]
+    pub role_id: Option<ProjectRoleId>,
 }
 
 impl ProjectMember {
-    /// Invites a user to the project and inserts the invitation as a `ProjectMember` into the database.
+    /// Invites users to the inviter's project. The invitations are pending until
+    /// the users accept them, and each user is sent a notification with actions
+    /// to accept or deny it. Users who are already members, or already invited,
+    /// are skipped.
     ///
     /// # Arguments
     ///
-    /// * `user`: The `UserId` of the user to be invited to the project.
-    /// * `project`: A reference to the `Project` instance to which the user is being invited.
+    /// * `form`: An `InviteMembers` with the users to invite and the role or permissions to give them,
+    ///   permissions are capped at the inviter's own.
+    /// * `inviter`: The `ProjectMember` sending the invitations.
     /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
     ///
     /// # Returns
     ///
-    /// This method returns `Result<ProjectMember, sqlx::error::Error>`, where:
-    /// - `Ok(member)` is returned with the created `ProjectMember` instance representing the invitation
-    ///    if the insertion is successful and the user is invited to the project.
-    /// - An `sqlx::error::Error` is returned if there is an error generating an ID or executing the database query.
+    /// This method returns `Result<Vec<ProjectMember>, ApiError>`, where:
+    /// - `Ok(invitations)` is returned with the pending `ProjectMember`s that were created.
+    /// - An `Err(ApiError::Forbidden)` is returned if the inviter does not hold every permission of the role.
+    /// - An `Err(ApiError::NotFound)` is returned if one of the users, or the role, does not exist.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating an ID or executing the database queries.
     ///
     pub async fn invite_users(
-        user_ids: Vec<UserId>,
-        project_id: ProjectId,
+        form: InviteMembers,
+        inviter: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Vec<ProjectMember>, ApiError> {
+        let project = Project::get(inviter.project_id.clone(), &mut **transaction)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        let inviter_user = User::get(inviter.user_id.clone(), &mut **transaction)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        let (role_id, permissions) = match form.role_id {
+            Some(role_id) => {
+                let role = ProjectRole::get(role_id, project.id.clone(), &mut **transaction)
+                    .await?
+                    .ok_or(ApiError::NotFound)?;
+
+                inviter.check_permissions(role.permissions)?;
+
+                (Some(role.id), role.permissions)
+            }
+            // Members can never hand out permissions they do not have
+            None => (None, form.permissions.unwrap_or_default() & inviter.permissions),
+        };
+
+        let mut invitations = Vec::new();
+
+        for user_id in form.user_ids {
+            if user_id.0 == DELETED_USER {
+                return Err(ApiError::NotFound);
+            }
+
+            User::get(user_id.clone(), &mut **transaction)
+                .await?
+                .ok_or(ApiError::NotFound)?;
+
+            if Self::get_from_user(user_id.clone(), project.id.clone(), &mut **transaction).await?.is_some() {
+                continue;
+            }
+
+            let member = ProjectMember {
+                id: ProjectMemberId::generate(&mut *transaction).await?,
+                project_id: project.id.clone(),
+                user_id,
+                permissions,
+                accepted: false,
+                role_id: role_id.clone(),
+            };
+
+            member.send_invitation(&project, &inviter_user.username, transaction).await?;
+
+            invitations.push(member);
+        }
+
+        Ok(invitations)
+    }
+
+    /// Inserts the pending member into the database and sends the user a
+    /// notification with actions to accept or deny the invitation.
+    ///
+    /// # Arguments
+    ///
+    /// * `project`: The `Project` the user is invited to.
+    /// * `inviter_username`: The username of the user sending the invitation.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the invitation was sent.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries.
+    ///
+    pub async fn send_invitation(
+        &self,
+        project: &Project,
+        inviter_username: &str,
         transaction: &mut sqlx::Transaction<'_, Database>,
     ) -> Result<(), sqlx::error::Error> {
-        for user_id in user_ids {
-            let member = ProjectMember {
-                id: ProjectMemberId::generate(&mut *transaction).await?,
-                project_id: project_id.clone(),
-                user_id,
-                permissions: Permissions::all(),
-                accepted: true,
-            };
+        self.insert(&mut *transaction).await?;
 
-            // TODO: Send notification
-
-            member.insert(&mut *transaction).await?;
-        }
+        let builder = NotificationBuilder {
+            body: format!(
+                "{} invited you to join {}.",
+                inviter_username,
+                project.name
+            ),
+            actions: vec![
+                NotificationActionBuilder {
+                    title: "Accept".to_string(),
+                    action_endpoint: invitation_endpoint(&project.id, "accept"),
+                },
+                NotificationActionBuilder {
+                    title: "Deny".to_string(),
+                    action_endpoint: invitation_endpoint(&project.id, "deny"),
+                },
+            ],
+        };
 
-        Ok(())
+        Notification::send(builder, self.user_id.clone(), transaction).await
     }
 
-    /// Accepts the invitation to the project by setting the "accepted" field to true in the database.
+    /// Accepts the invitation to the project by setting the "accepted" field to true in the database,
+    /// and removes the notification the invitation was sent with.
     ///
     /// # Arguments
     ///
@@ -529,11 +1570,12 @@ impl ProjectMember {
         .execute(&mut **transaction)
         .await?;
 
-        Ok(())
+        self.remove_invitation_notification(transaction).await
     }
 
     /// Denies the invitation to the project by removing the project member from the database
-    /// if the member's "accepted" field is false (not accepted).
+    /// if the member's "accepted" field is false (not accepted), and removes the notification
+    /// the invitation was sent with.
     ///
     /// # Arguments
     ///
@@ -560,6 +1602,303 @@ impl ProjectMember {
         .execute(&mut **transaction)
         .await?;
 
+        self.remove_invitation_notification(transaction).await
+    }
+
+    /// Removes the notification the invitation was sent with, once it has
+    /// been answered its actions no longer do anything
+    ///
+    async fn remove_invitation_notification(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        Notification::remove_with_action(
+            self.user_id.clone(),
+            &invitation_endpoint(&self.project_id, "accept"),
+            transaction
+        ).await
+    }
+
+    /// Changes the member's permissions on behalf of another member of the project,
+    /// overriding their role if they have one. The editor can only give or take away
+    /// permissions they hold themselves, and the project's owner always keeps every
+    /// permission. The change is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `permissions`: The member's new `Permissions`.
+    /// * `editor`: The `ProjectMember` making the change.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the permissions were changed.
+    /// - An `Err(ApiError::Forbidden)` is returned if the editor cannot both invite and remove members, changes permissions
+    ///   they do not hold or tries to change the owner's permissions.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn set_permissions(
+        &mut self,
+        permissions: Permissions,
+        editor: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        let previous = self.permissions;
+
+        self.change_permissions(permissions, None, editor, transaction).await?;
+
+        let username = self.username(transaction).await?;
+
+        Audit::create(
+            editor,
+            format!(
+                "Changed the permissions of {} from {} to {}",
+                username,
+                previous.names(),
+                permissions.names()
+            ),
+            transaction
+        ).await?;
+
+        Ok(())
+    }
+
+    /// Gives the member a role on behalf of another member of the project, the
+    /// member is given the role's permissions and any permissions set by hand
+    /// are dropped. The same restrictions as setting permissions by hand apply.
+    /// The change is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `role`: The member's new `ProjectRole`.
+    /// * `editor`: The `ProjectMember` making the change.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the role was given.
+    /// - An `Err(ApiError::Forbidden)` is returned if the editor cannot both invite and remove members, changes permissions
+    ///   they do not hold or tries to change the owner's role.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn set_role(
+        &mut self,
+        role: &ProjectRole,
+        editor: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        let previous = self.permissions;
+
+        self.change_permissions(role.permissions, Some(role), editor, transaction).await?;
+
+        let username = self.username(transaction).await?;
+
+        Audit::create(
+            editor,
+            format!(
+                "Gave {} the {} role, changing their permissions from {} to {}",
+                username,
+                role.name,
+                previous.names(),
+                role.permissions.names()
+            ),
+            transaction
+        ).await?;
+
+        Ok(())
+    }
+
+    /// Checks the editor is allowed to change the member's permissions and
+    /// stores the new permissions and role
+    ///
+    async fn change_permissions(
+        &mut self,
+        permissions: Permissions,
+        role: Option<&ProjectRole>,
+        editor: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        // Changing what a member can do is managing them, so it takes
+        // more than being able to invite new members
+        editor.check_permissions(Permissions::INVITE_MEMBERS | Permissions::REMOVE_MEMBERS)?;
+
+        // Every permission given or taken away has to be one
+        // the editor holds
+        editor.check_permissions(self.permissions.symmetric_difference(permissions))?;
+
+        let project = Project::get(self.project_id.clone(), &mut **transaction)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        if project.owner.0 == self.user_id.0 {
+            return Err(ApiError::Forbidden);
+        }
+
+        let bits = permissions.bits() as i64;
+        let role_id = role.map(|role| role.id.clone());
+
+        sqlx::query!(
+            "
+            UPDATE project_members
+            SET permissions = $1,
+                role_id = $2
+            WHERE id = $3
+            ",
+            bits,
+            role_id,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        self.permissions = permissions;
+        self.role_id = role_id;
+
+        Ok(())
+    }
+
+    /// The member's username as written in audit entries, falling back to
+    /// their user id
+    ///
+    pub async fn username(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<String, sqlx::error::Error> {
+        Ok(
+            User::get(self.user_id.clone(), &mut **transaction)
+                .await?
+                .map(|user| user.username)
+                .unwrap_or_else(|| self.user_id.0.clone())
+        )
+    }
+
+    /// Removes the member from the project on behalf of another member, who needs
+    /// permission to remove members and, to delete the removed member's tasks,
+    /// permission to delete tasks. Tasks which are kept are moved to the deleted
+    /// user. The removal is audited and the removed user is notified.
+    ///
+    /// # Arguments
+    ///
+    /// * `remover`: The `ProjectMember` removing the member.
+    /// * `delete_tasks`: Whether the tasks the member created should be deleted rather than kept.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the member was removed.
+    /// - An `Err(ApiError::BadRequest)` is returned if the remover is trying to remove themselves.
+    /// - An `Err(ApiError::Forbidden)` is returned if the remover is missing a permission or the member owns the project.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn remove(
+        &self,
+        remover: &ProjectMember,
+        delete_tasks: bool,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        remover.check_permissions(Permissions::REMOVE_MEMBERS)?;
+
+        if delete_tasks {
+            remover.check_permissions(Permissions::DELETE_TASKS)?;
+        }
+
+        if remover.id.0 == self.id.0 {
+            return Err(ApiError::BadRequest);
+        }
+
+        let project = Project::get(self.project_id.clone(), &mut **transaction)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+        if project.owner.0 == self.user_id.0 {
+            return Err(ApiError::Forbidden);
+        }
+
+        if delete_tasks {
+            for task in Task::get_many_from_member(self.id.clone(), &mut **transaction).await? {
+                task.remove(transaction).await?;
+            }
+        }
+
+        self.hand_over_to_deleted(transaction).await?;
+        self.leave(transaction).await?;
+
+        if !self.accepted {
+            self.remove_invitation_notification(transaction).await?;
+        }
+
+        let username = self.username(transaction).await?;
+        let remover_username = remover.username(transaction).await?;
+
+        Audit::create(
+            remover,
+            match delete_tasks {
+                true => format!("Removed {} from the project and deleted their tasks", username),
+                false => format!("Removed {} from the project", username),
+            },
+            transaction
+        ).await?;
+
+        let builder = NotificationBuilder {
+            body: format!("{} removed you from {}.", remover_username, project.name),
+            actions: Vec::new(),
+        };
+
+        Notification::send(builder, self.user_id.clone(), transaction).await?;
+
+        Ok(())
+    }
+
+    /// Moves the tasks the member created and the audit entries of actions they
+    /// took to the project's deleted user membership, so they are kept once the
+    /// member leaves.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the tasks and audit entries were moved.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries.
+    ///
+    pub async fn hand_over_to_deleted(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        let deleted_member = Self::get_or_create_deleted(
+            self.project_id.clone(),
+            transaction
+        ).await?;
+
+        sqlx::query!(
+            "
+            UPDATE audit_log
+            SET auditor = $1
+            WHERE auditor = $2
+            ",
+            deleted_member.id,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            UPDATE tasks
+            SET creator = $1
+            WHERE creator = $2
+            ",
+            deleted_member.id,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
         Ok(())
     }
 
@@ -591,6 +1930,22 @@ impl ProjectMember {
         )
         .execute(&mut **transaction)
         .await?;
+        // Revoke the invite links the member created
+        sqlx::query!(
+            "
+            DELETE FROM project_invite_links
+            WHERE creator = $1
+            ",
+            self.id,
+        )
+        .execute(&mut **transaction)
+        .await?;
+        // Withdraw any transfer of the project to the member
+        if let Some(transfer) = OwnershipTransfer::get_from_project(self.project_id.clone(), &mut **transaction).await? {
+            if transfer.nominee.0 == self.id.0 {
+                transfer.remove(self, transaction).await?;
+            }
+        }
         // Remove the membership itself
         sqlx::query!(
             "
@@ -605,6 +1960,104 @@ impl ProjectMember {
         Ok(())
     }
 
+    /// Retrieves the membership that audit entries of deleted users are moved to
+    /// for the given project, creating it if the project has none yet. The
+    /// membership has no permissions and is never accepted so it cannot be
+    /// used to access the project.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_id`: The `ProjectId` of the project.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, sqlx::error::Error>`, where:
+    /// - `Ok(member)` is returned with the project's deleted user membership.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries.
+    ///
+    pub async fn get_or_create_deleted(
+        project_id: ProjectId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, sqlx::error::Error> {
+        let deleted_user = UserId(DELETED_USER.into());
+
+        if let Some(member) = Self::get_from_user(deleted_user.clone(), project_id.clone(), &mut **transaction).await? {
+            return Ok(member);
+        }
+
+        let member = ProjectMember {
+            id: ProjectMemberId::generate(&mut *transaction).await?,
+            project_id,
+            user_id: deleted_user,
+            permissions: Permissions::empty(),
+            accepted: false,
+            role_id: None,
+        };
+
+        member.insert(&mut *transaction).await?;
+
+        Ok(member)
+    }
+
+    /// Resolves the effective permissions of a user in the project. Members
+    /// are given their own permissions along with those of visitors, and
+    /// users who are not members, or are not logged in, are given a visitor
+    /// membership if the project is public. Visitor memberships only exist
+    /// for the request, they are never accepted and have an empty id.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user, or `None` if they are not logged in.
+    /// * `project`: The `Project` to resolve the permissions in.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(member))` is returned with the user's membership, or a visitor membership.
+    /// - `Ok(None)` is returned if the user is not a member and the project is not public.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn resolve<'a, E>(
+        user_id: Option<UserId>,
+        project: &Project,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        let visitor_permissions = project.visitor_permissions();
+
+        let member = match user_id.clone() {
+            Some(user_id) => Self::get_accepted_from_user(user_id, project.id.clone(), executor).await?,
+            None => None,
+        };
+
+        if let Some(mut member) = member {
+            member.permissions |= visitor_permissions;
+
+            if project.archived {
+                member.permissions &= ARCHIVED_PERMISSIONS;
+            }
+
+            return Ok(Some(member));
+        }
+
+        if visitor_permissions.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(ProjectMember {
+            id: ProjectMemberId(String::new()),
+            project_id: project.id.clone(),
+            user_id: user_id.unwrap_or_else(|| UserId(String::new())),
+            permissions: visitor_permissions,
+            accepted: false,
+            role_id: None,
+        }))
+    }
+
     pub fn check_permissions(
         &self, 
         permissions: Permissions
@@ -636,17 +2089,18 @@ impl ProjectMember {
             "
             INSERT INTO project_members (
                 id, project_id, user_id,
-                permissions, accepted
+                permissions, accepted, role_id
             )
             VALUES (
-                $1, $2, $3, $4, $5
+                $1, $2, $3, $4, $5, $6
             )
             ",
             self.id,
             self.project_id,
             self.user_id,
             permssions,
-            self.accepted
+            self.accepted,
+            self.role_id
         )
         .execute(&mut **transaction)
         .await?;
@@ -679,7 +2133,8 @@ impl ProjectMember {
             ProjectMember,
             "
             SELECT id, project_id, user_id,
-                   permissions, accepted
+                   permissions, accepted,
+                   role_id AS \"role_id: ProjectRoleId\"
             FROM project_members
             WHERE id = $1
             ",
@@ -717,7 +2172,8 @@ impl ProjectMember {
             ProjectMember,
             "
             SELECT id, project_id, user_id,
-                   permissions, accepted
+                   permissions, accepted,
+                   role_id AS \"role_id: ProjectRoleId\"
             FROM project_members
             WHERE user_id = $1
             AND project_id = $2
@@ -741,7 +2197,8 @@ impl ProjectMember {
             ProjectMember,
             "
             SELECT id, project_id, user_id,
-                   permissions, accepted
+                   permissions, accepted,
+                   role_id AS \"role_id: ProjectRoleId\"
             FROM project_members
             WHERE user_id = $1
             AND project_id = $2
@@ -754,57 +2211,101 @@ impl ProjectMember {
         .await
     }
 
-    /// Retrieves a list of `ProjectMember` instances from the `project_members` table
-    /// based on the specified column and value.
+    /// Retrieves every membership of the given user, including invitations
+    /// they have not accepted yet.
     ///
     /// # Arguments
     ///
-    /// * `column`: The name of the column to use in the WHERE clause of the query.
-    /// * `value`: The value to use for filtering the results in the specified column.
+    /// * `user_id`: The `UserId` of the user.
     /// * `executor`: A type implementing `sqlx::Executor` that represents the database connection.
     ///
     /// # Returns
     ///
     /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
-    /// - `Ok(members)` is returned with the list of `ProjectMember` instances if found in the database.
-    /// - An empty vector is returned if no `ProjectMember` with the specified column and value is found in the database.
+    /// - `Ok(members)` is returned with the user's memberships.
     /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
     ///
-    pub async fn get_many<'a, E>(
-        column: &str,
-        value: String,
+    pub async fn get_many_from_user<'a, E>(
+        user_id: UserId,
         executor: E,
     ) -> Result<Vec<Self>, sqlx::error::Error>
-    where 
-        E: sqlx::Executor<'a, Database = Database>,
+    where
+        E: sqlx::Executor<'a, Database = Database>
     {
-        let results = sqlx::query_as!(
+        sqlx::query_as!(
             ProjectMember,
             "
             SELECT id, project_id, user_id,
-                   permissions, accepted
+                   permissions, accepted,
+                   role_id AS \"role_id: ProjectRoleId\"
             FROM project_members
-            WHERE $1 = $2
+            WHERE user_id = $1
             ",
-            column,
-            value
+            user_id
         )
         .fetch_all(executor)
-        .await?;
-
-        Ok(results)
+        .await
     }
 
-    pub async fn get_many_from_user<'a, E>(
+    /// Retrieves the memberships of the given user which belong in their list
+    /// of projects, including invitations they have not accepted yet. Projects
+    /// in the trash are always left out, archived projects only when asked.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user.
+    /// * `include_archived`: Whether to include memberships of archived projects.
+    /// * `executor`: A type implementing `sqlx::Executor` that represents the database connection.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(members)` is returned with the user's memberships.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_many_listed_from_user<'a, E>(
         user_id: UserId,
+        include_archived: bool,
         executor: E,
     ) -> Result<Vec<Self>, sqlx::error::Error>
     where
         E: sqlx::Executor<'a, Database = Database>
     {
-        Self::get_many("user_id", user_id.0, executor).await
+        sqlx::query_as!(
+            ProjectMember,
+            "
+            SELECT project_members.id, project_members.project_id,
+                   project_members.user_id, project_members.permissions,
+                   project_members.accepted,
+                   project_members.role_id AS \"role_id: ProjectRoleId\"
+            FROM project_members
+            JOIN projects ON projects.id = project_members.project_id
+            WHERE project_members.user_id = $1
+                AND projects.trashed IS NULL
+                AND ($2 OR NOT projects.archived)
+            ",
+            user_id,
+            include_archived
+        )
+        .fetch_all(executor)
+        .await
     }
 
+    /// Retrieves every member of the given project, including users who have
+    /// been invited but not accepted yet. The membership standing in for deleted
+    /// users is left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_id`: The `ProjectId` of the project.
+    /// * `executor`: A type implementing `sqlx::Executor` that represents the database connection.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(members)` is returned with the project's members.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
     pub async fn get_many_from_project<'a, E>(
         project_id: ProjectId,
         executor: E,
@@ -812,7 +2313,21 @@ impl ProjectMember {
     where
         E: sqlx::Executor<'a, Database = Database>
     {
-        Self::get_many("project_id", project_id.0, executor).await
+        sqlx::query_as!(
+            ProjectMember,
+            "
+            SELECT id, project_id, user_id,
+                   permissions, accepted,
+                   role_id AS \"role_id: ProjectRoleId\"
+            FROM project_members
+            WHERE project_id = $1
+            AND user_id != $2
+            ",
+            project_id,
+            DELETED_USER
+        )
+        .fetch_all(executor)
+        .await
     }
 
     pub async fn get_project<'a, E>(
@@ -0,0 +1,563 @@
+use std::collections::HashMap;
+
+use utoipa::ToSchema;
+
+use crate::database::Database;
+use crate::error::ApiError;
+
+use super::audits::Audit;
+use super::id::{OrganisationId, ProjectId, ProjectRoleId};
+use super::organisations::OrganisationRole;
+use super::projects::{Permissions, ProjectMember};
+
+#[derive(Serialize, ToSchema, Clone)]
+pub struct ProjectRole {
+    /// The role's id
+    ///
+    #[schema(example="123456789a", min_length=10, max_length=10)]
+    pub id: ProjectRoleId,
+    /// The id of the project the role belongs to
+    ///
+    #[schema(example="12345678", min_length=8, max_length=8)]
+    pub project_id: ProjectId,
+    /// The role's name (1 -> 30 charachters)
+    ///
+    #[schema(example="Contributor", min_length=1, max_length=30)]
+    pub name: String,
+    /// The permissions of every member with the role
+    ///
+    #[schema(value_type = u64, example=63)]
+    pub permissions: Permissions,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RoleBuilder {
+    /// The role's name (1 -> 30 charachters)
+    ///
+    #[schema(example="Contributor", min_length=1, max_length=30)]
+    pub name: String,
+    /// The role's permissions, which cannot include any the
+    /// creator does not hold
+    ///
+    #[schema(value_type = u64, example=63)]
+    pub permissions: Permissions,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EditRole {
+    /// The role's new name (1 -> 30 charachters)
+    ///
+    #[schema(example="Contributor", min_length=1, max_length=30)]
+    pub name: Option<String>,
+    /// The role's new permissions, which are given to every
+    /// member with the role
+    ///
+    #[schema(value_type = Option<u64>, example=63)]
+    pub permissions: Option<Permissions>,
+}
+
+/// The roles every new project starts with
+///
+fn default_roles() -> [(&'static str, Permissions); 3] {
+    [
+        ("Viewer", Permissions::READ_PROJECT),
+        ("Contributor", Permissions::default()),
+        (
+            "Maintainer",
+            Permissions::default()
+            | Permissions::UPLOAD_FILES | Permissions::REMOVE_FILES
+            | Permissions::INVITE_MEMBERS | Permissions::REMOVE_MEMBERS
+            | Permissions::EDIT_PROJECT
+        ),
+    ]
+}
+
+impl ProjectRole {
+    /// Gives a new project the default Viewer, Contributor and Maintainer roles.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_id`: The `ProjectId` of the new project.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(roles)` is returned with the created roles.
+    /// - An `sqlx::error::Error` is returned if there is an error generating IDs or executing the database queries.
+    ///
+    pub async fn create_defaults(
+        project_id: ProjectId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Vec<Self>, sqlx::error::Error> {
+        let mut roles = Vec::new();
+
+        for (name, permissions) in default_roles() {
+            let role = Self {
+                id: ProjectRoleId::generate(&mut *transaction).await?,
+                project_id: project_id.clone(),
+                name: name.to_string(),
+                permissions,
+            };
+
+            role.insert(&mut *transaction).await?;
+
+            roles.push(role);
+        }
+
+        Ok(roles)
+    }
+
+    /// Replaces the roles of the target project with copies of the source
+    /// project's roles, used when cloning a project along with its members.
+    /// The target's roles must not have any members yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `source`: The `ProjectId` of the project to copy the roles from.
+    /// * `target`: The `ProjectId` of the project to copy the roles to.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<HashMap<String, ProjectRoleId>, sqlx::error::Error>`, where:
+    /// - `Ok(roles)` is returned mapping the id of each source role to the id of its copy.
+    /// - An `sqlx::error::Error` is returned if there is an error generating IDs or executing the database queries.
+    ///
+    pub async fn copy_from_project(
+        source: ProjectId,
+        target: ProjectId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<HashMap<String, ProjectRoleId>, sqlx::error::Error> {
+        sqlx::query!(
+            "
+            DELETE FROM project_roles
+            WHERE project_id = $1
+            ",
+            target
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let mut copies = HashMap::new();
+
+        for role in Self::get_many_from_project(source, &mut **transaction).await? {
+            let copy = Self {
+                id: ProjectRoleId::generate(&mut *transaction).await?,
+                project_id: target.clone(),
+                name: role.name,
+                permissions: role.permissions,
+            };
+
+            copy.insert(&mut *transaction).await?;
+
+            copies.insert(role.id.0, copy.id);
+        }
+
+        Ok(copies)
+    }
+
+    /// Gives a new project in an organisation a copy of each of the
+    /// organisation's default roles.
+    ///
+    /// # Arguments
+    ///
+    /// * `organisation_id`: The `OrganisationId` of the project's organisation.
+    /// * `project_id`: The `ProjectId` of the new project.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(roles)` is returned with the created roles, which is empty if the organisation has none.
+    /// - An `sqlx::error::Error` is returned if there is an error generating IDs or executing the database queries.
+    ///
+    pub async fn copy_from_organisation(
+        organisation_id: OrganisationId,
+        project_id: ProjectId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Vec<Self>, sqlx::error::Error> {
+        let mut roles = Vec::new();
+
+        for organisation_role in OrganisationRole::get_many_from_organisation(organisation_id, &mut **transaction).await? {
+            let role = Self {
+                id: ProjectRoleId::generate(&mut *transaction).await?,
+                project_id: project_id.clone(),
+                name: organisation_role.name,
+                permissions: organisation_role.permissions,
+            };
+
+            role.insert(&mut *transaction).await?;
+
+            roles.push(role);
+        }
+
+        Ok(roles)
+    }
+
+    /// Creates a role in the creator's project. Members can only create roles
+    /// with permissions they hold themselves. The creation is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: A `RoleBuilder` with the role's name and permissions.
+    /// * `creator`: The `ProjectMember` creating the role.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, ApiError>`, where:
+    /// - `Ok(role)` is returned with the created `ProjectRole`.
+    /// - An `Err(ApiError::Forbidden)` is returned if the creator cannot manage members or gives permissions they do not hold.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if the name is taken or not 1 to 30 characters.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating the ID or executing the database queries.
+    ///
+    pub async fn create(
+        form: RoleBuilder,
+        creator: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, ApiError> {
+        creator.check_permissions(Permissions::INVITE_MEMBERS)?;
+        creator.check_permissions(form.permissions)?;
+
+        Self::check_name(&form.name, &creator.project_id, None, transaction).await?;
+
+        let role = Self {
+            id: ProjectRoleId::generate(&mut *transaction).await?,
+            project_id: creator.project_id.clone(),
+            name: form.name,
+            permissions: form.permissions,
+        };
+
+        role.insert(&mut *transaction).await?;
+
+        Audit::create(
+            creator,
+            format!("Created the {} role with {}", role.name, role.permissions.names()),
+            transaction
+        ).await?;
+
+        Ok(role)
+    }
+
+    /// Renames the role or changes its permissions, in which case every member
+    /// and invite link with the role is given the new permissions. The editor can only give or
+    /// take away permissions they hold themselves. The change is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: An `EditRole` with the fields to change, fields which are not given are left unchanged.
+    /// * `editor`: The `ProjectMember` making the change.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the role, and its members, were updated.
+    /// - An `Err(ApiError::Forbidden)` is returned if the editor cannot manage members or changes permissions they do not hold.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if the new name is taken or not 1 to 30 characters.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn edit(
+        &mut self,
+        form: EditRole,
+        editor: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        editor.check_permissions(Permissions::INVITE_MEMBERS)?;
+
+        let previous_name = self.name.clone();
+        let previous_permissions = self.permissions;
+
+        if let Some(permissions) = form.permissions {
+            // Every permission given or taken away has to be one
+            // the editor holds
+            editor.check_permissions(previous_permissions.symmetric_difference(permissions))?;
+
+            self.permissions = permissions;
+        }
+
+        if let Some(name) = form.name {
+            Self::check_name(&name, &self.project_id, Some(&self.id), transaction).await?;
+
+            self.name = name;
+        }
+
+        let bits = self.permissions.bits() as i64;
+
+        sqlx::query!(
+            "
+            UPDATE project_roles
+            SET name = $1,
+                permissions = $2
+            WHERE id = $3
+            ",
+            self.name,
+            bits,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            UPDATE project_members
+            SET permissions = $1
+            WHERE role_id = $2
+            ",
+            bits,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            UPDATE project_invite_links
+            SET permissions = $1
+            WHERE role_id = $2
+            ",
+            bits,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if previous_name != self.name {
+            Audit::create(
+                editor,
+                format!("Renamed the {} role to {}", previous_name, self.name),
+                transaction
+            ).await?;
+        }
+
+        if previous_permissions != self.permissions {
+            Audit::create(
+                editor,
+                format!(
+                    "Changed the permissions of the {} role from {} to {}",
+                    self.name,
+                    previous_permissions.names(),
+                    self.permissions.names()
+                ),
+                transaction
+            ).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the role from the project. Members and invite links with the
+    /// role keep its permissions, as if they had been set by hand. The removal
+    /// is audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `remover`: The `ProjectMember` removing the role.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the role was removed.
+    /// - An `Err(ApiError::Forbidden)` is returned if the remover cannot manage members.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn remove(
+        &self,
+        remover: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        remover.check_permissions(Permissions::INVITE_MEMBERS)?;
+
+        sqlx::query!(
+            "
+            UPDATE project_members
+            SET role_id = NULL
+            WHERE role_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            UPDATE project_invite_links
+            SET role_id = NULL
+            WHERE role_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM project_roles
+            WHERE id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Audit::create(
+            remover,
+            format!("Removed the {} role", self.name),
+            transaction
+        ).await?;
+
+        Ok(())
+    }
+
+    /// Checks the name is 1 to 30 characters and not used by another of the
+    /// project's roles
+    ///
+    async fn check_name(
+        name: &str,
+        project_id: &ProjectId,
+        role_id: Option<&ProjectRoleId>,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        if !(1..=30).contains(&name.trim().chars().count()) {
+            return Err(ApiError::unprocessable_entity([("name", "must be between 1 and 30 characters")]));
+        }
+
+        let role_id = role_id.map(|id| id.0.as_str());
+
+        let taken = sqlx::query_scalar!(
+            "
+            SELECT COUNT(*)
+            FROM project_roles
+            WHERE project_id = $1
+            AND name = $2
+            AND id IS NOT $3
+            ",
+            project_id,
+            name,
+            role_id
+        )
+        .fetch_one(&mut **transaction)
+        .await? > 0;
+
+        if taken {
+            return Err(ApiError::unprocessable_entity([("name", "already taken")]));
+        }
+
+        Ok(())
+    }
+}
+
+impl ProjectRole {
+    /// Inserts the role into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        let permissions = self.permissions.bits() as i64;
+
+        sqlx::query!(
+            "
+            INSERT INTO project_roles (
+                id, project_id, name, permissions
+            )
+            VALUES (
+                $1, $2, $3, $4
+            )
+            ",
+            self.id,
+            self.project_id,
+            self.name,
+            permissions
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the role with the given id, as long as it belongs to the
+    /// given project.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `ProjectRoleId` of the role.
+    /// * `project_id`: The `ProjectId` of the project the role must belong to.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(role))` is returned if the project has a role with the id.
+    /// - `Ok(None)` is returned if it does not.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get<'a, E>(
+        id: ProjectRoleId,
+        project_id: ProjectId,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            ProjectRole,
+            "
+            SELECT id, project_id, name, permissions
+            FROM project_roles
+            WHERE id = $1
+            AND project_id = $2
+            ",
+            id,
+            project_id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves every role of the given project, ordered by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_id`: The `ProjectId` of the project.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(roles)` is returned with the project's roles.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_many_from_project<'a, E>(
+        project_id: ProjectId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            ProjectRole,
+            "
+            SELECT id, project_id, name, permissions
+            FROM project_roles
+            WHERE project_id = $1
+            ORDER BY name
+            ",
+            project_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
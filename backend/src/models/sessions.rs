@@ -0,0 +1,467 @@
+use chrono::{NaiveDateTime, Utc, Duration};
+use utoipa::ToSchema;
+
+use crate::database::Database;
+use crate::utilities::hash_secret;
+use crate::error::ApiError;
+
+use super::id::{SessionId, UserId};
+use super::login_history::ClientInfo;
+
+/// How long a session can go without being refreshed before
+/// it expires and the user has to log in again (in days)
+const SESSION_LIFETIME: i64 = 30;
+
+#[derive(Serialize, ToSchema)]
+pub struct Session {
+    /// The session's id
+    ///
+    #[schema(example="123456789abcdefg", min_length=16, max_length=16)]
+    pub id: SessionId,
+    /// The id of the user the session belongs to
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub user_id: UserId,
+    /// A hash of the session's current refresh token
+    ///
+    #[serde(skip_serializing)]
+    pub refresh_token: String,
+    /// The datetime the session was created (on login)
+    ///
+    pub created: NaiveDateTime,
+    /// The datetime the session's refresh token was last rotated
+    ///
+    pub last_refreshed: NaiveDateTime,
+    /// The datetime the session will expire unless it is refreshed
+    ///
+    pub expires: NaiveDateTime,
+    /// Whether the session has been revoked, either by logging out
+    /// or by logging out of every session
+    ///
+    #[schema(example=false)]
+    pub revoked: bool,
+    /// The ip address the session was started from
+    ///
+    #[schema(example="127.0.0.1")]
+    pub ip_address: Option<String>,
+    /// The user agent of the client the session was started with
+    ///
+    #[schema(example="Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/119.0")]
+    pub user_agent: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ActiveSession {
+    /// The session's details
+    ///
+    #[serde(flatten)]
+    pub session: Session,
+    /// Whether this is the session the request was made with
+    ///
+    #[schema(example=true)]
+    pub current: bool,
+}
+
+impl Session {
+    /// Creates a new session for the given user and inserts it into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user the session is being created for.
+    /// * `client`: The `ClientInfo` of the client the user logged in with.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(Self, String), sqlx::error::Error>`, where:
+    /// - `Ok((session, refresh_token))` is returned with the new `Session` and the plain text refresh
+    ///   token, which is only ever available at this point as only its hash is stored.
+    /// - An `sqlx::error::Error` is returned if there is an error generating the id or executing the query.
+    ///
+    pub async fn create(
+        user_id: UserId,
+        client: &ClientInfo,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(Self, String), sqlx::error::Error> {
+        let id = SessionId::generate(&mut *transaction).await?;
+        let refresh_token = Self::generate_refresh_token();
+        let now = Utc::now().naive_utc();
+
+        let session = Self {
+            id,
+            user_id,
+            refresh_token: hash_secret(&refresh_token),
+            created: now,
+            last_refreshed: now,
+            expires: now + Duration::days(SESSION_LIFETIME),
+            revoked: false,
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+        };
+
+        session.insert(&mut *transaction).await?;
+
+        Ok((session, refresh_token))
+    }
+
+    /// Exchanges a refresh token for a new one, extending the lifetime of the session
+    /// it belongs to. The old refresh token can no longer be used after this, if it
+    /// is presented again the session is revoked as the token has been replayed, by
+    /// whoever stole it or by its owner after the thief refreshed first.
+    ///
+    /// # Arguments
+    ///
+    /// * `refresh_token`: The plain text refresh token provided by the client.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<(Self, String)>, ApiError>`, where:
+    /// - `Ok(Some((session, refresh_token)))` is returned with the updated `Session` and its new refresh token.
+    /// - `Ok(None)` is returned if the token had already been exchanged and the session was revoked, the
+    ///   transaction has to be committed for the revocation to take effect.
+    /// - An `Err(ApiError::Unauthorized)` is returned if the token does not belong to an active session.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn refresh(
+        refresh_token: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Option<(Self, String)>, ApiError> {
+        let previous_hash = hash_secret(refresh_token);
+
+        let Some(mut session) = Self::get_by_refresh_token(refresh_token, &mut **transaction).await? else {
+            let revoked = Self::revoke_from_previous_token(&previous_hash, transaction).await?;
+
+            return match revoked {
+                true => Ok(None),
+                false => Err(ApiError::Unauthorized),
+            };
+        };
+
+        if !session.is_active() {
+            return Err(ApiError::Unauthorized);
+        }
+
+        let refresh_token = Self::generate_refresh_token();
+        let now = Utc::now().naive_utc();
+
+        session.refresh_token = hash_secret(&refresh_token);
+        session.last_refreshed = now;
+        session.expires = now + Duration::days(SESSION_LIFETIME);
+
+        // Only the first of several refreshes with the same token
+        // can match it, the others are treated as replays
+        let result = sqlx::query!(
+            "
+            UPDATE sessions
+            SET refresh_token = $1,
+                previous_refresh_token = $2,
+                last_refreshed = $3,
+                expires = $4
+            WHERE id = $5
+            AND refresh_token = $2
+            ",
+            session.refresh_token,
+            previous_hash,
+            session.last_refreshed,
+            session.expires,
+            session.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        if result.rows_affected() != 1 {
+            Self::revoke_from_previous_token(&previous_hash, transaction).await?;
+
+            return Ok(None);
+        }
+
+        Ok(Some((session, refresh_token)))
+    }
+
+    /// Revokes the session whose previous refresh token has the given hash
+    ///
+    async fn revoke_from_previous_token(
+        hash: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<bool, sqlx::error::Error> {
+        let result = sqlx::query!(
+            "
+            UPDATE sessions
+            SET revoked = true
+            WHERE previous_refresh_token = $1
+            ",
+            hash
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revokes a single session so that neither its access tokens nor
+    /// its refresh token will be accepted anymore.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `SessionId` of the session to revoke.
+    /// * `user_id`: The `UserId` of the session's owner, sessions belonging to other users are left untouched.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<bool, sqlx::error::Error>`, where:
+    /// - `Ok(true)` is returned if a matching session was revoked.
+    /// - `Ok(false)` is returned if no matching session was found.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn revoke(
+        id: SessionId,
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<bool, sqlx::error::Error> {
+        let result = sqlx::query!(
+            "
+            UPDATE sessions
+            SET revoked = true
+            WHERE id = $1
+            AND user_id = $2
+            ",
+            id,
+            user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revokes every session belonging to the given user, logging them out
+    /// everywhere.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user whose sessions should be revoked.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the sessions were successfully revoked.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn revoke_all(
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            UPDATE sessions
+            SET revoked = true
+            WHERE user_id = $1
+            ",
+            user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Revokes every session belonging to the given user except for one, used
+    /// to log out other devices while staying logged in on the current one.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user whose sessions should be revoked.
+    /// * `keep`: The `SessionId` of the session to leave active.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the sessions were successfully revoked.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn revoke_others(
+        user_id: UserId,
+        keep: &SessionId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            UPDATE sessions
+            SET revoked = true
+            WHERE user_id = $1
+            AND id != $2
+            ",
+            user_id,
+            keep
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Whether the session can still be used, that is it has
+    /// neither been revoked nor expired
+    ///
+    pub fn is_active(&self) -> bool {
+        !self.revoked && self.expires > Utc::now().naive_utc()
+    }
+
+    fn generate_refresh_token() -> String {
+        nanoid::nanoid!(64)
+    }
+}
+
+impl Session {
+    /// Inserts the session into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO sessions (
+                id, user_id, refresh_token, created,
+                last_refreshed, expires, revoked,
+                ip_address, user_agent
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9
+            )
+            ",
+            self.id,
+            self.user_id,
+            self.refresh_token,
+            self.created,
+            self.last_refreshed,
+            self.expires,
+            self.revoked,
+            self.ip_address,
+            self.user_agent
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves a session from the database by its id.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `SessionId` of the session to retrieve.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(session))` is returned with the retrieved `Session` if it exists in the database.
+    /// - `Ok(None)` is returned if no session is found with the specified id.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get<'a, E>(
+        id: SessionId,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            Session,
+            "
+            SELECT id, user_id, refresh_token, created,
+                   last_refreshed, expires, revoked,
+                   ip_address, user_agent
+            FROM sessions
+            WHERE id = $1
+            ",
+            id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves the session that the given (plain text) refresh token
+    /// currently belongs to.
+    ///
+    pub async fn get_by_refresh_token<'a, E>(
+        refresh_token: &str,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        let hash = hash_secret(refresh_token);
+
+        sqlx::query_as!(
+            Session,
+            "
+            SELECT id, user_id, refresh_token, created,
+                   last_refreshed, expires, revoked,
+                   ip_address, user_agent
+            FROM sessions
+            WHERE refresh_token = $1
+            ",
+            hash
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves every session belonging to the given user which has neither
+    /// been revoked nor expired, most recently refreshed first.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(sessions)` is returned with the user's active sessions.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_active_from_user<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        let now = Utc::now().naive_utc();
+
+        sqlx::query_as!(
+            Session,
+            "
+            SELECT id, user_id, refresh_token, created,
+                   last_refreshed, expires, revoked,
+                   ip_address, user_agent
+            FROM sessions
+            WHERE user_id = $1
+            AND revoked = false
+            AND expires > $2
+            ORDER BY last_refreshed DESC
+            ",
+            user_id,
+            now
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
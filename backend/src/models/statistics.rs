@@ -0,0 +1,86 @@
+use chrono::Utc;
+use utoipa::ToSchema;
+
+use crate::database::Database;
+
+use super::users::DELETED_USER;
+
+/// Counts of what is stored on the instance, shown to administrators
+///
+#[derive(Serialize, ToSchema)]
+pub struct InstanceStatistics {
+    /// The number of users, excluding the deleted user placeholder
+    ///
+    #[schema(example=42)]
+    pub users: i64,
+    /// The number of users who are administrators
+    ///
+    #[schema(example=1)]
+    pub admins: i64,
+    /// The number of users who have been suspended
+    ///
+    #[schema(example=0)]
+    pub suspended_users: i64,
+    /// The number of users who have verified their email address
+    ///
+    #[schema(example=40)]
+    pub verified_users: i64,
+    /// The number of projects
+    ///
+    #[schema(example=12)]
+    pub projects: i64,
+    /// The number of tasks across every project
+    ///
+    #[schema(example=300)]
+    pub tasks: i64,
+    /// The number of sub-tasks across every project
+    ///
+    #[schema(example=800)]
+    pub sub_tasks: i64,
+    /// The number of sessions which have neither been revoked nor expired
+    ///
+    #[schema(example=25)]
+    pub active_sessions: i64,
+}
+
+impl InstanceStatistics {
+    /// Counts everything stored on the instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, sqlx::error::Error>`, where:
+    /// - `Ok(statistics)` is returned with the counts.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get<'a, E>(
+        executor: E,
+    ) -> Result<Self, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        let now = Utc::now().naive_utc();
+
+        sqlx::query_as!(
+            InstanceStatistics,
+            "
+            SELECT 
+                (SELECT COUNT(*) FROM users WHERE id != $1) AS \"users!: i64\",
+                (SELECT COUNT(*) FROM users WHERE is_admin = true) AS \"admins!: i64\",
+                (SELECT COUNT(*) FROM users WHERE suspended = true) AS \"suspended_users!: i64\",
+                (SELECT COUNT(*) FROM users WHERE verified = true AND id != $1) AS \"verified_users!: i64\",
+                (SELECT COUNT(*) FROM projects) AS \"projects!: i64\",
+                (SELECT COUNT(*) FROM tasks) AS \"tasks!: i64\",
+                (SELECT COUNT(*) FROM sub_tasks) AS \"sub_tasks!: i64\",
+                (SELECT COUNT(*) FROM sessions WHERE revoked = false AND expires > $2) AS \"active_sessions!: i64\"
+            ",
+            DELETED_USER,
+            now
+        )
+        .fetch_one(executor)
+        .await
+    }
+}
@@ -4,7 +4,7 @@ use utoipa::ToSchema;
 
 use crate::database::Database;
 
-use super::id::{TaskGroupId, ProjectId, TaskId, ProjectMemberId, SubTaskId};
+use super::id::{TaskGroupId, ProjectId, TaskId, ProjectMemberId, SubTaskId, UserId};
 
 #[derive(Serialize, ToSchema)]
 pub struct TaskGroup {
@@ -664,11 +664,12 @@ impl Task {
             INSERT INTO tasks (
                 id, project_id, task_group_id, 
                 name, information, creator, due, 
-                primary_colour, accent_colour
+                primary_colour, accent_colour,
+                position, created
             )
             VALUES (
                 $1, $2, $3, $4, $5, $6, $7, $8,
-                $9
+                $9, $10, $11
             )
             ",
             self.id,
@@ -679,7 +680,9 @@ impl Task {
             self.creator,
             self.due,
             self.primary_colour,
-            self.accent_colour
+            self.accent_colour,
+            self.position,
+            self.created
         )
         .execute(&mut **transaction)
         .await?;
@@ -878,6 +881,80 @@ impl Task {
     {
         Self::get_many_full("task_group_id", task_group_id.0, executor).await
     }
+
+    /// Retrieves every task created by the given user, across all of their memberships.
+    ///
+    /// # Parameters
+    ///
+    /// - `user_id`: The `UserId` of the user whose tasks will be retrieved.
+    /// - `executor`: An SQL executor used to execute the database query.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(tasks)`: A vector of tasks created by the user.
+    /// - `Err`: If an error occurs during the retrieval.
+    pub async fn get_many_from_creator<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>,
+    {
+        sqlx::query_as!(
+            Task,
+            "
+            SELECT id, project_id, task_group_id, 
+            name, information, creator, due, 
+            primary_colour, accent_colour, position,
+            created
+            FROM tasks
+            WHERE creator IN (
+                SELECT id
+                FROM project_members
+                WHERE user_id = $1
+            )
+            ",
+            user_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Retrieves every task created by the given membership, from the last
+    /// position to the first so they can be removed one after another.
+    ///
+    /// # Parameters
+    ///
+    /// - `member_id`: The `ProjectMemberId` of the membership whose tasks will be retrieved.
+    /// - `executor`: An SQL executor used to execute the database query.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(tasks)`: A vector of tasks created by the membership.
+    /// - `Err`: If an error occurs during the retrieval.
+    pub async fn get_many_from_member<'a, E>(
+        member_id: ProjectMemberId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>,
+    {
+        sqlx::query_as!(
+            Task,
+            "
+            SELECT id, project_id, task_group_id, 
+            name, information, creator, due, 
+            primary_colour, accent_colour, position,
+            created
+            FROM tasks
+            WHERE creator = $1
+            ORDER BY position DESC
+            ",
+            member_id
+        )
+        .fetch_all(executor)
+        .await
+    }
 }
 
 #[derive(Serialize, ToSchema)]
@@ -1106,24 +1183,27 @@ impl SubTask {
     ///
     /// - `Ok(())`: If the sub-task is successfully inserted into the database.
     /// - `Err`: If an error occurs during the insertion process.
-    async fn insert(
+    pub async fn insert(
         &self,
         transaction: &mut sqlx::Transaction<'_, Database>,
     ) -> Result<(), sqlx::error::Error> { 
         sqlx::query!(
             "
             INSERT INTO sub_tasks (
-                id, task_id, assignee, 
-                body, completed
+                id, task_id, project_id, assignee, 
+                body, weight, position, completed
             )
             VALUES (
-                $1, $2, $3, $4, $5
+                $1, $2, $3, $4, $5, $6, $7, $8
             )
             ",
             self.id,
             self.task_id,
+            self.project_id,
             self.assignee,
             self.body,
+            self.weight,
+            self.position,
             self.completed
         )
         .execute(&mut **transaction)
@@ -1275,4 +1355,43 @@ impl SubTask {
     {
         Self::get_many("task_id", task_id.0, executor).await
     }
+
+    /// Retrieves every sub-task assigned to the given user, across all of their memberships.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to retrieve assigned sub-tasks for.
+    /// * `executor` - An SQLx executor (e.g., a connection pool) to execute the query.
+    ///
+    /// # Returns
+    ///
+    /// This function returns `Result<Vec<SubTask>, sqlx::error::Error>`.
+    ///
+    /// - `Ok(sub_tasks)`: A vector of sub-tasks assigned to the user.
+    /// - `Err`: If an error occurs during the retrieval process.
+    pub async fn get_many_from_assignee<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>,
+    {
+        sqlx::query_as!(
+            SubTask,
+            "
+            SELECT id, task_id, project_id,
+            assignee, body, weight, position, 
+            completed
+            FROM sub_tasks
+            WHERE assignee IN (
+                SELECT id
+                FROM project_members
+                WHERE user_id = $1
+            )
+            ",
+            user_id
+        )
+        .fetch_all(executor)
+        .await
+    }
 }
\ No newline at end of file
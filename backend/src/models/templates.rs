@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::database::Database;
+use crate::error::ApiError;
+
+use super::id::{LabelId, ProjectId, ProjectMemberId, ProjectTemplateId, SubTaskId, TaskGroupId, TaskId, UserId};
+use super::projects::ProjectMember;
+use super::tasks::{SubTask, Task, TaskGroup};
+
+#[derive(Serialize, ToSchema)]
+pub struct ProjectTemplate {
+    /// The template's id
+    ///
+    #[schema(example="123456789abc", min_length=12, max_length=12)]
+    pub id: ProjectTemplateId,
+    /// The id of the user the template belongs to
+    ///
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub user_id: UserId,
+    /// The template's name (1 -> 30 charachters)
+    ///
+    #[schema(example="Client engagement", min_length=1, max_length=30)]
+    pub name: String,
+    /// The task groups, tasks, sub-tasks and labels projects
+    /// created from the template start with
+    ///
+    pub content: ProjectContent,
+    /// The datetime the template was saved
+    ///
+    pub created: NaiveDateTime,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TemplateBuilder {
+    /// The template's name (1 -> 30 charachters)
+    ///
+    #[schema(example="Client engagement", min_length=1, max_length=30)]
+    pub name: String,
+}
+
+/// Which parts of a project's structure to copy, each part
+/// needs the one it belongs to (labels and sub-tasks need
+/// tasks which need task groups)
+///
+#[derive(Clone, Copy)]
+pub struct ContentOptions {
+    pub task_groups: bool,
+    pub tasks: bool,
+    pub sub_tasks: bool,
+    pub labels: bool,
+}
+
+impl ContentOptions {
+    pub const ALL: Self = Self {
+        task_groups: true,
+        tasks: true,
+        sub_tasks: true,
+        labels: true,
+    };
+}
+
+/// The structure of a project, without any of its members, which
+/// can be copied into another project
+///
+#[derive(Serialize, Deserialize, ToSchema, Default)]
+pub struct ProjectContent {
+    pub task_groups: Vec<TaskGroupContent>,
+    /// The dependencies between the tasks
+    ///
+    pub task_edges: Vec<TaskEdgeContent>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TaskGroupContent {
+    #[schema(example="My Task Group")]
+    pub name: String,
+    #[schema(example=0)]
+    pub position: i64,
+    pub tasks: Vec<TaskContent>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TaskContent {
+    /// The id of the task the content was copied from, used to
+    /// link up the task edges
+    ///
+    #[schema(example="1234567890", min_length=10, max_length=10)]
+    pub id: TaskId,
+    #[schema(example="My task", max_length=90)]
+    pub name: String,
+    #[schema(example="Information about my task")]
+    pub information: Option<String>,
+    pub due: Option<NaiveDateTime>,
+    #[schema(example="#FFFFFF")]
+    pub primary_colour: String,
+    #[schema(example="#FFFFFF")]
+    pub accent_colour: String,
+    #[schema(example=0)]
+    pub position: i64,
+    pub sub_tasks: Vec<SubTaskContent>,
+    pub labels: Vec<LabelContent>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SubTaskContent {
+    /// The membership id of the member the sub-task was assigned
+    /// to in the project it was copied from
+    ///
+    #[schema(example="12345678", min_length=8, max_length=8)]
+    pub assignee: Option<String>,
+    #[schema(example="My Subtask")]
+    pub body: String,
+    #[schema(example=100)]
+    pub weight: Option<i64>,
+    #[schema(example=0)]
+    pub position: i64,
+    #[schema(example=false)]
+    pub completed: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct LabelContent {
+    #[schema(example="Urgent")]
+    pub body: String,
+    #[schema(example="#FF0000")]
+    pub colour: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TaskEdgeContent {
+    #[schema(example="1234567890", min_length=10, max_length=10)]
+    pub parent_task: TaskId,
+    #[schema(example="1234567890", min_length=10, max_length=10)]
+    pub child_task: TaskId,
+    #[schema(example="blocks")]
+    pub flow_type: String,
+}
+
+// The content is stored as JSON
+crate::json_column!(ProjectContent);
+
+impl ProjectContent {
+    /// Reads the structure of the given project.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_id`: The `ProjectId` of the project to read.
+    /// * `options`: The `ContentOptions` choosing which parts to read.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, sqlx::error::Error>`, where:
+    /// - `Ok(content)` is returned with the project's structure.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries.
+    ///
+    pub async fn read(
+        project_id: &ProjectId,
+        options: ContentOptions,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, sqlx::error::Error> {
+        if !options.task_groups {
+            return Ok(Self::default());
+        }
+
+        let task_groups = sqlx::query_as!(
+            TaskGroup,
+            "
+            SELECT id, project_id, name, position
+            FROM task_groups
+            WHERE project_id = $1
+            ORDER BY position
+            ",
+            project_id
+        )
+        .fetch_all(&mut **transaction)
+        .await?;
+
+        let tasks = match options.tasks {
+            true => sqlx::query_as!(
+                Task,
+                "
+                SELECT id, project_id, task_group_id,
+                name, information, creator, due,
+                primary_colour, accent_colour, position,
+                created
+                FROM tasks
+                WHERE project_id = $1
+                ORDER BY position
+                ",
+                project_id
+            )
+            .fetch_all(&mut **transaction)
+            .await?,
+            false => Vec::new(),
+        };
+
+        let sub_tasks = match options.tasks && options.sub_tasks {
+            true => sqlx::query_as!(
+                SubTask,
+                "
+                SELECT id, task_id, project_id, assignee,
+                body, weight, position, completed
+                FROM sub_tasks
+                WHERE project_id = $1
+                ORDER BY position
+                ",
+                project_id
+            )
+            .fetch_all(&mut **transaction)
+            .await?,
+            false => Vec::new(),
+        };
+
+        let labels = match options.tasks && options.labels {
+            true => sqlx::query!(
+                "
+                SELECT task_id, body, colour
+                FROM labels
+                WHERE project_id = $1
+                ",
+                project_id
+            )
+            .fetch_all(&mut **transaction)
+            .await?,
+            false => Vec::new(),
+        };
+
+        let task_edges = match options.tasks {
+            true => sqlx::query!(
+                "
+                SELECT parent_task, child_task, flow_type
+                FROM task_edges
+                WHERE project_id = $1
+                ",
+                project_id
+            )
+            .fetch_all(&mut **transaction)
+            .await?
+            .into_iter()
+            .map(|edge| TaskEdgeContent {
+                parent_task: TaskId(edge.parent_task),
+                child_task: TaskId(edge.child_task),
+                flow_type: edge.flow_type,
+            })
+            .collect(),
+            false => Vec::new(),
+        };
+
+        let mut task_sub_tasks: HashMap<String, Vec<SubTaskContent>> = HashMap::new();
+
+        for sub_task in sub_tasks {
+            task_sub_tasks.entry(sub_task.task_id.0).or_default().push(SubTaskContent {
+                assignee: sub_task.assignee,
+                body: sub_task.body,
+                weight: sub_task.weight,
+                position: sub_task.position,
+                completed: sub_task.completed,
+            });
+        }
+
+        let mut task_labels: HashMap<String, Vec<LabelContent>> = HashMap::new();
+
+        for label in labels {
+            task_labels.entry(label.task_id).or_default().push(LabelContent {
+                body: label.body,
+                colour: label.colour,
+            });
+        }
+
+        let mut group_tasks: HashMap<String, Vec<TaskContent>> = HashMap::new();
+
+        for task in tasks {
+            group_tasks.entry(task.task_group_id.0).or_default().push(TaskContent {
+                sub_tasks: task_sub_tasks.remove(&task.id.0).unwrap_or_default(),
+                labels: task_labels.remove(&task.id.0).unwrap_or_default(),
+                id: task.id,
+                name: task.name,
+                information: task.information,
+                due: task.due,
+                primary_colour: task.primary_colour,
+                accent_colour: task.accent_colour,
+                position: task.position,
+            });
+        }
+
+        let task_groups = task_groups
+            .into_iter()
+            .map(|group| TaskGroupContent {
+                tasks: group_tasks.remove(&group.id.0).unwrap_or_default(),
+                name: group.name,
+                position: group.position,
+            })
+            .collect();
+
+        Ok(Self { task_groups, task_edges })
+    }
+
+    /// Clears everything specific to the project the content was read
+    /// from, so due dates, completed sub-tasks and assignees aren't
+    /// carried into templates.
+    ///
+    pub fn into_template(mut self) -> Self {
+        for task in self.task_groups.iter_mut().flat_map(|group| group.tasks.iter_mut()) {
+            task.due = None;
+
+            for sub_task in &mut task.sub_tasks {
+                sub_task.assignee = None;
+                sub_task.completed = false;
+            }
+        }
+
+        self
+    }
+
+    /// Creates the content in the given project.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_id`: The `ProjectId` of the project to create the content in.
+    /// * `creator`: The `ProjectMemberId` of the member the tasks are created by.
+    /// * `assignees`: The memberships in the new project of the members sub-tasks were assigned to,
+    ///   keyed by their membership id in the original project. Sub-tasks assigned to anyone else are left unassigned.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the content was created.
+    /// - An `sqlx::error::Error` is returned if there is an error generating IDs or executing the database queries.
+    ///
+    pub async fn build(
+        self,
+        project_id: &ProjectId,
+        creator: &ProjectMemberId,
+        assignees: &HashMap<String, ProjectMemberId>,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        let created = Utc::now().naive_utc();
+
+        // The ids of the created tasks keyed by the ids they had in
+        // the original project
+        let mut task_ids = HashMap::new();
+
+        for group_content in self.task_groups {
+            let group = TaskGroup {
+                id: TaskGroupId::generate(&mut *transaction).await?,
+                project_id: project_id.clone(),
+                name: group_content.name,
+                position: group_content.position,
+            };
+
+            group.insert(&mut **transaction).await?;
+
+            for task_content in group_content.tasks {
+                let task = Task {
+                    id: TaskId::generate(&mut *transaction).await?,
+                    project_id: project_id.clone(),
+                    task_group_id: group.id.clone(),
+                    name: task_content.name,
+                    information: task_content.information,
+                    creator: creator.clone(),
+                    due: task_content.due,
+                    primary_colour: task_content.primary_colour,
+                    accent_colour: task_content.accent_colour,
+                    position: task_content.position,
+                    created,
+                };
+
+                task.insert(transaction).await?;
+
+                for sub_task_content in task_content.sub_tasks {
+                    let assignee = sub_task_content.assignee
+                        .and_then(|assignee| assignees.get(&assignee))
+                        .map(|member_id| member_id.0.clone());
+
+                    let sub_task = SubTask {
+                        id: SubTaskId::generate(&mut *transaction).await?,
+                        task_id: task.id.clone(),
+                        project_id: project_id.clone(),
+                        assignee,
+                        body: sub_task_content.body,
+                        weight: sub_task_content.weight,
+                        position: sub_task_content.position,
+                        completed: sub_task_content.completed,
+                    };
+
+                    sub_task.insert(transaction).await?;
+                }
+
+                for label in task_content.labels {
+                    let id = LabelId::generate(&mut *transaction).await?;
+
+                    sqlx::query!(
+                        "
+                        INSERT INTO labels (
+                            id, task_id, project_id, body, colour
+                        )
+                        VALUES (
+                            $1, $2, $3, $4, $5
+                        )
+                        ",
+                        id,
+                        task.id,
+                        project_id,
+                        label.body,
+                        label.colour
+                    )
+                    .execute(&mut **transaction)
+                    .await?;
+                }
+
+                task_ids.insert(task_content.id.0, task.id);
+            }
+        }
+
+        for edge in self.task_edges {
+            let (Some(parent_task), Some(child_task)) = (
+                task_ids.get(&edge.parent_task.0),
+                task_ids.get(&edge.child_task.0)
+            ) else {
+                continue;
+            };
+
+            sqlx::query!(
+                "
+                INSERT INTO task_edges (
+                    parent_task, child_task, project_id, flow_type
+                )
+                VALUES (
+                    $1, $2, $3, $4
+                )
+                ",
+                parent_task,
+                child_task,
+                project_id,
+                edge.flow_type
+            )
+            .execute(&mut **transaction)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ProjectTemplate {
+    /// Saves the structure of the member's project as a template belonging
+    /// to them. Due dates, assignees and completed sub-tasks are cleared.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: A `TemplateBuilder` with the template's name.
+    /// * `member`: The `ProjectMember` of the user saving the template.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, ApiError>`, where:
+    /// - `Ok(template)` is returned with the saved `ProjectTemplate`.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if the name is not 1 to 30 characters.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error generating the ID or executing the database queries.
+    ///
+    pub async fn create(
+        form: TemplateBuilder,
+        member: &ProjectMember,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, ApiError> {
+        if !(1..=30).contains(&form.name.trim().chars().count()) {
+            return Err(ApiError::unprocessable_entity([("name", "must be between 1 and 30 characters")]));
+        }
+
+        let content = ProjectContent::read(&member.project_id, ContentOptions::ALL, transaction)
+            .await?
+            .into_template();
+
+        let template = Self {
+            id: ProjectTemplateId::generate(&mut *transaction).await?,
+            user_id: member.user_id.clone(),
+            name: form.name.trim().to_string(),
+            content,
+            created: Utc::now().naive_utc(),
+        };
+
+        template.insert(transaction).await?;
+
+        Ok(template)
+    }
+
+    /// Removes the template, projects already created from it are
+    /// unaffected.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the template was removed.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn remove(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            DELETE FROM project_templates
+            WHERE id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl ProjectTemplate {
+    /// Inserts the template into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `Err(ApiError::Anyhow)` is returned if the content can't be serialized.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        let content = serde_json::to_string(&self.content).map_err(anyhow::Error::from)?;
+
+        sqlx::query!(
+            "
+            INSERT INTO project_templates (
+                id, user_id, name, content, created
+            )
+            VALUES (
+                $1, $2, $3, $4, $5
+            )
+            ",
+            self.id,
+            self.user_id,
+            self.name,
+            content,
+            self.created
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the template with the given id, as long as it belongs to
+    /// the given user.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `ProjectTemplateId` of the template.
+    /// * `user_id`: The `UserId` of the user the template must belong to.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(template))` is returned if the user has a template with the id.
+    /// - `Ok(None)` is returned if they do not.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get<'a, E>(
+        id: ProjectTemplateId,
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            ProjectTemplate,
+            r#"
+            SELECT id, user_id, name, content AS "content: ProjectContent", created
+            FROM project_templates
+            WHERE id = $1
+            AND user_id = $2
+            "#,
+            id,
+            user_id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves every template of the given user, ordered by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(templates)` is returned with the user's templates.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get_many_from_user<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            ProjectTemplate,
+            r#"
+            SELECT id, user_id, name, content AS "content: ProjectContent", created
+            FROM project_templates
+            WHERE user_id = $1
+            ORDER BY name
+            "#,
+            user_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
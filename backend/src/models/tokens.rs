@@ -1,13 +1,17 @@
 use chrono::Utc;
-use jsonwebtoken::{TokenData, DecodingKey, Validation, Header, EncodingKey, errors::Result};
+use jsonwebtoken::errors::{ErrorKind, Result};
+use jsonwebtoken::{TokenData, Validation, Header};
 use time::Duration;
+use tower_cookies::cookie::SameSite;
 use tower_cookies::{Cookie, Cookies};
 use utoipa::ToSchema;
 
+use crate::utilities::keyring::Keyring;
+
+use super::sessions::Session;
 use super::users::User;
 
-pub static KEY: [u8; 16] = *include_bytes!("../secret.key");
-static ONE_WEEK: i64 = 60 * 60 * 24 * 7; // in seconds
+static FIFTEEN_MINUTES: i64 = 60 * 15; // in seconds
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Token(pub String);
@@ -15,41 +19,60 @@ pub struct Token(pub String);
 #[derive(Serialize, Deserialize)]
 pub struct TokenClaims {
     /// The time the token was issues at (s)
-    /// 
+    ///
     pub iat: i64,
-    /// The time the token will expire (issue time + one week)
-    /// 
+    /// The time the token will expire (issue time + fifteen minutes)
+    ///
     pub exp: i64,
     /// The user's id
-    /// 
+    ///
     pub user_id: String,
+    /// The id of the session the token was issued for, the
+    /// token is rejected once this session is revoked
+    ///
+    pub session_id: String,
 }
 
 impl Token {
-    pub const TOKEN_KEY: &str = "token";
+    pub const TOKEN_KEY: &'static str = "token";
+
+    /// Verifies the token against the key named by its `kid` header
+    /// and returns its claims
+    ///
+    pub fn decode(&self, keyring: &Keyring) -> Result<TokenData<TokenClaims>> {
+        let header = jsonwebtoken::decode_header(&self.0)?;
+
+        let key = keyring.decoding_key(header.kid.as_deref())
+            .ok_or(ErrorKind::InvalidToken)?;
 
-    pub fn decode(&self) -> Result<TokenData<TokenClaims>> {
         jsonwebtoken::decode::<TokenClaims>(
             &self.0,
-            &DecodingKey::from_secret(&KEY),
+            &key,
             &Validation::default(),
         )
     }
 
-    pub fn encode(user: &User) -> Self {
-        let now = Utc::now().timestamp_nanos() / 1_000_000_000; // nanosecond -> second
+    /// Signs a new access token for the given session with the
+    /// keyring's current key
+    ///
+    pub fn encode(user: &User, session: &Session, keyring: &Keyring) -> Self {
+        let now = Utc::now().timestamp();
 
         let claims = TokenClaims {
             iat: now,
-            exp: now + ONE_WEEK,
+            exp: now + FIFTEEN_MINUTES,
             user_id: user.id.0.clone(),
+            session_id: session.id.0.clone(),
+        };
+
+        let (kid, key) = keyring.encoding_key();
+
+        let header = Header {
+            kid: Some(kid.to_string()),
+            ..Default::default()
         };
 
-        let token = jsonwebtoken::encode(
-            &Header::default(), 
-            &claims, 
-            &EncodingKey::from_secret(&KEY)
-        ).unwrap();
+        let token = jsonwebtoken::encode(&header, &claims, &key).unwrap();
 
         Self(token)
     }
@@ -58,11 +81,12 @@ impl Token {
         Cookie::build(Self::TOKEN_KEY, self.0)
             .secure(true)
             .http_only(true)
-            .max_age(Duration::days(7))
+            .path("/")
+            .max_age(Duration::minutes(15))
             .finish()
     }
 
-    pub fn from_jar(cookies: Cookies) -> Option<Self> {
+    pub fn from_jar(cookies: &Cookies) -> Option<Self> {
         cookies.get(Self::TOKEN_KEY)
             .map(|t| Self(t.value().to_string()))
     }
@@ -72,4 +96,75 @@ impl From<String> for Token {
     fn from(value: String) -> Self {
         Self(value)
     }
-}
\ No newline at end of file
+}
+
+/// An opaque token used to obtain a new access token once the
+/// current one expires. Refresh tokens are rotated on every use
+/// so each one can only be exchanged once.
+///
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RefreshToken(pub String);
+
+impl RefreshToken {
+    pub const TOKEN_KEY: &'static str = "refresh_token";
+
+    pub fn into_cookie<'c>(self) -> Cookie<'c> {
+        Cookie::build(Self::TOKEN_KEY, self.0)
+            .secure(true)
+            .http_only(true)
+            .path("/api/v1/users")
+            .max_age(Duration::days(30))
+            .finish()
+    }
+
+    pub fn from_jar(cookies: &Cookies) -> Option<Self> {
+        cookies.get(Self::TOKEN_KEY)
+            .map(|t| Self(t.value().to_string()))
+    }
+}
+
+impl From<String> for RefreshToken {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+/// The state of an OpenID Connect login started by this client,
+/// checked when the provider redirects back so a login started
+/// by someone else cannot be completed in this browser.
+///
+#[derive(Debug)]
+pub struct OidcState(pub String);
+
+impl OidcState {
+    pub const TOKEN_KEY: &'static str = "oidc_state";
+
+    pub fn into_cookie<'c>(self) -> Cookie<'c> {
+        // Lax so the cookie is still sent when the provider
+        // redirects back to the callback
+        Cookie::build(Self::TOKEN_KEY, self.0)
+            .secure(true)
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .path("/api/v1/users/oidc")
+            .max_age(Duration::minutes(10))
+            .finish()
+    }
+
+    pub fn from_jar(cookies: &Cookies) -> Option<Self> {
+        cookies.get(Self::TOKEN_KEY)
+            .map(|t| Self(t.value().to_string()))
+    }
+
+    pub fn clear(cookies: &Cookies) {
+        cookies.remove(Cookie::build(Self::TOKEN_KEY, "").path("/api/v1/users/oidc").finish());
+    }
+}
+
+/// Removes both the access and refresh token cookies from the
+/// client, used when logging out
+///
+pub fn clear_cookies(cookies: &Cookies) {
+    cookies.remove(Cookie::build(Token::TOKEN_KEY, "").path("/").finish());
+    cookies.remove(Cookie::build(RefreshToken::TOKEN_KEY, "").path("/api/v1/users").finish());
+}
@@ -0,0 +1,647 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use totp_rs::{Algorithm, Secret, TOTP};
+use utoipa::ToSchema;
+
+use crate::database::Database;
+use crate::error::ApiError;
+use crate::utilities::hash_secret;
+
+use super::id::{MfaChallengeId, UserId};
+
+/// The issuer shown by authenticator apps
+const ISSUER: &str = "Nine Yards";
+
+/// How long a user has to enter a code after logging in (in minutes)
+const CHALLENGE_LIFETIME: i64 = 5;
+
+/// The length of each time step (in seconds)
+const STEP: u64 = 30;
+
+/// How many recovery codes are generated at once
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// The alphabet recovery codes are made up of, which avoids characters
+/// that are easily confused when written down
+const RECOVERY_CODE_ALPHABET: [char; 32] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'j', 'k', 'm', 'n', 'p', 'q', 'r', 's',
+    't', 'u', 'v', 'w', 'x', 'y', 'z', '2', '3', '4', '5', '6', '7', '8', '9', '0',
+];
+
+/// A user's TOTP two-factor authentication settings
+///
+pub struct TwoFactor {
+    /// The id of the user the settings belong to
+    ///
+    pub user_id: UserId,
+    /// The base32 encoded secret, set once the user has started
+    /// enrolling
+    ///
+    pub secret: Option<String>,
+    /// Whether the user has confirmed enrolment, codes are only
+    /// required once this is set
+    ///
+    pub enabled: bool,
+    /// The last time step a code was accepted for
+    ///
+    pub last_step: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TwoFactorStatus {
+    /// Whether two-factor authentication is enabled
+    ///
+    #[schema(example=true)]
+    pub enabled: bool,
+    /// How many unused recovery codes the user has left
+    ///
+    #[schema(example=10)]
+    pub recovery_codes_remaining: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TotpEnrolment {
+    /// The base32 encoded secret, for users who cannot scan the
+    /// provisioning uri
+    ///
+    #[schema(example="JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP")]
+    pub secret: String,
+    /// An `otpauth://` uri to be shown as a QR code and scanned
+    /// with an authenticator app
+    ///
+    #[schema(example="otpauth://totp/Nine%20Yards:user%40example.com?secret=JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP&issuer=Nine%20Yards")]
+    pub provisioning_uri: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RecoveryCodes {
+    /// Single use codes which can be entered instead of a TOTP code,
+    /// these are only shown once
+    ///
+    #[schema(example=json!(["abcde-fghjk", "mnpqr-stuvw"]))]
+    pub codes: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TotpCode {
+    /// A code from the user's authenticator app, or a recovery code
+    ///
+    #[schema(example="123456")]
+    pub code: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TotpLogin {
+    /// The challenge returned when logging in
+    ///
+    #[schema(example="V1StGXR8Z5jdHi6BmyTq2xLJm7uT3pEwNd2Kq9sF")]
+    pub challenge: String,
+    /// A code from the user's authenticator app, or a recovery code
+    ///
+    #[schema(example="123456")]
+    pub code: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LoginChallenge {
+    /// A token to send along with a TOTP code to complete logging in
+    ///
+    #[schema(example="V1StGXR8Z5jdHi6BmyTq2xLJm7uT3pEwNd2Kq9sF")]
+    pub challenge: String,
+    /// The datetime after which the challenge can no longer be completed
+    ///
+    pub expires: NaiveDateTime,
+}
+
+impl TwoFactor {
+    /// Starts enrolling the user by generating a new secret. Codes are not
+    /// required until the enrolment is confirmed.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user enrolling.
+    /// * `account_name`: The name shown next to the code in authenticator apps.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<TotpEnrolment, ApiError>`, where:
+    /// - `Ok(enrolment)` is returned with the secret and its provisioning uri.
+    /// - An `Err(ApiError::BadRequest)` is returned if two-factor authentication is already enabled.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn enrol(
+        user_id: UserId,
+        account_name: String,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<TotpEnrolment, ApiError> {
+        if Self::get(user_id.clone(), &mut **transaction).await?.enabled {
+            return Err(ApiError::BadRequest);
+        }
+
+        let secret = Secret::generate_secret().to_encoded().to_string();
+
+        let totp = Self::totp(&secret, account_name)
+            .ok_or(ApiError::Internal("Failed to create TOTP from generated secret".into()))?;
+
+        sqlx::query!(
+            "
+            UPDATE users
+            SET totp_secret = $1,
+                totp_enabled = false,
+                totp_last_step = NULL
+            WHERE id = $2
+            ",
+            secret,
+            user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(TotpEnrolment {
+            secret,
+            provisioning_uri: totp.get_url(),
+        })
+    }
+
+    /// Confirms enrolment with a code from the user's authenticator app, enabling
+    /// two-factor authentication and generating a set of recovery codes.
+    ///
+    /// # Arguments
+    ///
+    /// * `code`: A code generated from the secret returned on enrolment.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<RecoveryCodes, ApiError>`, where:
+    /// - `Ok(codes)` is returned with the user's new recovery codes.
+    /// - An `Err(ApiError::BadRequest)` is returned if the user has not enrolled or is already enabled.
+    /// - An `Err(ApiError::Unauthorized)` is returned if the code is incorrect.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn confirm(
+        &self,
+        code: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<RecoveryCodes, ApiError> {
+        if self.enabled || self.secret.is_none() {
+            return Err(ApiError::BadRequest);
+        }
+
+        if !self.check_totp(code, transaction).await? {
+            return Err(ApiError::Unauthorized);
+        }
+
+        sqlx::query!(
+            "
+            UPDATE users
+            SET totp_enabled = true
+            WHERE id = $1
+            ",
+            self.user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(self.generate_recovery_codes(transaction).await?)
+    }
+
+    /// Disables two-factor authentication, removing the user's secret and
+    /// recovery codes.
+    ///
+    /// # Arguments
+    ///
+    /// * `code`: A TOTP or recovery code, to prove the user still has access.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if two-factor authentication was disabled.
+    /// - An `Err(ApiError::Unauthorized)` is returned if the code is incorrect.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn disable(
+        &self,
+        code: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        if !self.verify(code, transaction).await? {
+            return Err(ApiError::Unauthorized);
+        }
+
+        sqlx::query!(
+            "
+            UPDATE users
+            SET totp_secret = NULL,
+                totp_enabled = false,
+                totp_last_step = NULL
+            WHERE id = $1
+            ",
+            self.user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM recovery_codes
+            WHERE user_id = $1
+            ",
+            self.user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Checks a code entered by the user, either a TOTP code or one of their unused
+    /// recovery codes. Each code can only be used once.
+    ///
+    /// # Arguments
+    ///
+    /// * `code`: The code entered by the user.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<bool, sqlx::error::Error>`, where:
+    /// - `Ok(true)` is returned if two-factor authentication is enabled and the code is valid.
+    /// - `Ok(false)` is returned otherwise.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries.
+    ///
+    pub async fn verify(
+        &self,
+        code: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<bool, sqlx::error::Error> {
+        if !self.enabled {
+            return Ok(false);
+        }
+
+        if self.check_totp(code, transaction).await? {
+            return Ok(true);
+        }
+
+        let hash = hash_secret(&code.trim().to_lowercase());
+
+        let result = sqlx::query!(
+            "
+            UPDATE recovery_codes
+            SET used = true
+            WHERE user_id = $1
+            AND code = $2
+            AND used = false
+            ",
+            self.user_id,
+            hash
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Replaces the user's recovery codes with a new set, any unused codes
+    /// can no longer be used.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<RecoveryCodes, sqlx::error::Error>`, where:
+    /// - `Ok(codes)` is returned with the plain text recovery codes.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries.
+    ///
+    pub async fn generate_recovery_codes(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<RecoveryCodes, sqlx::error::Error> {
+        sqlx::query!(
+            "
+            DELETE FROM recovery_codes
+            WHERE user_id = $1
+            ",
+            self.user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        let mut codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+
+        for _ in 0..RECOVERY_CODE_COUNT {
+            let code = format!(
+                "{}-{}",
+                nanoid::nanoid!(5, &RECOVERY_CODE_ALPHABET),
+                nanoid::nanoid!(5, &RECOVERY_CODE_ALPHABET)
+            );
+
+            let hash = hash_secret(&code);
+
+            sqlx::query!(
+                "
+                INSERT INTO recovery_codes (
+                    user_id, code
+                )
+                VALUES (
+                    $1, $2
+                )
+                ",
+                self.user_id,
+                hash
+            )
+            .execute(&mut **transaction)
+            .await?;
+
+            codes.push(code);
+        }
+
+        Ok(RecoveryCodes { codes })
+    }
+
+    /// Checks a TOTP code against the current time step, allowing one step
+    /// either side for clock drift. Codes for a step at or before the last
+    /// accepted one are rejected.
+    ///
+    async fn check_totp(
+        &self,
+        code: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<bool, sqlx::error::Error> {
+        let Some(totp) = self.secret.as_deref().and_then(|s| Self::totp(s, String::new())) else {
+            return Ok(false);
+        };
+
+        let current = Utc::now().timestamp() as u64 / STEP;
+        let code = code.trim();
+
+        let step = [current - 1, current, current + 1]
+            .into_iter()
+            .filter(|step| self.last_step.is_none_or(|last| *step as i64 > last))
+            .find(|step| totp.generate(step * STEP) == code);
+
+        let Some(step) = step.map(|step| step as i64) else {
+            return Ok(false);
+        };
+
+        sqlx::query!(
+            "
+            UPDATE users
+            SET totp_last_step = $1
+            WHERE id = $2
+            ",
+            step,
+            self.user_id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(true)
+    }
+
+    fn totp(secret: &str, account_name: String) -> Option<TOTP> {
+        let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+
+        TOTP::new(
+            Algorithm::SHA1,
+            6,
+            1,
+            STEP,
+            secret,
+            Some(ISSUER.to_string()),
+            account_name,
+        )
+        .ok()
+    }
+}
+
+impl TwoFactor {
+    /// Retrieves the two-factor authentication settings of the given user,
+    /// users which do not exist are treated as not having enrolled.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user.
+    /// * `executor`: An implementation of `sqlx::Executor`, representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, sqlx::error::Error>`, where:
+    /// - `Ok(two_factor)` is returned with the user's settings.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn get<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<Self, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        let two_factor = sqlx::query_as!(
+            TwoFactor,
+            "
+            SELECT id AS user_id, totp_secret AS secret,
+                   totp_enabled AS enabled, totp_last_step AS last_step
+            FROM users
+            WHERE id = $1
+            ",
+            user_id
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(two_factor.unwrap_or(Self {
+            user_id,
+            secret: None,
+            enabled: false,
+            last_step: None,
+        }))
+    }
+
+    /// Retrieves whether the user has two-factor authentication enabled and
+    /// how many recovery codes they have left.
+    ///
+    pub async fn get_status<'a, E>(
+        user_id: UserId,
+        executor: E,
+    ) -> Result<TwoFactorStatus, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        sqlx::query_as!(
+            TwoFactorStatus,
+            "
+            SELECT u.totp_enabled AS enabled,
+                   (
+                       SELECT COUNT(*)
+                       FROM recovery_codes r
+                       WHERE r.user_id = u.id
+                       AND r.used = false
+                   ) AS \"recovery_codes_remaining!: i64\"
+            FROM users u
+            WHERE u.id = $1
+            ",
+            user_id
+        )
+        .fetch_one(executor)
+        .await
+    }
+}
+
+/// A pending login for a user with two-factor authentication enabled, which
+/// is completed by providing a valid code.
+///
+pub struct MfaChallenge {
+    /// The challenge's id
+    ///
+    pub id: MfaChallengeId,
+    /// The id of the user logging in
+    ///
+    pub user_id: UserId,
+    /// A hash of the challenge token
+    ///
+    pub token: String,
+    /// The datetime the user logged in
+    ///
+    pub created: NaiveDateTime,
+    /// The datetime after which the challenge can no longer be completed
+    ///
+    pub expires: NaiveDateTime,
+    /// Whether the challenge has already been completed
+    ///
+    pub used: bool,
+}
+
+impl MfaChallenge {
+    /// Creates a new challenge for the given user and inserts it into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The `UserId` of the user logging in.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<LoginChallenge, sqlx::error::Error>`, where:
+    /// - `Ok(challenge)` is returned with the plain text challenge token to give to the client.
+    /// - An `sqlx::error::Error` is returned if there is an error generating the id or executing the query.
+    ///
+    pub async fn create(
+        user_id: UserId,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<LoginChallenge, sqlx::error::Error> {
+        let id = MfaChallengeId::generate(&mut *transaction).await?;
+        let token = nanoid::nanoid!(48);
+        let now = Utc::now().naive_utc();
+
+        let challenge = Self {
+            id,
+            user_id,
+            token: hash_secret(&token),
+            created: now,
+            expires: now + Duration::minutes(CHALLENGE_LIFETIME),
+            used: false,
+        };
+
+        challenge.insert(&mut *transaction).await?;
+
+        Ok(LoginChallenge {
+            challenge: token,
+            expires: challenge.expires,
+        })
+    }
+
+    /// Marks the challenge as completed so it cannot be used again
+    ///
+    pub async fn complete(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            UPDATE mfa_challenges
+            SET used = true
+            WHERE id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl MfaChallenge {
+    /// Inserts the challenge into the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the insertion is successful.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn insert(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        sqlx::query!(
+            "
+            INSERT INTO mfa_challenges (
+                id, user_id, token, created,
+                expires, used
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6
+            )
+            ",
+            self.id,
+            self.user_id,
+            self.token,
+            self.created,
+            self.expires,
+            self.used
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Retrieves the challenge matching the given plain text token, as long
+    /// as it has neither been completed nor expired
+    ///
+    pub async fn get_active<'a, E>(
+        token: &str,
+        executor: E,
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>
+    {
+        let hash = hash_secret(token);
+        let now = Utc::now().naive_utc();
+
+        sqlx::query_as!(
+            MfaChallenge,
+            "
+            SELECT id, user_id, token, created,
+                   expires, used
+            FROM mfa_challenges
+            WHERE token = $1
+            AND used = false
+            AND expires > $2
+            ",
+            hash,
+            now
+        )
+        .fetch_optional(executor)
+        .await
+    }
+}
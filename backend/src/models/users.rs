@@ -6,7 +6,10 @@ use utoipa::ToSchema;
 use crate::error::ApiError;
 use crate::database::Database;
 
-use super::id::UserId;
+use super::id::{ProjectId, UserId};
+use super::notifications::Notification;
+use super::organisations::OrganisationMember;
+use super::projects::{Project, ProjectMember};
 
 pub const DELETED_USER: &str = "03082007";
 
@@ -29,6 +32,57 @@ pub struct User {
     /// 
     #[schema(example="user@example.com")]
     pub email: String,
+    /// Whether the user has confirmed they own their email
+    /// address
+    /// 
+    #[schema(example=true)]
+    pub verified: bool,
+    /// A url to the user's profile picture
+    /// 
+    #[schema(example="https://example.com/icon.png")]
+    pub icon_url: Option<String>,
+    /// Whether the user is an administrator of the instance
+    /// 
+    #[schema(example=false)]
+    pub is_admin: bool,
+    /// Whether the user has been suspended by an administrator
+    /// 
+    #[schema(example=false)]
+    pub suspended: bool,
+    /// Whether the user can be found by searching for their
+    /// username or email address
+    /// 
+    #[schema(example=true)]
+    pub discoverable: bool,
+}
+
+/// The parts of a user anyone can see, returned when searching
+/// for users to invite
+///
+#[derive(Serialize, ToSchema)]
+pub struct PublicUser {
+    /// The user's unqiue id
+    /// 
+    #[schema(example="03082007", min_length=8, max_length=8)]
+    pub id: UserId,
+    /// The user's unique username
+    /// 
+    #[schema(example="My username")]
+    pub username: String,
+    /// A url to the user's profile picture
+    /// 
+    #[schema(example="https://example.com/icon.png")]
+    pub icon_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UserSearch {
+    /// The start of the username or email address to search for
+    /// 
+    pub query: String,
+    /// Leaves out members of, and users invited to, this project
+    /// 
+    pub exclude_project: Option<ProjectId>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema, Debug)]
@@ -59,6 +113,102 @@ pub struct Login {
     pub password: String
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct EditUser {
+    /// The user's new username (3 -> 30 chars)
+    /// 
+    #[schema(example="My new username")]
+    pub username: Option<String>,
+    /// A url to the user's new profile picture
+    /// 
+    #[schema(example="https://example.com/icon.png")]
+    pub icon_url: Option<String>,
+    /// Whether the user can be found by searching for them
+    /// 
+    #[schema(example=true)]
+    pub discoverable: Option<bool>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangeEmail {
+    /// The user's new email address, which will have to
+    /// be verified again
+    /// 
+    #[schema(example="new@example.com")]
+    pub email: String,
+    /// The user's current password
+    /// 
+    #[schema(example="password", format=Password)]
+    pub password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePassword {
+    /// The user's current password
+    /// 
+    #[schema(example="password", format=Password)]
+    pub current_password: String,
+    /// The user's new password
+    /// 
+    #[schema(example="new password", format=Password)]
+    pub new_password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DeleteAccount {
+    /// The user's current password, to confirm the deletion
+    /// 
+    #[schema(example="password", format=Password)]
+    pub password: String,
+    /// A code from the user's authenticator app or a recovery
+    /// code, required if two-factor authentication is enabled
+    /// 
+    #[schema(example="123456")]
+    pub code: Option<String>,
+    /// Confirms the projects the user owns in the trash should be
+    /// permanently removed along with the account
+    /// 
+    #[serde(default)]
+    #[schema(example=false)]
+    pub purge_trash: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ForgotPassword {
+    /// The email address of the account to recover
+    /// 
+    #[schema(example="user@example.com")]
+    pub email: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyEmail {
+    /// The verification token sent to the user's email address
+    /// 
+    #[schema(example="V1StGXR8Z5jdHi6BmyTq2xLJm7uT3pEwNd2Kq9sF")]
+    pub token: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResendVerification {
+    /// The email address to send a new verification link to
+    /// 
+    #[schema(example="user@example.com")]
+    pub email: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPassword {
+    /// The reset token sent to the user's email address
+    /// 
+    #[schema(example="V1StGXR8Z5jdHi6BmyTq2xLJm7uT3pEwNd2Kq9sF")]
+    pub token: String,
+    /// The user's new password
+    /// 
+    #[schema(example="password", format=Password)]
+    pub password: String,
+}
+
 impl User {
     /// Registers a new user with the provided registration form and inserts it into the database.
     ///
@@ -87,6 +237,11 @@ impl User {
             username: form.username,
             password,
             email: form.email,
+            verified: false,
+            icon_url: None,
+            is_admin: false,
+            suspended: false,
+            discoverable: true,
         };
 
         user.insert(&mut *transaction).await?;
@@ -94,6 +249,72 @@ impl User {
         Ok(user)
     }
 
+    /// Registers a new user who logged in through an external identity provider,
+    /// whose address the provider has already verified. The user has no password
+    /// until they set one by resetting it.
+    ///
+    /// # Arguments
+    ///
+    /// * `preferred_username`: The username suggested by the provider, if any, otherwise one is
+    ///   taken from the email address. A number is appended if it is already taken.
+    /// * `email`: The user's verified email address.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Self, sqlx::error::Error>`, where:
+    /// - `Ok(user)` is returned with the newly registered `User`.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries or generating the user ID.
+    ///
+    pub async fn register_external(
+        preferred_username: Option<&str>,
+        email: String,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<Self, sqlx::error::Error> {
+        let base: String = preferred_username
+            .unwrap_or_else(|| email.split('@').next().unwrap_or_default())
+            .chars()
+            .filter(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            .take(24)
+            .collect();
+
+        let base = if base.chars().count() < 3 { format!("user{}", base) } else { base };
+
+        let mut username = base.clone();
+        let mut suffix = 1;
+
+        while sqlx::query_scalar!(
+            "
+            SELECT COUNT(*)
+            FROM users
+            WHERE username = $1
+            ",
+            username
+        )
+        .fetch_one(&mut **transaction)
+        .await? > 0 {
+            suffix += 1;
+            username = format!("{}{}", base, suffix);
+        }
+
+        let user = User {
+            id: UserId::generate(&mut *transaction).await?,
+            username,
+            // Not a valid hash, so no password is accepted
+            password: String::new(),
+            email,
+            verified: true,
+            icon_url: None,
+            is_admin: false,
+            suspended: false,
+            discoverable: true,
+        };
+
+        user.insert(&mut *transaction).await?;
+
+        Ok(user)
+    }
+
     /// Attempts to log in a user with the provided login form and returns an authentication token on success.
     ///
     /// # Arguments
@@ -106,6 +327,7 @@ impl User {
     /// This method returns `Result<Token, sqlx::error::Error>`, where:
     /// - `Ok(token)` is returned with an authentication `Token` if the login is successful.
     /// - An `sqlx::error::Error` is returned if there is an error executing the database queries or if the login credentials are invalid.
+    /// - An `Err(ApiError::AccountSuspended)` is returned if the credentials are valid but the user has been suspended.
     ///
     pub async fn login(
         form: Login,
@@ -115,7 +337,9 @@ impl User {
             User,
             "
             SELECT id, username, 
-            password, email
+            password, email, verified,
+            icon_url, is_admin, suspended,
+                discoverable
             FROM users
             WHERE username = $1
             OR email = $1
@@ -126,12 +350,296 @@ impl User {
         .await?
         .ok_or(ApiError::NotFound)?;
 
-        if !bcrypt::verify(&form.password, &user.password).unwrap() {
+        if !user.check_password(&form.password) {
             return Err(ApiError::Unauthorized);
         }   
 
+        // Only revealed once the password is known to be correct
+        if user.suspended {
+            return Err(ApiError::AccountSuspended);
+        }
+
         Ok(user)
     }
+
+    /// Checks the given plain text password against the user's password hash
+    /// 
+    pub fn check_password(&self, password: &str) -> bool {
+        bcrypt::verify(password, &self.password).unwrap_or(false)
+    }
+
+    /// Updates the user's username, profile picture and discoverability, any fields
+    /// which are not given are left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `form`: An `EditUser` containing the fields to change.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the user was updated.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if the username is taken or not 3 to 30 characters.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn edit(
+        &mut self,
+        form: EditUser,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        if let Some(username) = form.username {
+            if !(3..=30).contains(&username.chars().count()) {
+                return Err(ApiError::unprocessable_entity([("username", "must be between 3 and 30 characters")]));
+            }
+
+            let taken = sqlx::query_scalar!(
+                "
+                SELECT COUNT(*)
+                FROM users
+                WHERE username = $1
+                AND id != $2
+                ",
+                username,
+                self.id
+            )
+            .fetch_one(&mut **transaction)
+            .await? > 0;
+
+            if taken {
+                return Err(ApiError::unprocessable_entity([("username", "already taken")]));
+            }
+
+            self.username = username;
+        }
+
+        if let Some(icon_url) = form.icon_url {
+            self.icon_url = Some(icon_url);
+        }
+
+        if let Some(discoverable) = form.discoverable {
+            self.discoverable = discoverable;
+        }
+
+        sqlx::query!(
+            "
+            UPDATE users
+            SET username = $1,
+                icon_url = $2,
+                discoverable = $3
+            WHERE id = $4
+            ",
+            self.username,
+            self.icon_url,
+            self.discoverable,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Changes the user's email address, the new address is not verified until
+    /// the user follows the link sent to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `email`: The new email address.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), ApiError>`, where:
+    /// - `Ok(())` is returned if the address was changed.
+    /// - An `Err(ApiError::UnprocessableEntity)` is returned if another user has the address.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
+    ///
+    pub async fn set_email(
+        &mut self,
+        email: String,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        let taken = sqlx::query_scalar!(
+            "
+            SELECT COUNT(*)
+            FROM users
+            WHERE email = $1
+            AND id != $2
+            ",
+            email,
+            self.id
+        )
+        .fetch_one(&mut **transaction)
+        .await? > 0;
+
+        if taken {
+            return Err(ApiError::unprocessable_entity([("email", "already taken")]));
+        }
+
+        sqlx::query!(
+            "
+            UPDATE users
+            SET email = $1,
+                verified = false
+            WHERE id = $2
+            ",
+            email,
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        self.email = email;
+        self.verified = false;
+
+        Ok(())
+    }
+
+    /// Replaces the user's password with a hash of the given password.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `UserId` of the user whose password is being changed.
+    /// * `password`: The new plain text password.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// - `Ok(())` is returned if the password was updated.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn set_password(
+        id: UserId,
+        password: String,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), sqlx::error::Error> {
+        let password = bcrypt::hash(password, DEFAULT_COST).unwrap();
+
+        sqlx::query!(
+            "
+            UPDATE users
+            SET password = $1
+            WHERE id = $2
+            ",
+            password,
+            id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Marks the user's email address as verified, as long as it is still the
+    /// address the verification was sent to.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `UserId` of the user whose address was verified.
+    /// * `email`: The address the verification token was sent to.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<bool, sqlx::error::Error>`, where:
+    /// - `Ok(true)` is returned if the user was marked as verified.
+    /// - `Ok(false)` is returned if the user's address has changed since the token was sent.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn mark_verified(
+        id: UserId,
+        email: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<bool, sqlx::error::Error> {
+        let result = sqlx::query!(
+            "
+            UPDATE users
+            SET verified = true
+            WHERE id = $1
+            AND email = $2
+            ",
+            id,
+            email
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Suspends or reinstates the user. Suspending a user does not end their
+    /// sessions, this has to be done separately.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The `UserId` of the user to suspend or reinstate.
+    /// * `suspended`: Whether the user should be suspended.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<bool, sqlx::error::Error>`, where:
+    /// - `Ok(true)` is returned if the user exists and was updated.
+    /// - `Ok(false)` is returned if there is no such user.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn set_suspended(
+        id: UserId,
+        suspended: bool,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<bool, sqlx::error::Error> {
+        let result = sqlx::query!(
+            "
+            UPDATE users
+            SET suspended = $1
+            WHERE id = $2
+            AND id != $3
+            ",
+            suspended,
+            id,
+            DELETED_USER
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Makes the user with the given username an administrator of the instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `username`: The username of the user to promote.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<bool, sqlx::error::Error>`, where:
+    /// - `Ok(true)` is returned if the user exists and was promoted.
+    /// - `Ok(false)` is returned if there is no user with the username.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the database query.
+    ///
+    pub async fn promote(
+        username: &str,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<bool, sqlx::error::Error> {
+        let result = sqlx::query!(
+            "
+            UPDATE users
+            SET is_admin = true
+            WHERE username = $1
+            AND id != $2
+            ",
+            username,
+            DELETED_USER
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
 }
 
 impl User {
@@ -155,16 +663,23 @@ impl User {
             "
             INSERT INTO users (
                 id, username, 
-                password, email
+                password, email, verified,
+                icon_url, is_admin, suspended,
+                discoverable
             )
             VALUES (
-                $1, $2, $3, $4
+                $1, $2, $3, $4, $5, $6, $7, $8, $9
             )
             ",
             self.id,
             self.username,
             self.password,
             self.email,
+            self.verified,
+            self.icon_url,
+            self.is_admin,
+            self.suspended,
+            self.discoverable,
         )
         .execute(&mut **transaction)
         .await?;
@@ -197,7 +712,9 @@ impl User {
             User,
             "
             SELECT id, username, 
-                password, email
+                password, email, verified,
+                icon_url, is_admin, suspended,
+                discoverable
             FROM users
             WHERE id = $1
             ",
@@ -209,6 +726,189 @@ impl User {
         Ok(result)
     }
 
+    /// Retrieves the public information of the user with the provided `id`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: A `UserId` representing the unique identifier of the user to retrieve.
+    /// * `executor`: An implementation of `sqlx::Executor` representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<PublicUser>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(user))` is returned if a user with the provided `id` is found in the database.
+    /// - `Ok(None)` is returned if no user with the provided `id` is found in the database.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the retrieval query.
+    ///
+    pub async fn get_public<'a, E>(
+        id: UserId,
+        executor: E
+    ) -> Result<Option<PublicUser>, sqlx::error::Error> 
+    where
+        E: sqlx::Executor<'a, Database = Database>,
+    {
+        sqlx::query_as!(
+            PublicUser,
+            "
+            SELECT id, username, icon_url
+            FROM users
+            WHERE id = $1
+            ",
+            id
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves a user record from the database based on the provided `email`.
+    ///
+    /// # Arguments
+    ///
+    /// * `email`: The email address to search for in the database.
+    /// * `executor`: An implementation of `sqlx::Executor` representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Option<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(Some(user))` is returned if a user with the provided `email` is found in the database.
+    /// - `Ok(None)` is returned if no user with the provided `email` is found in the database.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the retrieval query.
+    ///
+    pub async fn get_by_email<'a, E>(
+        email: &str,
+        executor: E
+    ) -> Result<Option<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>,
+    {
+        sqlx::query_as!(
+            User,
+            "
+            SELECT id, username, 
+                password, email, verified,
+                icon_url, is_admin, suspended,
+                discoverable
+            FROM users
+            WHERE email = $1
+            ",
+            email
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Retrieves a page of every user on the instance, ordered by username. The
+    /// placeholder deleted user is left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit`: The maximum number of users to retrieve.
+    /// * `offset`: The number of users to skip.
+    /// * `executor`: An implementation of `sqlx::Executor` representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<Self>, sqlx::error::Error>`, where:
+    /// - `Ok(vec)` is returned with the page of users.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the retrieval query.
+    ///
+    pub async fn get_all<'a, E>(
+        limit: u32,
+        offset: u32,
+        executor: E,
+    ) -> Result<Vec<Self>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>,
+    {
+        sqlx::query_as!(
+            User,
+            "
+            SELECT id, username, 
+                password, email, verified,
+                icon_url, is_admin, suspended,
+                discoverable
+            FROM users
+            WHERE id != $1
+            ORDER BY username
+            LIMIT $2
+            OFFSET $3
+            ",
+            DELETED_USER,
+            limit,
+            offset
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Searches for discoverable users whose username or email address starts
+    /// with the given query, ordered by username. Suspended users and the
+    /// placeholder deleted user are never included.
+    ///
+    /// # Arguments
+    ///
+    /// * `query`: The start of the username or email address to search for.
+    /// * `exclude_project`: A project whose members and invitees should be left out, if any.
+    /// * `limit`: The maximum number of users to retrieve.
+    /// * `offset`: The number of users to skip.
+    /// * `executor`: An implementation of `sqlx::Executor` representing the database executor.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<Vec<PublicUser>, sqlx::error::Error>`, where:
+    /// - `Ok(vec)` is returned with the page of matching users.
+    /// - An `sqlx::error::Error` is returned if there is an error executing the retrieval query.
+    ///
+    pub async fn search<'a, E>(
+        query: &str,
+        exclude_project: Option<ProjectId>,
+        limit: u32,
+        offset: u32,
+        executor: E,
+    ) -> Result<Vec<PublicUser>, sqlx::error::Error>
+    where
+        E: sqlx::Executor<'a, Database = Database>,
+    {
+        // Wildcards typed by the user are matched literally
+        let pattern = format!(
+            "{}%",
+            query
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+
+        sqlx::query_as!(
+            PublicUser,
+            "
+            SELECT id, username, icon_url
+            FROM users
+            WHERE (
+                username LIKE $1 ESCAPE '\\'
+                OR email LIKE $1 ESCAPE '\\'
+            )
+            AND discoverable = true
+            AND suspended = false
+            AND id != $2
+            AND ($3 IS NULL OR id NOT IN (
+                SELECT user_id
+                FROM project_members
+                WHERE project_id = $3
+            ))
+            ORDER BY username
+            LIMIT $4
+            OFFSET $5
+            ",
+            pattern,
+            DELETED_USER,
+            exclude_project,
+            limit,
+            offset
+        )
+        .fetch_all(executor)
+        .await
+    }
+
     /// Retrieves a list of user records from the database that match the given column and value.
     ///
     /// # Arguments
@@ -236,7 +936,9 @@ impl User {
             User,
             "
             SELECT id, username, 
-                password, email
+                password, email, verified,
+                icon_url, is_admin, suspended,
+                discoverable
             FROM users
             WHERE $1 = $2
             ",
@@ -276,70 +978,207 @@ impl User {
             .map(|x| x.into_iter().next())
     }
 
-
-    /// Removes the user and associated data from the database.
+    /// Removes the user and their personal data from the database. Tasks they
+    /// created and audit entries they made are kept but moved to the deleted
+    /// user, and they leave every project and organisation they are a member of.
+    /// Projects they own which are in the trash are permanently removed, but only
+    /// when `purge_trash` is given so they aren't lost without warning.
     ///
     /// # Arguments
     ///
-    /// * `executor`: An implementation of `sqlx::Executor` representing the database executor.
+    /// * `purge_trash`: Whether the projects the user owns in the trash should be permanently removed.
+    /// * `transaction`: A mutable reference to a `sqlx::Transaction`, representing a database transaction.
     ///
     /// # Returns
     ///
-    /// This method returns `Result<(), sqlx::error::Error>`, where:
+    /// This method returns `Result<(), ApiError>`, where:
     /// - `Ok(())` is returned if the user and associated data are successfully removed from the database.
-    /// - An `sqlx::error::Error` is returned if there is an error executing the database queries.
+    /// - An `Err(ApiError::Conflict)` is returned if the user still owns a project outside the trash,
+    ///   owns a project in the trash without `purge_trash`, or is the last admin of an organisation.
+    /// - An `Err(ApiError::Sqlx)` is returned if there is an error executing the database queries.
     ///
-    pub async fn remove<'a, E>(
+    pub async fn remove(
         &self,  
-        executor: E
-    ) -> Result<(), sqlx::error::Error>
-    where 
-        E: sqlx::Executor<'a, Database = Database> + Copy,
-    {
-        let deleted_user: UserId = UserId(DELETED_USER.into());
+        purge_trash: bool,
+        transaction: &mut sqlx::Transaction<'_, Database>,
+    ) -> Result<(), ApiError> {
+        // Projects waiting in the trash would otherwise keep the account
+        // from being removed, so they are purged early once confirmed
+        let trashed_projects = sqlx::query_scalar!(
+            r#"
+            SELECT id AS "id: ProjectId"
+            FROM projects
+            WHERE owner = $1
+                AND trashed IS NOT NULL
+            "#,
+            self.id
+        )
+        .fetch_all(&mut **transaction)
+        .await?;
+
+        if !trashed_projects.is_empty() && !purge_trash {
+            return Err(ApiError::Conflict(
+                "you own projects in the trash, confirm they should be permanently removed"
+            ));
+        }
+
+        for project_id in trashed_projects {
+            Project::remove(project_id, transaction).await?;
+        }
+
+        let owned_projects = sqlx::query_scalar!(
+            "
+            SELECT COUNT(*)
+            FROM projects
+            WHERE owner = $1
+            ",
+            self.id
+        )
+        .fetch_one(&mut **transaction)
+        .await?;
+
+        if owned_projects > 0 {
+            return Err(ApiError::Conflict(
+                "you still own projects, transfer their ownership or delete them first"
+            ));
+        }
+
+        // Leaving fails if the user is an organisation's last admin
+        for membership in OrganisationMember::get_many_from_user(self.id.clone(), &mut **transaction).await? {
+            membership.remove(&membership, transaction).await?;
+        }
+
+        for membership in ProjectMember::get_many_from_user(self.id.clone(), &mut **transaction).await? {
+            membership.hand_over_to_deleted(transaction).await?;
+            membership.leave(transaction).await?;
+        }
+
+        Notification::remove_all(self.id.clone(), transaction).await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM personal_access_token_projects
+            WHERE token_id IN (
+                SELECT id
+                FROM personal_access_tokens
+                WHERE user_id = $1
+            )
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
 
         sqlx::query!(
             "
-            UPDATE project_members
-            SET user_id = $1
-            WHERE user_id = $2
+            DELETE FROM personal_access_tokens
+            WHERE user_id = $1
             ",
-            deleted_user.0,
             self.id
         )
-        .execute(executor)
+        .execute(&mut **transaction)
         .await?;
 
+        // Login history references sessions so has to be
+        // removed before them
         sqlx::query!(
             "
-            DELETE FROM notifications
+            DELETE FROM login_history
             WHERE user_id = $1
             ",
             self.id
         )
-        .execute(executor)
+        .execute(&mut **transaction)
         .await?;
 
         sqlx::query!(
             "
-            DELETE FROM tasks
-            WHERE creator = $1
+            DELETE FROM sessions
+            WHERE user_id = $1
             ",
             self.id
         )
-        .execute(executor)
+        .execute(&mut **transaction)
         .await?;
 
         sqlx::query!(
             "
-            UPDATE sub_tasks
-            SET assignee = $1
-            WHERE assignee = $2
+            DELETE FROM password_resets
+            WHERE user_id = $1
             ",
-            deleted_user.0,
             self.id
         )
-        .execute(executor)
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM email_verifications
+            WHERE user_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM recovery_codes
+            WHERE user_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM mfa_challenges
+            WHERE user_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM login_attempts
+            WHERE user_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM user_identities
+            WHERE user_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM data_exports
+            WHERE user_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            DELETE FROM project_templates
+            WHERE user_id = $1
+            ",
+            self.id
+        )
+        .execute(&mut **transaction)
         .await?;
 
         sqlx::query!(
@@ -349,9 +1188,9 @@ impl User {
             ",
             self.id
         )
-        .execute(executor)
+        .execute(&mut **transaction)
         .await?;
 
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,355 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use jsonwebtoken::jwk::JwkSet;
+use reqwest::{StatusCode, Url};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use crate::cli::OidcArguments;
+use crate::error::ApiError;
+
+/// How long to wait for the provider to respond (in seconds)
+const REQUEST_TIMEOUT: u64 = 10;
+
+#[derive(thiserror::Error, Debug)]
+pub enum OidcError {
+    #[error("An OpenID Connect issuer was provided without a client id, set OIDC_CLIENT_ID")]
+    MissingClientId,
+    #[error("An OpenID Connect issuer was provided without a redirect url, set OIDC_REDIRECT_URL")]
+    MissingRedirectUrl,
+    #[error("Invalid OpenID Connect url: {0}")]
+    Url(#[from] url::ParseError),
+    #[error("Failed to reach the OpenID Connect provider: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("The OpenID Connect provider identifies itself as {0}, which is not the configured issuer")]
+    IssuerMismatch(String),
+    #[error("The OpenID Connect provider rejected the authorization code with status {0}")]
+    CodeRejected(StatusCode),
+    #[error("Invalid id token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+    #[error("The id token was not signed with one of the provider's keys")]
+    UnknownKey,
+    #[error("The id token's nonce does not match the login")]
+    NonceMismatch,
+    #[error("The provider's user info belongs to a different subject than the id token")]
+    SubjectMismatch,
+}
+
+impl From<OidcError> for ApiError {
+    fn from(error: OidcError) -> Self {
+        match error {
+            OidcError::CodeRejected(_)
+            | OidcError::InvalidToken(_)
+            | OidcError::UnknownKey
+            | OidcError::NonceMismatch
+            | OidcError::SubjectMismatch => {
+                tracing::warn!("Rejected OpenID Connect login: {}", error);
+                ApiError::Unauthorized
+            }
+            error => ApiError::Anyhow(error.into()),
+        }
+    }
+}
+
+/// The parts of the provider's discovery document that are needed
+/// to log users in
+///
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+    userinfo_endpoint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+    access_token: String,
+}
+
+/// What the provider knows about the user who logged in, taken from
+/// the id token and, if it does not include an email, the user info
+/// endpoint
+///
+#[derive(Deserialize)]
+pub struct IdentityClaims {
+    /// The provider's identifier for the user, unique per issuer
+    pub sub: String,
+    /// The user's email address
+    pub email: Option<String>,
+    /// Whether the provider has verified the user owns their address
+    #[serde(default)]
+    pub email_verified: bool,
+    /// The username the user would like to be known by
+    pub preferred_username: Option<String>,
+    /// The nonce sent with the authorization request
+    pub nonce: Option<String>,
+}
+
+/// A freshly generated authorization request, the state, nonce and
+/// verifier have to be kept until the provider redirects back
+///
+pub struct AuthorizationRequest {
+    /// The provider's url to send the user to
+    pub url: String,
+    /// An opaque value the provider returns, linking the callback to this request
+    pub state: String,
+    /// A value the provider includes in the id token to prevent replays
+    pub nonce: String,
+    /// The PKCE verifier whose hash was sent with the request
+    pub code_verifier: String,
+}
+
+/// A client for a single OpenID Connect provider using the
+/// authorization code flow with PKCE.
+///
+pub struct OidcClient {
+    http: reqwest::Client,
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    scopes: String,
+    redirect_url: String,
+    /// Fetched on first use, so the server can start while the
+    /// provider is unreachable
+    metadata: OnceCell<ProviderMetadata>,
+}
+
+impl OidcClient {
+    /// The issuer identities from this provider are recorded against
+    ///
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// Generates the url to send a user to so they can log in with the
+    /// provider, along with the values needed to complete the login.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<AuthorizationRequest, OidcError>`, where:
+    /// - `Ok(request)` is returned with the provider's url and the login's secrets.
+    /// - An `OidcError` is returned if the provider's discovery document cannot be fetched.
+    ///
+    pub async fn authorization_request(&self) -> Result<AuthorizationRequest, OidcError> {
+        let metadata = self.metadata().await?;
+
+        let state = nanoid::nanoid!(48);
+        let nonce = nanoid::nanoid!(48);
+        // Nanoid's alphabet only contains characters allowed in verifiers
+        let code_verifier = nanoid::nanoid!(64);
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", &self.redirect_url),
+                ("scope", &self.scopes),
+                ("state", &state),
+                ("nonce", &nonce),
+                ("code_challenge", &code_challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )?;
+
+        Ok(AuthorizationRequest {
+            url: url.into(),
+            state,
+            nonce,
+            code_verifier,
+        })
+    }
+
+    /// Exchanges the authorization code returned by the provider for an id
+    /// token and returns its verified claims.
+    ///
+    /// # Arguments
+    ///
+    /// * `code`: The authorization code the provider redirected back with.
+    /// * `code_verifier`: The PKCE verifier generated with the authorization request.
+    /// * `nonce`: The nonce generated with the authorization request.
+    ///
+    /// # Returns
+    ///
+    /// This method returns `Result<IdentityClaims, OidcError>`, where:
+    /// - `Ok(claims)` is returned if the code was accepted and the id token is valid.
+    /// - An `OidcError` is returned if the code is rejected, the token is invalid or the provider cannot be reached.
+    ///
+    pub async fn exchange(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<IdentityClaims, OidcError> {
+        let metadata = self.metadata().await?;
+
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_url),
+            ("client_id", &self.client_id),
+            ("code_verifier", code_verifier),
+        ];
+
+        if let Some(client_secret) = &self.client_secret {
+            params.push(("client_secret", client_secret));
+        }
+
+        let response = self.http
+            .post(&metadata.token_endpoint)
+            .form(&params)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(OidcError::CodeRejected(response.status()));
+        }
+
+        let tokens: TokenResponse = response.json().await?;
+        let mut claims = self.verify(&tokens.id_token, metadata).await?;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::NonceMismatch);
+        }
+
+        // Some providers leave the profile out of the id token and
+        // only return it from the user info endpoint
+        if let (None, Some(userinfo_endpoint)) = (&claims.email, &metadata.userinfo_endpoint) {
+            let userinfo: IdentityClaims = self.http
+                .get(userinfo_endpoint)
+                .bearer_auth(&tokens.access_token)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            if userinfo.sub != claims.sub {
+                return Err(OidcError::SubjectMismatch);
+            }
+
+            claims.email = userinfo.email;
+            claims.email_verified = userinfo.email_verified;
+            claims.preferred_username = claims.preferred_username.or(userinfo.preferred_username);
+        }
+
+        Ok(claims)
+    }
+
+    /// Checks the id token's signature against the provider's keys, and
+    /// that it was issued by the provider, for this client and has not
+    /// expired
+    ///
+    async fn verify(
+        &self,
+        id_token: &str,
+        metadata: &ProviderMetadata,
+    ) -> Result<IdentityClaims, OidcError> {
+        let header = jsonwebtoken::decode_header(id_token)?;
+
+        // Only keys published by the provider are trusted, never
+        // the client secret
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(OidcError::UnknownKey);
+        }
+
+        // Logins are rare enough that the keys are fetched every
+        // time rather than cached, which also picks up rotations
+        let keys: JwkSet = self.http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let jwk = match &header.kid {
+            Some(kid) => keys.find(kid),
+            None => keys.keys.first(),
+        }
+        .ok_or(OidcError::UnknownKey)?;
+
+        let key = DecodingKey::from_jwk(jwk)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.client_id]);
+
+        let token = jsonwebtoken::decode::<IdentityClaims>(id_token, &key, &validation)?;
+
+        Ok(token.claims)
+    }
+
+    /// Fetches the provider's discovery document the first time it is
+    /// needed
+    ///
+    async fn metadata(&self) -> Result<&ProviderMetadata, OidcError> {
+        self.metadata.get_or_try_init(|| async {
+            let url = format!(
+                "{}/.well-known/openid-configuration",
+                self.issuer.trim_end_matches('/')
+            );
+
+            let metadata: ProviderMetadata = self.http
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            if metadata.issuer.trim_end_matches('/') != self.issuer.trim_end_matches('/') {
+                return Err(OidcError::IssuerMismatch(metadata.issuer));
+            }
+
+            Ok(metadata)
+        })
+        .await
+    }
+}
+
+/// Creates a client for the provider selected by the start command's
+/// arguments, or nothing if logging in with OpenID Connect is disabled
+///
+pub fn configure(
+    OidcArguments {
+        oidc_issuer,
+        oidc_client_id,
+        oidc_client_secret,
+        oidc_scopes,
+        oidc_redirect_url,
+    }: OidcArguments
+) -> Result<Option<Arc<OidcClient>>, OidcError> {
+    let Some(issuer) = oidc_issuer else {
+        return Ok(None);
+    };
+
+    let client_id = oidc_client_id.ok_or(OidcError::MissingClientId)?;
+    let redirect_url = oidc_redirect_url.ok_or(OidcError::MissingRedirectUrl)?;
+
+    // Fail on startup rather than on the first login
+    Url::parse(&issuer)?;
+    Url::parse(&redirect_url)?;
+
+    let http = reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT))
+        .build()?;
+
+    Ok(Some(Arc::new(OidcClient {
+        http,
+        issuer,
+        client_id,
+        client_secret: oidc_client_secret,
+        scopes: oidc_scopes,
+        redirect_url,
+        metadata: OnceCell::new(),
+    })))
+}
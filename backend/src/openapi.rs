@@ -12,20 +12,90 @@ use crate::{api, models, cli::OpenApiSchemaArguements};
     ),
     paths(
         api::v1::users::get_current_user,
+        api::v1::users::edit_current_user,
+        api::v1::users::change_email,
+        api::v1::users::change_password,
+        api::v1::users::delete_current_user,
+        api::v1::users::search_users,
         api::v1::users::get_user_by_id,
         api::v1::users::register,
         api::v1::users::login,
+        api::v1::users::refresh,
+        api::v1::users::logout,
+        api::v1::users::logout_all,
+        api::v1::users::get_login_history,
+        api::v1::users::get_sessions,
+        api::v1::users::revoke_session,
+        api::v1::users::get_access_tokens,
+        api::v1::users::create_access_token,
+        api::v1::users::revoke_access_token,
+        api::v1::users::create_data_export,
+        api::v1::users::get_data_exports,
+        api::v1::users::get_data_export,
+        api::v1::users::download_data_export,
+        api::v1::users::forgot_password,
+        api::v1::users::reset_password,
+        api::v1::users::verify_email,
+        api::v1::users::resend_verification,
+        api::v1::users::login_totp,
+        api::v1::users::oidc_authorize,
+        api::v1::users::oidc_callback,
+        api::v1::users::get_two_factor_status,
+        api::v1::users::enrol_two_factor,
+        api::v1::users::confirm_two_factor,
+        api::v1::users::disable_two_factor,
+        api::v1::users::regenerate_recovery_codes,
 
         api::v1::projects::create_project,
         api::v1::projects::get_memberships_from_user,
         api::v1::projects::get_project_by_id,
         api::v1::projects::update_project,
         api::v1::projects::remove_project,
+        api::v1::projects::restore_project,
+        api::v1::projects::get_trash,
+        api::v1::projects::archive_project,
+        api::v1::projects::unarchive_project,
+        api::v1::projects::clone_project,
+        api::v1::projects::save_template,
         api::v1::projects::get_audits,
         api::v1::projects::get_members,
         api::v1::projects::invite_member,
+        api::v1::projects::edit_member,
+        api::v1::projects::remove_member,
+        api::v1::projects::accept_invitation,
+        api::v1::projects::deny_invitation,
+        api::v1::projects::get_invite_links,
+        api::v1::projects::create_invite_link,
+        api::v1::projects::revoke_invite_link,
+        api::v1::projects::join_project,
+        api::v1::projects::get_roles,
+        api::v1::projects::create_role,
+        api::v1::projects::edit_role,
+        api::v1::projects::remove_role,
+        api::v1::projects::get_transfer,
+        api::v1::projects::transfer_project,
+        api::v1::projects::cancel_transfer,
+        api::v1::projects::accept_transfer,
+        api::v1::projects::deny_transfer,
         api::v1::projects::get_task_groups,
         api::v1::projects::create_task_group,
+        api::v1::templates::get_templates,
+        api::v1::templates::get_template,
+        api::v1::templates::remove_template,
+        api::v1::organisations::get_organisations,
+        api::v1::organisations::create_organisation,
+        api::v1::organisations::get_organisation,
+        api::v1::organisations::edit_organisation,
+        api::v1::organisations::remove_organisation,
+        api::v1::organisations::get_projects,
+        api::v1::organisations::get_members,
+        api::v1::organisations::add_member,
+        api::v1::organisations::edit_member,
+        api::v1::organisations::remove_member,
+        api::v1::organisations::get_roles,
+        api::v1::organisations::create_role,
+        api::v1::organisations::edit_role,
+        api::v1::organisations::remove_role,
 
         api::v1::task_groups::get_task_group_by_id,
         api::v1::task_groups::edit_task_group,
@@ -40,7 +110,15 @@ use crate::{api, models, cli::OpenApiSchemaArguements};
 
         api::v1::sub_tasks::get_sub_task_by_id,
         api::v1::sub_tasks::edit_sub_task,
-        api::v1::sub_tasks::remove_sub_task
+        api::v1::sub_tasks::remove_sub_task,
+
+        api::v1::admin::get_users,
+        api::v1::admin::suspend_user,
+        api::v1::admin::unsuspend_user,
+        api::v1::admin::delete_user,
+        api::v1::admin::get_projects,
+        api::v1::admin::get_statistics,
+        api::v1::admin::get_login_attempts
     ),
     components(schemas(
         models::id::UserId,
@@ -52,14 +130,54 @@ use crate::{api, models, cli::OpenApiSchemaArguements};
         models::id::AuditId,
         models::id::NotificationId,
         models::id::NotificationActionId,
+        models::id::SessionId,
+        models::id::LoginHistoryId,
+        models::id::PersonalAccessTokenId,
+        models::id::LoginAttemptId,
+        models::id::ProjectRoleId,
+        models::id::OwnershipTransferId,
+        models::id::ProjectTemplateId,
+        models::id::InviteLinkId,
+        models::id::OrganisationId,
+        models::id::OrganisationMemberId,
+        models::id::OrganisationRoleId,
 
         models::users::User,
+        models::users::PublicUser,
         models::users::Register,
         models::users::Login,
+        models::users::EditUser,
+        models::users::ChangeEmail,
+        models::users::ChangePassword,
+        models::users::DeleteAccount,
+        models::users::ForgotPassword,
+        models::users::ResetPassword,
+        models::users::VerifyEmail,
+        models::users::ResendVerification,
+        models::two_factor::TwoFactorStatus,
+        models::two_factor::TotpEnrolment,
+        models::two_factor::RecoveryCodes,
+        models::two_factor::TotpCode,
+        models::two_factor::TotpLogin,
+        models::two_factor::LoginChallenge,
 
         models::audits::Audit,
 
         models::tokens::Token,
+        models::tokens::RefreshToken,
+
+        models::sessions::Session,
+        models::sessions::ActiveSession,
+        models::login_history::LoginHistory,
+        models::login_attempts::LoginAttempt,
+        models::statistics::InstanceStatistics,
+
+        models::access_tokens::PersonalAccessToken,
+        models::access_tokens::PersonalAccessTokenBuilder,
+        models::access_tokens::CreatedPersonalAccessToken,
+        models::data_exports::DataExport,
+        models::data_exports::ExportStatus,
+        models::access_tokens::TokenProjects,
 
         models::notifications::Notification,
         models::notifications::NotificationAction,
@@ -68,8 +186,40 @@ use crate::{api, models, cli::OpenApiSchemaArguements};
 
         models::projects::Project,
         models::projects::EditProject,
+        models::projects::ProjectLink,
         models::projects::ProjectBuilder,
+        models::projects::CloneProject,
         models::projects::ProjectMember,
+        models::projects::InviteMembers,
+        models::projects::EditMember,
+
+        models::roles::ProjectRole,
+        models::roles::RoleBuilder,
+        models::roles::EditRole,
+
+        models::ownership_transfers::OwnershipTransfer,
+        models::invite_links::InviteLink,
+        models::invite_links::InviteLinkBuilder,
+        models::invite_links::CreatedInviteLink,
+        models::invite_links::JoinProject,
+        models::templates::ProjectTemplate,
+        models::templates::TemplateBuilder,
+        models::templates::ProjectContent,
+        models::templates::TaskGroupContent,
+        models::templates::TaskContent,
+        models::templates::SubTaskContent,
+        models::templates::LabelContent,
+        models::templates::TaskEdgeContent,
+        models::organisations::Organisation,
+        models::organisations::OrganisationBuilder,
+        models::organisations::EditOrganisation,
+        models::organisations::OrganisationMember,
+        models::organisations::AddOrganisationMember,
+        models::organisations::EditOrganisationMember,
+        models::organisations::OrganisationRole,
+        models::organisations::OrganisationRoleBuilder,
+        models::organisations::EditOrganisationRole,
+        models::ownership_transfers::NominateOwner,
         
         models::tasks::TaskGroup,
         models::tasks::EditTaskGroup,
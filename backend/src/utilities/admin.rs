@@ -0,0 +1,35 @@
+use tracing::info;
+
+use crate::cli::PromoteArguments;
+use crate::database::sql::connect;
+use crate::models::users::User;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AdminError {
+    #[error("There is no user with the username '{0}'")]
+    UnknownUser(String),
+}
+
+/// Makes an existing user an administrator, this is how the first
+/// administrator of an instance is created
+///
+pub async fn promote(
+    PromoteArguments {
+        username,
+        database_url,
+    }: PromoteArguments
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pool = connect(database_url).await?;
+
+    let mut transaction = pool.begin().await?;
+
+    if !User::promote(&username, &mut transaction).await? {
+        return Err(AdminError::UnknownUser(username).into());
+    }
+
+    transaction.commit().await?;
+
+    info!("Promoted '{}' to an administrator", username);
+
+    Ok(())
+}
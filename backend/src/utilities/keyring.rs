@@ -0,0 +1,211 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDateTime, Utc};
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::cli::RotateKeysArguments;
+
+#[derive(thiserror::Error, Debug)]
+pub enum KeyringError {
+    #[error("Failed to read or write the keyring: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The keyring is not valid json: {0}")]
+    Malformed(#[from] serde_json::Error),
+}
+
+/// A single secret used to sign access tokens, identified by the
+/// `kid` header of the tokens it signs.
+///
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SigningKey {
+    /// The key's id, placed in the `kid` header of every token
+    /// signed with this key
+    ///
+    pub kid: String,
+    /// The HMAC secret itself
+    ///
+    pub secret: String,
+    /// The time the key was generated
+    ///
+    pub created: NaiveDateTime,
+}
+
+impl SigningKey {
+    /// Generates a new random signing key
+    ///
+    pub fn generate() -> Self {
+        Self {
+            kid: nanoid::nanoid!(8),
+            secret: nanoid::nanoid!(64),
+            created: Utc::now().naive_utc(),
+        }
+    }
+
+    /// Creates a signing key from a fixed secret, such as one given
+    /// through the environment. The id is derived from the secret
+    /// so it stays the same across restarts.
+    ///
+    pub fn from_secret(secret: String) -> Self {
+        let digest = format!("{:x}", Sha256::digest(secret.as_bytes()));
+
+        Self {
+            kid: digest[..8].to_string(),
+            secret,
+            created: Utc::now().naive_utc(),
+        }
+    }
+}
+
+/// The set of keys the server signs and verifies tokens with.
+///
+/// New tokens are always signed with the current key, tokens signed
+/// with previous keys are still accepted so rotating the key does
+/// not log every user out.
+///
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Keyring {
+    /// The key used to sign new tokens
+    ///
+    pub current: SigningKey,
+    /// Retired keys which are only used to verify existing tokens,
+    /// newest first
+    ///
+    #[serde(default)]
+    pub previous: Vec<SigningKey>,
+}
+
+impl Keyring {
+    /// Creates a keyring containing a single newly generated key
+    ///
+    pub fn generate() -> Self {
+        Self {
+            current: SigningKey::generate(),
+            previous: Vec::new(),
+        }
+    }
+
+    /// Creates a keyring from a single fixed secret
+    ///
+    pub fn from_secret(secret: String) -> Self {
+        Self {
+            current: SigningKey::from_secret(secret),
+            previous: Vec::new(),
+        }
+    }
+
+    /// Reads a keyring from the given json file
+    ///
+    pub fn load(path: &Path) -> Result<Self, KeyringError> {
+        let contents = fs::read_to_string(path)?;
+
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Reads a keyring from the given json file, if the file does not
+    /// exist yet a new keyring is generated and written to it.
+    ///
+    pub fn load_or_generate(path: &Path) -> Result<Self, KeyringError> {
+        if path.exists() {
+            return Self::load(path);
+        }
+
+        warn!("No keyring found at {}, generating a new one", path.display());
+
+        let keyring = Self::generate();
+        keyring.save(path)?;
+
+        Ok(keyring)
+    }
+
+    /// Writes the keyring to the given json file, readable only by its
+    /// owner. The keyring is written to a temporary file first which then
+    /// replaces the old one, so a crash part way through never leaves a
+    /// truncated keyring behind.
+    ///
+    pub fn save(&self, path: &Path) -> Result<(), KeyringError> {
+        let contents = serde_json::to_string_pretty(self)?;
+
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+
+        // The mode is only applied to new files, so one left behind
+        // by an earlier crash is removed rather than reused
+        if temporary.exists() {
+            fs::remove_file(&temporary)?;
+        }
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options.open(&temporary)?;
+
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+
+        fs::rename(&temporary, path)?;
+
+        Ok(())
+    }
+
+    /// Replaces the current key with a newly generated one, keeping at
+    /// most `retain` previous keys to verify existing tokens with.
+    ///
+    pub fn rotate(&mut self, retain: usize) {
+        let retired = std::mem::replace(&mut self.current, SigningKey::generate());
+
+        self.previous.insert(0, retired);
+        self.previous.truncate(retain);
+    }
+
+    /// The id and encoding key of the key new tokens should be signed with
+    ///
+    pub fn encoding_key(&self) -> (&str, EncodingKey) {
+        (
+            &self.current.kid,
+            EncodingKey::from_secret(self.current.secret.as_bytes()),
+        )
+    }
+
+    /// Finds the key with the given id, either the current key or one of the
+    /// previous keys. Tokens without a key id are checked against the current key.
+    ///
+    pub fn decoding_key(&self, kid: Option<&str>) -> Option<DecodingKey> {
+        let key = match kid {
+            Some(kid) => std::iter::once(&self.current)
+                .chain(self.previous.iter())
+                .find(|key| key.kid == kid)?,
+            None => &self.current,
+        };
+
+        Some(DecodingKey::from_secret(key.secret.as_bytes()))
+    }
+}
+
+pub async fn rotate(
+    RotateKeysArguments {
+        keyring_location,
+        retain,
+    }: RotateKeysArguments
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut keyring = Keyring::load_or_generate(&keyring_location)?;
+
+    keyring.rotate(retain);
+    keyring.save(&keyring_location)?;
+
+    info!(
+        "Rotated signing key, new tokens will be signed with key '{}' once the server is restarted",
+        keyring.current.kid
+    );
+
+    Ok(())
+}
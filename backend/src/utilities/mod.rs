@@ -0,0 +1,26 @@
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+
+pub mod admin;
+pub mod keyring;
+
+/// Hashes a randomly generated secret, such as a refresh token, so
+/// that it can be stored and looked up without storing the secret
+/// itself. These secrets are long and random so unlike passwords
+/// they do not need a slow, salted hash.
+///
+pub fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// Deserializes a field which was given as `Some`, even when it is null,
+/// so that a field being cleared (`Some(None)`) can be told apart from it
+/// being left out (`None`). Used along with `#[serde(default)]`.
+///
+pub fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
@@ -0,0 +1,484 @@
+//! Logs in through the OpenID Connect endpoints against a mock provider
+//! serving its discovery document, keys and token endpoint. Each test
+//! starts the server with its own database.
+
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::header::{LOCATION, SET_COOKIE};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+
+const CLIENT_ID: &str = "nine-yards";
+const FRONTEND_URL: &str = "http://frontend.test";
+const EMAIL: &str = "oidc@example.com";
+const PASSWORD: &str = "Password123!";
+
+const SIGNING_KEY: &str = include_str!("fixtures/oidc/signing_key.pem");
+const UNTRUSTED_KEY: &str = include_str!("fixtures/oidc/untrusted_key.pem");
+const JWKS: &str = include_str!("fixtures/oidc/jwks.json");
+
+/// An authorization code the provider has issued, the id token is only
+/// handed out to whoever has the verifier for the PKCE challenge
+///
+struct Grant {
+    code_challenge: String,
+    id_token: String,
+}
+
+#[derive(Clone)]
+struct Provider {
+    issuer: String,
+    grants: Arc<Mutex<HashMap<String, Grant>>>,
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    grant_type: String,
+    code: String,
+    code_verifier: String,
+    client_id: String,
+}
+
+async fn discovery(State(provider): State<Provider>) -> Json<Value> {
+    Json(json!({
+        "issuer": provider.issuer,
+        "authorization_endpoint": format!("{}/authorize", provider.issuer),
+        "token_endpoint": format!("{}/token", provider.issuer),
+        "jwks_uri": format!("{}/jwks", provider.issuer),
+    }))
+}
+
+async fn jwks() -> Json<Value> {
+    Json(serde_json::from_str(JWKS).unwrap())
+}
+
+async fn token(
+    State(provider): State<Provider>,
+    Form(form): Form<TokenRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let grant = provider.grants.lock().unwrap()
+        .remove(&form.code)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(form.code_verifier.as_bytes()));
+
+    if form.grant_type != "authorization_code"
+        || form.client_id != CLIENT_ID
+        || code_challenge != grant.code_challenge
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Ok(Json(json!({
+        "id_token": grant.id_token,
+        "access_token": "access-token",
+        "token_type": "Bearer",
+    })))
+}
+
+impl Provider {
+    fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let provider = Self {
+            issuer: format!("http://{}", listener.local_addr().unwrap()),
+            grants: Arc::default(),
+        };
+
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(provider.clone());
+
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+        provider
+    }
+
+    /// Issues a code which can be exchanged for the given id token
+    ///
+    fn grant(&self, code_challenge: &str, id_token: String) -> String {
+        let code = nanoid::nanoid!(32);
+
+        self.grants.lock().unwrap().insert(code.clone(), Grant {
+            code_challenge: code_challenge.to_string(),
+            id_token,
+        });
+
+        code
+    }
+
+    /// The claims of an id token for a new login, which tests can change
+    /// before signing it
+    ///
+    fn claims(&self, nonce: &str) -> Value {
+        let now = chrono::Utc::now().timestamp();
+
+        json!({
+            "iss": self.issuer,
+            "aud": CLIENT_ID,
+            "sub": "provider-subject",
+            "email": EMAIL,
+            "email_verified": true,
+            "preferred_username": "provider_user",
+            "nonce": nonce,
+            "iat": now,
+            "exp": now + 300,
+        })
+    }
+}
+
+fn sign(claims: &Value, key: &str) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some("test-key".into());
+
+    jsonwebtoken::encode(&header, claims, &EncodingKey::from_rsa_pem(key.as_bytes()).unwrap()).unwrap()
+}
+
+/// A login started at the authorize endpoint
+///
+struct Login {
+    state: String,
+    nonce: String,
+    code_challenge: String,
+    /// The state cookie to send back to the callback
+    cookie: String,
+}
+
+struct TestServer {
+    url: String,
+    http: reqwest::Client,
+    pool: SqlitePool,
+    provider: Provider,
+    directory: PathBuf,
+    child: Child,
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.directory);
+    }
+}
+
+impl TestServer {
+    async fn start() -> Self {
+        let provider = Provider::start();
+
+        let directory = std::env::temp_dir().join(format!("nine-yards-oidc-{}", nanoid::nanoid!(12)));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let database_url = format!("sqlite://{}", directory.join("database.db").display());
+
+        let options = SqliteConnectOptions::from_str(&database_url).unwrap().create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+
+        // Reserve a free port for the server to bind to
+        let address: SocketAddr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let url = format!("http://{}", address);
+
+        let child = Command::new(env!("CARGO_BIN_EXE_backend"))
+            .arg("start")
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .env("BIND_ADDR", address.to_string())
+            .env("DATABASE_URL", &database_url)
+            .env("JWT_SECRET", "oidc-test-secret")
+            .env("MAIL_TRANSPORT", "directory")
+            .env("MAIL_DIRECTORY", directory.join("mail"))
+            .env("FRONTEND_URL", FRONTEND_URL)
+            .env("OIDC_ISSUER", &provider.issuer)
+            .env("OIDC_CLIENT_ID", CLIENT_ID)
+            .env("OIDC_REDIRECT_URL", format!("{}/api/v1/users/oidc/callback", url))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let server = Self {
+            url,
+            http: reqwest::Client::builder().redirect(Policy::none()).build().unwrap(),
+            pool,
+            provider,
+            directory,
+            child,
+        };
+
+        for _ in 0..100 {
+            if tokio::net::TcpStream::connect(address).await.is_ok() {
+                return server;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        panic!("the server did not start");
+    }
+
+    async fn authorize(&self) -> Login {
+        let response = self.http
+            .get(format!("{}/api/v1/users/oidc/authorize", self.url))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+        let location = Url::parse(response.headers()[LOCATION].to_str().unwrap()).unwrap();
+        let params: HashMap<_, _> = location.query_pairs().into_owned().collect();
+
+        assert_eq!(location.as_str().split('?').next(), Some(format!("{}/authorize", self.provider.issuer).as_str()));
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["client_id"], CLIENT_ID);
+        assert_eq!(params["code_challenge_method"], "S256");
+
+        let cookie = response.headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .map(|cookie| cookie.to_str().unwrap())
+            .find(|cookie| cookie.starts_with("oidc_state="))
+            .expect("the state cookie is set")
+            .split(';')
+            .next()
+            .unwrap()
+            .to_string();
+
+        assert_eq!(cookie, format!("oidc_state={}", params["state"]));
+
+        Login {
+            state: params["state"].clone(),
+            nonce: params["nonce"].clone(),
+            code_challenge: params["code_challenge"].clone(),
+            cookie,
+        }
+    }
+
+    async fn callback(&self, state: &str, code: &str, cookie: Option<&str>) -> reqwest::Response {
+        let mut request = self.http
+            .get(format!("{}/api/v1/users/oidc/callback", self.url))
+            .query(&[("state", state), ("code", code)]);
+
+        if let Some(cookie) = cookie {
+            request = request.header("cookie", cookie);
+        }
+
+        request.send().await.unwrap()
+    }
+
+    /// Logs in with an id token signed by `key`, after `change` has been
+    /// applied to its claims
+    ///
+    async fn log_in(&self, key: &str, change: impl FnOnce(&mut Value)) -> reqwest::Response {
+        let login = self.authorize().await;
+
+        let mut claims = self.provider.claims(&login.nonce);
+        change(&mut claims);
+
+        let code = self.provider.grant(&login.code_challenge, sign(&claims, key));
+
+        self.callback(&login.state, &code, Some(&login.cookie)).await
+    }
+
+    async fn register(&self) -> String {
+        let response = self.http
+            .post(format!("{}/api/v1/users/register", self.url))
+            .json(&json!({ "username": "local_user", "password": PASSWORD, "email": EMAIL }))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        response.json::<Value>().await.unwrap()["id"].as_str().unwrap().to_string()
+    }
+
+    async fn users(&self) -> Vec<(String, bool)> {
+        sqlx::query_as("SELECT id, verified FROM users WHERE email = $1")
+            .bind(EMAIL)
+            .fetch_all(&self.pool)
+            .await
+            .unwrap()
+    }
+
+    async fn identities(&self) -> Vec<String> {
+        sqlx::query_scalar("SELECT user_id FROM user_identities WHERE issuer = $1")
+            .bind(&self.provider.issuer)
+            .fetch_all(&self.pool)
+            .await
+            .unwrap()
+    }
+}
+
+fn assert_logged_in(response: &reqwest::Response) {
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()[LOCATION], FRONTEND_URL);
+    assert!(response.headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .any(|cookie| cookie.to_str().unwrap().starts_with("token=")));
+}
+
+#[tokio::test]
+async fn registers_new_users() {
+    let server = TestServer::start().await;
+
+    let response = server.log_in(SIGNING_KEY, |_| ()).await;
+    assert_logged_in(&response);
+
+    let users = server.users().await;
+    assert_eq!(users.len(), 1);
+    assert!(users[0].1, "the provider verified the address");
+    assert_eq!(server.identities().await, vec![users[0].0.clone()]);
+
+    // Logging in again finds the same user through the identity
+    let response = server.log_in(SIGNING_KEY, |_| ()).await;
+    assert_logged_in(&response);
+
+    assert_eq!(server.users().await.len(), 1);
+    assert_eq!(server.identities().await.len(), 1);
+}
+
+#[tokio::test]
+async fn exchanges_the_code_with_the_pkce_verifier() {
+    let server = TestServer::start().await;
+
+    let login = server.authorize().await;
+    let claims = server.provider.claims(&login.nonce);
+
+    // A code issued for another login's challenge can't be exchanged
+    let other = server.authorize().await;
+    let code = server.provider.grant(&other.code_challenge, sign(&claims, SIGNING_KEY));
+
+    let response = server.callback(&login.state, &code, Some(&login.cookie)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(server.users().await.is_empty());
+}
+
+#[tokio::test]
+async fn requires_the_state_cookie() {
+    let server = TestServer::start().await;
+
+    let login = server.authorize().await;
+    let claims = server.provider.claims(&login.nonce);
+    let code = server.provider.grant(&login.code_challenge, sign(&claims, SIGNING_KEY));
+
+    let response = server.callback(&login.state, &code, None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // The cookie of a login started elsewhere doesn't match the state
+    let other = server.authorize().await;
+
+    let response = server.callback(&login.state, &code, Some(&other.cookie)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = server.callback(&login.state, &code, Some(&login.cookie)).await;
+    assert_logged_in(&response);
+
+    // Each login can only be completed once
+    let response = server.callback(&login.state, &code, Some(&login.cookie)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn rejects_nonce_mismatch() {
+    let server = TestServer::start().await;
+
+    let response = server.log_in(SIGNING_KEY, |claims| claims["nonce"] = json!("another-nonce")).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = server.log_in(SIGNING_KEY, |claims| {
+        claims.as_object_mut().unwrap().remove("nonce");
+    }).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    assert!(server.users().await.is_empty());
+}
+
+#[tokio::test]
+async fn rejects_untrusted_signature() {
+    let server = TestServer::start().await;
+
+    let response = server.log_in(UNTRUSTED_KEY, |_| ()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    assert!(server.users().await.is_empty());
+}
+
+#[tokio::test]
+async fn rejects_other_audience_or_issuer() {
+    let server = TestServer::start().await;
+
+    let response = server.log_in(SIGNING_KEY, |claims| claims["aud"] = json!("another-client")).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = server.log_in(SIGNING_KEY, |claims| claims["iss"] = json!("http://another-issuer.test")).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = server.log_in(SIGNING_KEY, |claims| claims["exp"] = json!(chrono::Utc::now().timestamp() - 600)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    assert!(server.users().await.is_empty());
+}
+
+#[tokio::test]
+async fn rejects_unverified_email() {
+    let server = TestServer::start().await;
+
+    let response = server.log_in(SIGNING_KEY, |claims| claims["email_verified"] = json!(false)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    assert!(server.users().await.is_empty());
+    assert!(server.identities().await.is_empty());
+}
+
+#[tokio::test]
+async fn links_verified_account() {
+    let server = TestServer::start().await;
+
+    let user_id = server.register().await;
+
+    sqlx::query("UPDATE users SET verified = true WHERE id = $1")
+        .bind(&user_id)
+        .execute(&server.pool)
+        .await
+        .unwrap();
+
+    let response = server.log_in(SIGNING_KEY, |_| ()).await;
+    assert_logged_in(&response);
+
+    assert_eq!(server.users().await, vec![(user_id.clone(), true)]);
+    assert_eq!(server.identities().await, vec![user_id]);
+}
+
+#[tokio::test]
+async fn refuses_to_link_unverified_account() {
+    let server = TestServer::start().await;
+
+    let user_id = server.register().await;
+
+    let response = server.log_in(SIGNING_KEY, |_| ()).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // The account is left as it was, still waiting to be verified
+    assert_eq!(server.users().await, vec![(user_id, false)]);
+    assert!(server.identities().await.is_empty());
+}